@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use image::ImageResult;
+
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+// Image files in a folder, sorted by name so numbered frames play in order
+pub fn frames_in_folder(folder: impl AsRef<Path>) -> std::io::Result<Vec<String>> {
+    let mut frames = std::fs::read_dir(folder)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+                })
+        })
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    frames.sort();
+    Ok(frames)
+}
+
+// Cuts a horizontal strip of square frames into separate images
+// The frames are written next to the other generated images so they can be loaded as textures
+pub fn split_sprite_sheet(path: &str) -> ImageResult<Vec<String>> {
+    let sheet = image::open(path)?.to_rgba8();
+    let frame_size = sheet.height();
+    let stem = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let directory = std::env::temp_dir().join("wfc_frames");
+    std::fs::create_dir_all(&directory)?;
+
+    (0..sheet.width() / frame_size.max(1))
+        .map(|frame| {
+            let frame_path = directory.join(format!("{stem}_{frame}.png"));
+            image::imageops::crop_imm(&sheet, frame * frame_size, 0, frame_size, frame_size)
+                .to_image()
+                .save(&frame_path)?;
+            Ok(frame_path.to_string_lossy().into_owned())
+        })
+        .collect()
+}
@@ -0,0 +1,143 @@
+use std::{collections::HashMap, fs::File, io::Write, path::Path};
+
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::tileset::Tileset;
+
+// Name of the rules inside a bundle, the images sit next to it in the images folder
+const RULES_FILE: &str = "tileset.json";
+
+// Images and rules are both compressed
+fn compressed() -> FileOptions {
+    FileOptions::default().compression_method(CompressionMethod::Deflated)
+}
+
+// Copies an image into the archive once, and returns the path it has in there
+fn store_image(
+    archive: &mut ZipWriter<File>,
+    stored: &mut HashMap<String, String>,
+    path: &str,
+) -> std::io::Result<String> {
+    if let Some(bundled) = stored.get(path) {
+        return Ok(bundled.clone());
+    }
+    // Images of different folders may share a name, so every one is numbered
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let bundled = format!("images/{}_{file_name}", stored.len());
+    archive
+        .start_file(bundled.clone(), compressed())
+        .map_err(std::io::Error::other)?;
+    archive.write_all(&std::fs::read(path)?)?;
+    stored.insert(path.to_owned(), bundled.clone());
+    Ok(bundled)
+}
+
+// Packs a tileset and every image it uses, animation frames included, into one archive
+// Images are referred to relative to the archive, so it can be opened on any machine
+pub fn export_bundle(tileset: &Tileset, path: impl AsRef<Path>) -> std::io::Result<()> {
+    let mut archive = ZipWriter::new(File::create(path)?);
+    let mut stored = HashMap::new();
+    let mut bundled = tileset.clone();
+    for image in &mut bundled.images {
+        image.path = store_image(&mut archive, &mut stored, &image.path)?;
+        for frame in &mut image.frames {
+            *frame = store_image(&mut archive, &mut stored, frame)?;
+        }
+    }
+
+    archive
+        .start_file(RULES_FILE, compressed())
+        .map_err(std::io::Error::other)?;
+    archive.write_all(bundled.to_text()?.as_bytes())?;
+    archive.finish().map_err(std::io::Error::other)?;
+    Ok(())
+}
+
+// Unpacks a bundle into the temporary folder and reads the tileset in it
+// The images are found next to it, like for any tileset file
+pub fn import_bundle(path: impl AsRef<Path>) -> std::io::Result<Tileset> {
+    let path = path.as_ref();
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let directory = std::env::temp_dir().join("wfc_bundles").join(stem);
+    // Leftovers of an earlier import of the same bundle would be mixed in otherwise
+    if directory.exists() {
+        std::fs::remove_dir_all(&directory)?;
+    }
+    std::fs::create_dir_all(&directory)?;
+    ZipArchive::new(File::open(path)?)
+        .and_then(|mut archive| archive.extract(&directory))
+        .map_err(std::io::Error::other)?;
+
+    let rules = directory.join(RULES_FILE);
+    if !rules.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("The bundle has no {RULES_FILE}"),
+        ));
+    }
+    Tileset::load(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tile::{Direction, TileData},
+        tileset::TileImage,
+    };
+
+    #[test]
+    fn bundle_round_trip_keeps_tiles_and_images() {
+        let directory = std::env::temp_dir()
+            .join("wfc_tests")
+            .join("bundle_round_trip");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(directory.join("a")).unwrap();
+        std::fs::create_dir_all(directory.join("b")).unwrap();
+
+        // Two images with the same name in different folders
+        let mut tileset = Tileset::default();
+        for (index, folder) in ["a", "b"].into_iter().enumerate() {
+            let path = directory.join(folder).join("tile.png");
+            image::RgbaImage::from_pixel(2, 2, image::Rgba([index as u8 * 200, 0, 0, 255]))
+                .save(&path)
+                .unwrap();
+            tileset.images.push(TileImage::new(
+                path.to_string_lossy().into_owned(),
+                format!("tile {folder}"),
+            ));
+            let mut tile = TileData::new(index);
+            tile.weight = index as f32 + 1.0;
+            tileset.tiles.push(tile);
+        }
+        let ids = [tileset.tiles[0].id, tileset.tiles[1].id];
+        tileset.tiles[0]
+            .east_valid_tiles
+            .push((ids[1], Direction::West));
+        tileset.tiles[1]
+            .west_valid_tiles
+            .push((ids[0], Direction::East));
+
+        let archive = directory.join("bundle.zip");
+        export_bundle(&tileset, &archive).unwrap();
+        let imported = import_bundle(&archive).unwrap();
+
+        assert_eq!(imported.tiles, tileset.tiles);
+        assert_eq!(imported.adjacency, tileset.adjacency);
+        assert_eq!(imported.images.len(), tileset.images.len());
+        for (imported, original) in imported.images.iter().zip(&tileset.images) {
+            assert_eq!(imported.name, original.name);
+            assert_ne!(imported.path, original.path);
+            assert_eq!(
+                std::fs::read(&imported.path).unwrap(),
+                std::fs::read(&original.path).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use image::RgbaImage;
+
+// Writes the image on the system clipboard to a file, so it can be loaded like any other tile
+// Returns None when the clipboard doesn't hold an image
+pub fn save_clipboard_image() -> std::io::Result<Option<String>> {
+    let mut clipboard = arboard::Clipboard::new().map_err(std::io::Error::other)?;
+    let pasted = match clipboard.get_image() {
+        Ok(pasted) => pasted,
+        Err(arboard::Error::ContentNotAvailable) => return Ok(None),
+        Err(err) => return Err(std::io::Error::other(err)),
+    };
+    let image = RgbaImage::from_raw(
+        pasted.width as u32,
+        pasted.height as u32,
+        pasted.bytes.into_owned(),
+    )
+    .ok_or_else(|| std::io::Error::other("The pasted image has an unexpected size"))?;
+
+    // Pasted images have no name, so they are told apart by the time they were pasted at
+    let directory = std::env::temp_dir().join("wfc_tiles");
+    std::fs::create_dir_all(&directory)?;
+    let pasted_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_millis());
+    let path = directory.join(format!("pasted_{pasted_at}.png"));
+    image.save(&path).map_err(std::io::Error::other)?;
+    Ok(Some(path.to_string_lossy().into_owned()))
+}
+
+thread_local! {
+    // Some platforms only offer copied content while the clipboard that copied it is alive
+    static CLIPBOARD: RefCell<Option<arboard::Clipboard>> = const { RefCell::new(None) };
+}
+
+// Puts an image on the system clipboard, transparent pixels stay transparent
+pub fn copy_image_to_clipboard(image: &RgbaImage) -> std::io::Result<()> {
+    CLIPBOARD.with_borrow_mut(|clipboard| {
+        if clipboard.is_none() {
+            *clipboard = Some(arboard::Clipboard::new().map_err(std::io::Error::other)?);
+        }
+        clipboard
+            .as_mut()
+            .unwrap()
+            .set_image(arboard::ImageData {
+                width: image.width() as usize,
+                height: image.height() as usize,
+                bytes: Cow::Borrowed(image.as_raw()),
+            })
+            .map_err(std::io::Error::other)
+    })
+}
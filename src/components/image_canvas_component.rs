@@ -1,8 +1,14 @@
 use std::{
-    any::TypeId,
+    any::{Any, TypeId},
+    collections::{hash_map::DefaultHasher, HashMap, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    path::Path,
     rc::Rc,
-    sync::{Arc, Mutex},
-    time::Instant,
+    sync::{
+        mpsc::{self, Receiver, TryRecvError},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
 
 use gamezap::{
@@ -15,322 +21,403 @@ use gamezap::{
     EngineDetails, EngineSystems,
 };
 
-use rand::Rng;
+use rfd::FileDialog;
 use wgpu::{Device, Queue};
 
-use super::tile_creation_component::{
-    Direction, ImageData, TileConnection, TileCreationComponent, TileData,
+use crate::{
+    clipboard::copy_image_to_clipboard,
+    layer::Layer,
+    map::{Map, MapLayer, MapTile},
+    recording::{BoardRenderer, FrameRecorder},
+    sample::learn_from_board,
+    tile::{tile_positions, Direction, TileData},
+    wfc::{CollapseBias, GenerationSettings, PhaseTimings, Solver, SolverError, DIRECTIONS},
 };
 
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
-struct PossibleConnections {
-    north_connections: Vec<TileConnection>,
-    south_connections: Vec<TileConnection>,
-    east_connections: Vec<TileConnection>,
-    west_connections: Vec<TileConnection>,
-}
+use super::tile_creation_component::{ImageData, TilesetHandle};
 
-impl PossibleConnections {
-    // Count all possible states of a location
-    // Quantifying entropy
-    fn total_len(&self) -> usize {
-        self.north_connections.len()
-            + self.south_connections.len()
-            + self.east_connections.len()
-            + self.west_connections.len()
-    }
+// Candidate counts before and after of every open cell a placement would narrow down
+type NarrowedCells = HashMap<(usize, usize), (usize, usize)>;
 
-    // Randomly chooses a tile from the possible states of the location
-    fn random_tile<'a>(&'a self, tiles: &'a [TileData]) -> &'a TileData {
-        if self == &Self::default() {
-            return &tiles[0];
-        }
-        let mut rng = rand::thread_rng();
-        loop {
-            let dir_f: f32 = rng.gen();
-            let dir = (dir_f * 4.0) as usize;
-            let temp_vec = Vec::new();
-            let arr = match dir {
-                0 => &self.north_connections,
-                1 => &self.south_connections,
-                2 => &self.east_connections,
-                3 => &self.west_connections,
-                _ => &temp_vec,
-            };
+// Candidates of every cell with their chances, row by row
+type CellDistributions = Vec<Vec<Vec<(usize, f32)>>>;
 
-            if arr.is_empty() {
-                continue;
-            }
-            let index_f: f32 = rng.gen();
-            let index = (index_f * arr.len() as f32) as usize;
-            return &tiles[arr[index].0];
-        }
-    }
-}
+// Frames the shown timings are averaged over
+const TIMING_FRAMES: usize = 120;
+
+// Shortest step the stall limit is counted in, about a frame at 60 fps, for boards without a step interval
+const STALL_STEP_MS: u64 = 16;
+
+// Canvas sizes offered in the settings as (width, height), anything else is a custom size
+const SIZE_PRESETS: [(usize, usize); 7] = [
+    (8, 8),
+    (16, 16),
+    (32, 32),
+    (64, 64),
+    (32, 18),
+    (64, 36),
+    (128, 72),
+];
 
 #[derive(Debug, Clone)]
 pub struct ImageCanvasComponent {
     parent: EntityId,
     id: ComponentId,
-    current_tile_set: Vec<TileData>,
-    canvas_connections: [[PossibleConnections; 10]; 10],
-    canvas_representation: [[Option<TileData>; 10]; 10],
+    concept_ids: Vec<String>,
+    tileset: TilesetHandle,
+    window_position: [f32; 2],
+    settings: GenerationSettings,
+    solver: Solver,
     last_update: Instant,
+    // Cell changed by the latest step, outlined on the canvas
+    last_collapsed: Option<(usize, usize)>,
+    // Collapses done by one press of the step button
+    steps_at_once: u32,
+    // Width of a rendered cell in pixels
+    zoom: f32,
+    // Lines drawn between the cells, to see where the borders of seamless tiles are
+    show_grid: bool,
+    grid_color: [f32; 4],
+    grid_thickness: f32,
+    // Painting restricts cells to the brush category, an empty brush clears them
+    painting_regions: bool,
+    brush: String,
+    // Clicking an open cell places the paint tile there, number keys pick one of the first ten
+    painting_tiles: bool,
+    paint_tile: usize,
+    // The right mouse button keeps the forbidden tile out of cells instead of selecting them, Ctrl allows it again
+    forbidding_tiles: bool,
+    forbidden_tile: usize,
+    // Image indices of the last board that got anywhere, compared against the current one
+    previous_run: Option<Vec<Vec<Option<usize>>>>,
+    // Seed the remembered board was generated from
+    last_seed: Option<u64>,
+    show_diff: bool,
+    // Open cells show a blend of their candidates instead of the first image
+    preview_candidates: bool,
+    // Chances the blend is drawn with, along with the revision of the board they were worked out for
+    blend: Option<(u64, CellDistributions)>,
+    // Number of tiles still possible, written in the corner of every open cell
+    show_candidate_counts: bool,
+    // Tile whose chance of being placed is tinted over the open cells
+    heatmap_tile: Option<usize>,
+    canvas_error: Option<String>,
+    // Cell shown in the tile inspector popup
+    inspected_cell: Option<(usize, usize)>,
+    // Open cell the collapse menu was opened on
+    overridden_cell: Option<(usize, usize)>,
+    // Cell and tile hovered in the collapse menu, with the open cells placing it would narrow down
+    what_if: Option<((usize, usize), usize, NarrowedCells)>,
+    // Open cell whose candidates and their chances are listed in the distribution window
+    distribution_cell: Option<(usize, usize)>,
+    // Corners of the rectangle selected with the right mouse button, the first one is where the drag started
+    selection: Option<((usize, usize), (usize, usize))>,
+    selecting: bool,
+    // Time the generation has spent on an unfinished board since the last collapse,
+    // and when the previous frame was counted
+    stalled_for: Duration,
+    last_frame: Instant,
+    // Boards of the layers on top of the main one, bottom layer first
+    layer_solvers: Vec<Solver>,
+    // Time spent in the algorithm and on drawing the canvas in each of the latest frames
+    frame_timings: VecDeque<(PhaseTimings, Duration)>,
+    // Layers and main board the layer boards were generated from
+    layers_built_from: Vec<Layer>,
+    layers_built_on: Option<Vec<Vec<Option<usize>>>>,
+    // Writes a frame after every step while set
+    frame_recorder: Option<FrameRecorder>,
+    // Board of a generation running in the background, sent back once it is done
+    solving: Option<Rc<Receiver<Result<Solver, SolverError>>>>,
 }
 
 impl ImageCanvasComponent {
-    // Removes all of the duplicate elements from a slice
-    // (This could be done with a hash set but I dont want to do that)
-    fn remove_dupes(arr: &[TileConnection]) -> Vec<TileConnection> {
-        let mut vec = Vec::with_capacity(arr.len());
-        for elem in arr {
-            if !vec.contains(elem) {
-                vec.push(*elem);
-            }
-        }
-        vec.shrink_to_fit();
-        vec
-    }
+    // The tileset is read through the handle of the tile creation component it comes from
+    pub fn new(
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        settings: GenerationSettings,
+        tileset: TilesetHandle,
+        window_position: [f32; 2],
+    ) -> Self {
+        let mut comp = Self {
+            tileset,
+            window_position,
+            settings,
+            solver: Solver::new(Vec::new(), settings),
+            ..Default::default()
+        };
 
-    // Calculates the initial entropy of the board
-    fn fill_representation_array(&mut self, tiles: &[TileData]) {
-        let all_north_connections = Self::remove_dupes(
-            &tiles
-                .iter()
-                .flat_map(|tile| tile.north_valid_tiles.clone())
-                .collect::<Vec<_>>(),
-        );
-        let all_south_connections = Self::remove_dupes(
-            &tiles
-                .iter()
-                .flat_map(|tile| tile.south_valid_tiles.clone())
-                .collect::<Vec<_>>(),
-        );
-        let all_east_connections = Self::remove_dupes(
-            &tiles
-                .iter()
-                .flat_map(|tile| tile.east_valid_tiles.clone())
-                .collect::<Vec<_>>(),
-        );
-        let all_west_connections = Self::remove_dupes(
-            &tiles
-                .iter()
-                .flat_map(|tile| tile.west_valid_tiles.clone())
-                .collect::<Vec<_>>(),
-        );
+        let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
 
-        for (row_index, row) in self.canvas_connections.iter_mut().enumerate() {
-            for (col_index, slot) in row.iter_mut().enumerate() {
-                let mut valid_connections: PossibleConnections = PossibleConnections::default();
-                if row_index != 0 {
-                    valid_connections
-                        .north_connections
-                        .append(&mut all_north_connections.clone());
-                }
-                if row_index != 9 {
-                    valid_connections
-                        .south_connections
-                        .append(&mut all_south_connections.clone());
-                }
-                if col_index != 0 {
-                    valid_connections
-                        .west_connections
-                        .append(&mut all_west_connections.clone());
-                }
-                if col_index != 9 {
-                    valid_connections
-                        .east_connections
-                        .append(&mut all_east_connections.clone());
-                }
+        concepts.insert("generation_settings".to_string(), Box::new(settings));
 
-                *slot = valid_connections;
-            }
-        }
+        comp.register_component(concept_manager, concepts);
+
+        comp
     }
+}
 
-    // Calculates the tile with the lowest entropy (lowest amount of possible states)
-    fn get_lowest_entropy(&self) -> Option<(usize, usize)> {
-        let mut rng = rand::thread_rng();
-
-        let x: usize = rng.gen_range(0..10);
-        let y: usize = rng.gen_range(0..10);
-
-        let mut lowest_position = (x, y);
-        let mut lowest_val = &self.canvas_connections[x][y];
-        for (row_index, row) in self.canvas_connections.iter().enumerate() {
-            for (col_index, val) in row.iter().enumerate() {
-                if val.total_len() < lowest_val.total_len()
-                    && self.canvas_representation[row_index][col_index].is_none()
-                {
-                    lowest_val = val;
-                    lowest_position = (row_index, col_index);
+impl ImageCanvasComponent {
+    // Collapses one cell, outlining it and recording the board if frames are being recorded
+    fn step(&mut self) -> Result<Option<(usize, usize)>, SolverError> {
+        let pos = self.solver.step()?;
+        if pos.is_some() {
+            self.last_collapsed = pos;
+            // The preview of the collapse menu is worked out again for the new board
+            self.what_if = None;
+            if let Some(recorder) = &mut self.frame_recorder {
+                if let Err(err) = recorder.record(self.solver.representation()) {
+                    self.canvas_error = Some(err.to_string());
+                    self.frame_recorder = None;
                 }
             }
         }
-        if lowest_position == (x, y) && self.canvas_representation[x][y].is_some() {
-            return None;
-        }
-        Some(lowest_position)
+        Ok(pos)
     }
 
-    // Checks to see if vec2 shares any elements of vec1
-    fn do_tile_arrs_overlap(vec1: &[TileConnection], vec2: &[TileConnection]) -> bool {
-        for elem in vec1 {
-            if vec2.contains(elem) {
-                return true;
+    // Places a tile on a copy of the board and finds the open cells that would lose candidates
+    fn what_if(&self, pos: (usize, usize), tile: usize) -> NarrowedCells {
+        let mut preview = self.solver.clone();
+        if preview.place_tile(pos, tile).is_err() {
+            return HashMap::new();
+        }
+        let mut narrowed = HashMap::new();
+        for (row_index, row) in preview.representation().iter().enumerate() {
+            for (col_index, cell) in row.iter().enumerate() {
+                let cell_pos = (row_index, col_index);
+                if cell.is_some() || cell_pos == pos {
+                    continue;
+                }
+                let (before, after) = (self.solver.entropy(cell_pos), preview.entropy(cell_pos));
+                if after < before {
+                    narrowed.insert(cell_pos, (before, after));
+                }
             }
         }
-        false
+        narrowed
     }
 
-    // Calculates how well a tile matches entropy at a position
-    fn tile_confidence(tile: &TileData, connections: &PossibleConnections) -> f32 {
-        let mut confidence = 0.0;
-        if Self::do_tile_arrs_overlap(&tile.north_valid_tiles, &connections.north_connections) {
-            confidence += 0.25;
-        }
-        if Self::do_tile_arrs_overlap(&tile.south_valid_tiles, &connections.south_connections) {
-            confidence += 0.25;
+    // Puts the board on the system clipboard as an image, drawn like the recorded frames
+    fn copy_canvas_image(&self, images: &[ImageData], pattern_size: u32) -> std::io::Result<()> {
+        let renderer = BoardRenderer::new(images, pattern_size).map_err(std::io::Error::other)?;
+        copy_image_to_clipboard(&renderer.render(self.solver.representation()))
+    }
+
+    // Restarts the generation, keeping the painted regions and forbidden tiles that still fit on the canvas
+    // and the edge constraints that still refer to existing tiles
+    fn rebuild_solver(&mut self, tiles: Vec<TileData>) {
+        let mut mask = self.solver.mask().to_vec();
+        mask.resize(self.settings.height, Vec::new());
+        for row in &mut mask {
+            row.resize(self.settings.width, None);
         }
-        if Self::do_tile_arrs_overlap(&tile.east_valid_tiles, &connections.east_connections) {
-            confidence += 0.25;
+        let mut forbidden = self.solver.forbidden().to_vec();
+        forbidden.resize(self.settings.height, Vec::new());
+        for row in &mut forbidden {
+            row.resize(self.settings.width, HashSet::new());
+            for cell in row {
+                cell.retain(|index| *index < tiles.len());
+            }
         }
-        if Self::do_tile_arrs_overlap(&tile.west_valid_tiles, &connections.west_connections) {
-            confidence += 0.25;
+        let mut edge_constraints = self.solver.edge_constraints().clone();
+        for allowed in edge_constraints.values_mut() {
+            allowed.retain(|index| *index < tiles.len());
         }
+        edge_constraints.retain(|_, allowed| !allowed.is_empty());
 
-        confidence
-    }
-
-    // Reads surrounding tiles and converts the entropy into a set of possible states
-    fn get_possible_tiles(&self, pos: (usize, usize)) -> Vec<TileData> {
-        let mut tiles = Vec::new();
-        if pos.0 > 0 {
-            let tile = &self.canvas_representation[pos.0 - 1][pos.1];
-            if let Some(tile) = tile {
-                for (index, _) in &tile.south_valid_tiles {
-                    tiles.push(self.current_tile_set[*index].clone());
-                }
-            }
+        self.remember_run();
+        // A background generation would come back with outdated settings
+        self.solving = None;
+        self.selection = None;
+        let recording = self.solver.is_recording();
+        self.solver = Solver::new(tiles, self.settings);
+        self.blend = None;
+        self.solver.set_recording(recording);
+        self.solver.set_mask(mask);
+        self.solver.set_forbidden(forbidden);
+        self.solver.set_edge_constraints(edge_constraints);
+        self.last_collapsed = None;
+        self.stalled_for = Duration::ZERO;
+        self.distribution_cell = None;
+        self.overridden_cell = None;
+        self.what_if = None;
+        self.heatmap_tile = self
+            .heatmap_tile
+            .filter(|&index| index < self.solver.tiles().len());
+        if self.paint_tile >= self.solver.tiles().len() {
+            self.paint_tile = 0;
         }
-        if pos.0 < 9 {
-            let tile = &self.canvas_representation[pos.0 + 1][pos.1];
-            if let Some(tile) = tile {
-                for (index, _) in &tile.north_valid_tiles {
-                    tiles.push(self.current_tile_set[*index].clone());
-                }
-            }
+        if self.forbidden_tile >= self.solver.tiles().len() {
+            self.forbidden_tile = 0;
         }
-        if pos.1 > 0 {
-            let tile = &self.canvas_representation[pos.0][pos.1 - 1];
-            if let Some(tile) = tile {
-                for (index, _) in &tile.east_valid_tiles {
-                    tiles.push(self.current_tile_set[*index].clone());
-                }
+    }
+
+    // Generates every layer on top of the one below it, starting from the main board
+    // Each layer gets its own seed derived from the one of the main board
+    fn generate_layers(&mut self, layers: &[Layer]) {
+        self.layer_solvers.clear();
+        for (index, layer) in layers.iter().enumerate() {
+            let below = match self.layer_solvers.last() {
+                Some(solver) => solver.representation().to_vec(),
+                None => self.solver.representation().to_vec(),
+            };
+            let settings = GenerationSettings {
+                seed: self.solver.seed().wrapping_add(index as u64 + 1),
+                ..self.settings
+            };
+            let mut solver = Solver::new(layer.tiles.clone(), settings);
+            solver.set_restrictions(layer.restrictions(&below));
+            if let Err(err) = solver.solve_all() {
+                self.canvas_error = Some(format!("Layer {}: {err}", layer.name));
+                return;
             }
+            self.layer_solvers.push(solver);
         }
-        if pos.1 < 9 {
-            let tile = &self.canvas_representation[pos.0][pos.1 + 1];
-            if let Some(tile) = tile {
-                for (index, _) in &tile.west_valid_tiles {
-                    tiles.push(self.current_tile_set[*index].clone());
-                }
-            }
+    }
+
+    // Writes the board and its layers as a map other engines can load, along with what their tiles stand for
+    fn export_map_json(
+        &self,
+        path: impl AsRef<Path>,
+        tileset_path: Option<String>,
+        images: &[ImageData],
+        layer_images: &[Vec<ImageData>],
+    ) -> std::io::Result<()> {
+        let mut map = Map::new(
+            tileset_path,
+            map_tiles(self.solver.tiles(), images),
+            self.solver.cells(),
+        );
+        for ((layer, solver), images) in self
+            .layers_built_from
+            .iter()
+            .zip(&self.layer_solvers)
+            .zip(layer_images)
+        {
+            map.layers.push(MapLayer {
+                name: layer.name.clone(),
+                tileset: Some(layer.tileset_path.clone()),
+                tiles: map_tiles(&layer.tiles, images),
+                cells: solver.cells(),
+            });
         }
+        map.save(path)
+    }
 
-        tiles
+    // Keeps the current board around before it is restarted
+    fn remember_run(&mut self) {
+        let board = self.solver.cells();
+        if board.iter().flatten().any(Option::is_some) {
+            self.previous_run = Some(board);
+            self.last_seed = Some(self.solver.seed());
+        }
     }
+}
 
-    // Collapses a single tile into a single tile
-    // Reduces the possible states (entropy) of surrounding tiles
-    fn collapse_tile(
-        &mut self,
-        tile_connections: &PossibleConnections,
-        pos: (usize, usize),
-    ) -> TileData {
-        let possible_tiles = self.get_possible_tiles(pos);
+// Name of a tile in the UI, tiles whose image is missing fall back to their index
+fn tile_label(images: &[ImageData], tile: &TileData, index: usize) -> String {
+    images
+        .get(tile.image_index)
+        .map_or_else(|| format!("{index}"), |image| image.label(index))
+}
 
-        let most_likely_tile = if possible_tiles.is_empty() {
-            tile_connections.random_tile(&self.current_tile_set).clone()
-        } else {
-            // Calculates which tile has the highest confidence
-            let mut most_confident_tile = &possible_tiles[0];
-            let mut highest_confidence =
-                Self::tile_confidence(most_confident_tile, tile_connections);
-
-            for tile in &possible_tiles {
-                let confidence = Self::tile_confidence(tile, tile_connections);
-                if confidence > highest_confidence {
-                    highest_confidence = confidence;
-                    most_confident_tile = tile;
-                }
+// Name, image and rotation of every tile, for exported maps
+fn map_tiles(tiles: &[TileData], images: &[ImageData]) -> Vec<MapTile> {
+    tiles
+        .iter()
+        .enumerate()
+        .map(|(index, tile)| {
+            let image = images.get(tile.image_index);
+            MapTile {
+                name: tile_label(images, tile, index),
+                image: image
+                    .map(|image| image.path().to_owned())
+                    .unwrap_or_default(),
+                rotation: tile.rotation,
             }
+        })
+        .collect()
+}
 
-            most_confident_tile.clone()
-        };
-
-        // Collapses all of the states for the adjacent tiles
-        if pos.0 > 0 {
-            let vec = vec![(most_likely_tile.image_index, Direction::North)];
-            self.canvas_connections[pos.0 - 1][pos.1].south_connections = vec;
-        }
-        if pos.0 < 9 {
-            let vec = vec![(most_likely_tile.image_index, Direction::South)];
-            self.canvas_connections[pos.0 + 1][pos.1].north_connections = vec;
-        }
-        if pos.1 > 0 {
-            let vec = vec![(most_likely_tile.image_index, Direction::West)];
-            self.canvas_connections[pos.0][pos.1 - 1].east_connections = vec;
-        }
-        if pos.1 < 9 {
-            let vec = vec![(most_likely_tile.image_index, Direction::East)];
-            self.canvas_connections[pos.0][pos.1 + 1].west_connections = vec;
-        }
-
-        most_likely_tile
-    }
+// Color a region is drawn with, derived from its name
+fn region_color(category: &str) -> [f32; 4] {
+    let mut hasher = DefaultHasher::new();
+    category.hash(&mut hasher);
+    let hash = hasher.finish();
+    [
+        (hash & 0xff) as f32 / 255.0,
+        ((hash >> 8) & 0xff) as f32 / 255.0,
+        ((hash >> 16) & 0xff) as f32 / 255.0,
+        0.4,
+    ]
 }
 
 impl Default for ImageCanvasComponent {
     fn default() -> Self {
-        let canvas_connections = (0..10)
-            .map(|_| {
-                let inner: [PossibleConnections; 10] = (0..10)
-                    .map(|_| PossibleConnections::default())
-                    .collect::<Vec<_>>()
-                    .try_into()
-                    .unwrap();
-                inner
-            })
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap();
-
-        let canvas_representation = (0..10)
-            .map(|_| {
-                let inner: [Option<TileData>; 10] = (0..10)
-                    .map(|_| None)
-                    .collect::<Vec<_>>()
-                    .try_into()
-                    .unwrap();
-                inner
-            })
-            .collect::<Vec<_>>()
-            .try_into()
-            .unwrap();
+        let settings = GenerationSettings::default();
 
         Self {
             parent: EntityId::MAX,
             id: (EntityId::MAX, TypeId::of::<Self>(), 0),
-            current_tile_set: Vec::new(),
-            canvas_connections,
-            canvas_representation,
+            concept_ids: Vec::new(),
+            tileset: TilesetHandle::new(0),
+            window_position: [500.0, 20.0],
+            settings,
+            solver: Solver::new(Vec::new(), settings),
             last_update: Instant::now(),
+            last_collapsed: None,
+            steps_at_once: 10,
+            zoom: 50.0,
+            show_grid: false,
+            grid_color: [0.0, 0.0, 0.0, 0.5],
+            grid_thickness: 1.0,
+            painting_regions: false,
+            painting_tiles: false,
+            forbidding_tiles: false,
+            forbidden_tile: 0,
+            paint_tile: 0,
+            brush: String::new(),
+            previous_run: None,
+            last_seed: None,
+            show_diff: false,
+            preview_candidates: true,
+            blend: None,
+            show_candidate_counts: false,
+            heatmap_tile: None,
+            canvas_error: None,
+            inspected_cell: None,
+            distribution_cell: None,
+            overridden_cell: None,
+            what_if: None,
+            selection: None,
+            selecting: false,
+            stalled_for: Duration::ZERO,
+            last_frame: Instant::now(),
+            layer_solvers: Vec::new(),
+            frame_timings: VecDeque::new(),
+            layers_built_from: Vec::new(),
+            layers_built_on: None,
+            frame_recorder: None,
+            solving: None,
         }
     }
 }
 
 impl ComponentSystem for ImageCanvasComponent {
+    // Registers all of the component concepts
+    // This makes it possible to share data between components
+    fn register_component(
+        &mut self,
+        concept_manager: Rc<Mutex<ConceptManager>>,
+        data: HashMap<String, Box<dyn Any>>,
+    ) {
+        self.concept_ids = data.keys().cloned().collect();
+
+        concept_manager
+            .lock()
+            .unwrap()
+            .register_component_concepts(self.id, data);
+    }
+
     // Main update method
     // Called every frame
     fn update(
@@ -345,32 +432,82 @@ impl ComponentSystem for ImageCanvasComponent {
         _entities: &mut Vec<Entity>,
     ) {
         let concept_manager = concept_manager.lock().unwrap();
-        let tiles = concept_manager
-            .get_concept::<Vec<TileData>>(
-                (0, TypeId::of::<TileCreationComponent>(), 0),
-                "loaded_tiles".to_string(),
-            )
-            .unwrap()
-            .clone();
+        let tiles = self.tileset.tiles(&concept_manager).clone();
+
+        self.frame_timings.push_back(Default::default());
+        if self.frame_timings.len() > TIMING_FRAMES {
+            self.frame_timings.pop_front();
+        }
+
+        if tiles != self.solver.tiles() {
+            self.rebuild_solver(tiles);
+        }
+
+        let now = Instant::now();
+        let frame_time = now - self.last_frame;
+        self.last_frame = now;
 
-        if tiles != self.current_tile_set {
-            self.fill_representation_array(&tiles);
-            self.current_tile_set = tiles;
+        // Swaps in the board of a background generation once it finishes
+        // The animated generation waits until then
+        if let Some(receiver) = &self.solving {
+            match receiver.try_recv() {
+                Ok(Ok(solver)) => {
+                    self.solver = solver;
+                    self.blend = None;
+                    self.last_collapsed = None;
+                    self.solving = None;
+                }
+                Ok(Err(err)) => {
+                    self.canvas_error = Some(err.to_string());
+                    self.solving = None;
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => self.solving = None,
+            }
         }
 
         // Main algorithm loop
         // Delay added to help visualize the collapse
-        if !self.current_tile_set.is_empty()
-            && (Instant::now() - self.last_update).as_millis() > 100
-        {
-            let lowest_entropy_pos = self.get_lowest_entropy();
-            if let Some(lowest_entropy_pos) = lowest_entropy_pos {
-                let lowest_entropy_tile =
-                    self.canvas_connections[lowest_entropy_pos.0][lowest_entropy_pos.1].clone();
-                let result = self.collapse_tile(&lowest_entropy_tile, lowest_entropy_pos);
-                self.canvas_representation[lowest_entropy_pos.0][lowest_entropy_pos.1] =
-                    Some(result);
+        // Without a delay several cells are collapsed every frame, up to the cap
+        let mut collapsed_any = false;
+        if (Instant::now() - self.last_update).as_millis() >= self.settings.step_interval as u128 {
+            let collapses = if self.settings.step_interval == 0 {
+                self.settings.max_collapses_per_frame.max(1)
+            } else {
+                1
+            };
+            for _ in 0..collapses {
+                if !matches!(self.step(), Ok(Some(_))) {
+                    break;
+                }
                 self.last_update = Instant::now();
+                collapsed_any = true;
+            }
+        }
+
+        // Tells a finished board apart from one that stopped making progress
+        // Frames spent waiting for the step interval count as well, only a collapse starts over
+        if collapsed_any || self.solver.remaining_cells() == 0 {
+            self.stalled_for = Duration::ZERO;
+        } else {
+            self.stalled_for += frame_time;
+        }
+        if let Some((timings, _)) = self.frame_timings.back_mut() {
+            *timings = self.solver.take_timings();
+        }
+
+        // Layers are generated in one go once the board below them is complete,
+        // and again whenever that board or the layers change
+        let layers = self.tileset.layers(&concept_manager);
+        if layers.is_empty() || self.solver.remaining_cells() > 0 {
+            self.layer_solvers.clear();
+            self.layers_built_on = None;
+        } else {
+            let board = self.solver.cells();
+            if self.layers_built_on.as_ref() != Some(&board) || self.layers_built_from != *layers {
+                self.generate_layers(layers);
+                self.layers_built_from = layers.clone();
+                self.layers_built_on = Some(board);
             }
         }
     }
@@ -386,47 +523,1259 @@ impl ComponentSystem for ImageCanvasComponent {
         _component_map: &mut AllComponents,
         concept_manager: Rc<Mutex<ConceptManager>>,
         _engine_details: Rc<Mutex<EngineDetails>>,
-        _engine_systems: Rc<Mutex<EngineSystems>>,
+        engine_systems: Rc<Mutex<EngineSystems>>,
     ) {
-        let concept_manager = concept_manager.lock().unwrap();
-        let images = concept_manager
-            .get_concept::<Vec<ImageData>>(
-                (0, TypeId::of::<TileCreationComponent>(), 0),
-                "loaded_images".to_string(),
-            )
-            .unwrap()
-            .clone();
+        let draw_started = Instant::now();
+        let mut concept_manager = concept_manager.lock().unwrap();
+        let images = self.tileset.images(&concept_manager).clone();
+        let layer_images = self.tileset.layer_images(&concept_manager).clone();
+        // Patterns of the overlapping model only show their top left pixel
+        let pattern_size = self.tileset.pattern_size(&concept_manager);
+        let uv_max = 1.0 / pattern_size as f32;
 
         // Canvas for the tiles
         if !images.is_empty() {
-            let style = ui_frame.push_style_var(imgui::StyleVar::CellPadding([0.0, 0.0]));
+            let default_size = [
+                (self.settings.width as f32 * self.zoom + 20.0).min(800.0),
+                (self.settings.height as f32 * self.zoom + 60.0).min(700.0),
+            ];
             ui_frame
-                .window("Canvas")
-                .resizable(false)
+                .window(format!("Canvas {}", self.parent))
                 .title_bar(false)
                 .scroll_bar(false)
                 .scrollable(false)
-                .always_auto_resize(true)
-                .position([500.0, 20.0], imgui::Condition::Once)
+                .size(default_size, imgui::Condition::FirstUseEver)
+                .position(self.window_position, imgui::Condition::Once)
                 .build(|| {
-                    let image_table = ui_frame.begin_table("Image table", 10).unwrap();
-                    for row in &self.canvas_representation {
-                        ui_frame.table_next_row();
-                        for tile in row {
-                            // Actual data from collapse
-                            ui_frame.table_next_column();
-                            let image_index = if let Some(tile) = tile {
-                                tile.image_index
+                    // Changing any setting restarts the generation
+                    if ui_frame.collapsing_header("Settings", imgui::TreeNodeFlags::empty()) {
+                        let mut settings = self.settings;
+                        let input_width = ui_frame.push_item_width(100.0);
+                        let mut size_labels = SIZE_PRESETS
+                            .iter()
+                            .map(|(width, height)| format!("{width}x{height}"))
+                            .collect::<Vec<_>>();
+                        size_labels.push("Custom".to_owned());
+                        let mut size = SIZE_PRESETS
+                            .iter()
+                            .position(|&preset| preset == (settings.width, settings.height))
+                            .unwrap_or(SIZE_PRESETS.len());
+                        if ui_frame.combo_simple_string("Size", &mut size, &size_labels) {
+                            if let Some(&(width, height)) = SIZE_PRESETS.get(size) {
+                                settings.width = width;
+                                settings.height = height;
+                            }
+                        }
+                        ui_frame.input_scalar("Width", &mut settings.width).build();
+                        ui_frame
+                            .input_scalar("Height", &mut settings.height)
+                            .build();
+                        ui_frame.input_scalar("Seed", &mut settings.seed).build();
+                        ui_frame.slider("Step interval (ms)", 0, 1000, &mut settings.step_interval);
+                        if settings.step_interval == 0 {
+                            ui_frame.slider(
+                                "Collapses per frame",
+                                1,
+                                1024,
+                                &mut settings.max_collapses_per_frame,
+                            );
+                        }
+                        let noise = ui_frame.begin_disabled(settings.uniform_tie_break);
+                        ui_frame.slider("Noise", 0.0, 1.0, &mut settings.noise);
+                        noise.end();
+                        ui_frame.checkbox("Uniform tie-break", &mut settings.uniform_tie_break);
+                        if ui_frame.is_item_hovered() {
+                            ui_frame.tooltip_text(
+                                "Picks evenly among the cells with the lowest entropy, the noise isn't used",
+                            );
+                        }
+                        let mut collapse_order = CollapseBias::ALL
+                            .iter()
+                            .position(|bias| *bias == settings.collapse_order)
+                            .unwrap_or(0);
+                        if ui_frame.combo(
+                            "Collapse order",
+                            &mut collapse_order,
+                            &CollapseBias::ALL,
+                            |bias| bias.to_string().into(),
+                        ) {
+                            settings.collapse_order = CollapseBias::ALL[collapse_order];
+                        }
+                        if settings.collapse_order == CollapseBias::CornersIn {
+                            ui_frame.checkbox(
+                                "Same tile in every corner",
+                                &mut settings.matching_corners,
+                            );
+                        }
+                        ui_frame
+                            .input_scalar("Stall limit", &mut settings.stall_limit)
+                            .build();
+                        ui_frame
+                            .input_scalar("Max attempts", &mut settings.max_attempts)
+                            .build();
+                        input_width.end();
+                        ui_frame.checkbox("Wrap edges", &mut settings.wrap);
+                        let mut limit_propagation = settings.propagation_radius.is_some();
+                        if ui_frame.checkbox("Limit propagation", &mut limit_propagation) {
+                            settings.propagation_radius = limit_propagation.then_some(4);
+                        }
+                        if let Some(radius) = &mut settings.propagation_radius {
+                            let input_width = ui_frame.push_item_width(100.0);
+                            ui_frame.slider("Propagation radius", 1, 32, radius);
+                            input_width.end();
+                            ui_frame.text_disabled(
+                                "Limited propagation is faster, but may cause more contradictions",
+                            );
+                        }
+                        settings.width = settings.width.clamp(1, 256);
+                        settings.height = settings.height.clamp(1, 256);
+                        settings.max_attempts = settings.max_attempts.max(1);
+                        settings.stall_limit = settings.stall_limit.max(1);
+                        settings.max_collapses_per_frame = settings.max_collapses_per_frame.max(1);
+
+                        if settings != self.settings {
+                            self.settings = settings;
+                            self.rebuild_solver(self.solver.tiles().to_vec());
+                        }
+
+                        let input_width = ui_frame.push_item_width(100.0);
+                        ui_frame.slider("Zoom", 16.0, 128.0, &mut self.zoom);
+                        input_width.end();
+
+                        ui_frame.checkbox("Grid lines", &mut self.show_grid);
+                        if self.show_grid {
+                            ui_frame.same_line();
+                            ui_frame
+                                .color_edit4_config("##Grid color", &mut self.grid_color)
+                                .inputs(false)
+                                .build();
+                            ui_frame.same_line();
+                            let input_width = ui_frame.push_item_width(100.0);
+                            ui_frame.slider("Thickness", 1.0, 4.0, &mut self.grid_thickness);
+                            input_width.end();
+                        }
+
+                        // Tiles allowed along each edge, nothing selected allows any tile
+                        if let Some(_node) = ui_frame.tree_node("Edge constraints") {
+                            let mut edge_constraints = self.solver.edge_constraints().clone();
+                            for direction in [
+                                Direction::North,
+                                Direction::South,
+                                Direction::East,
+                                Direction::West,
+                            ] {
+                                let allowed = edge_constraints.entry(direction).or_default();
+                                let preview = if allowed.is_empty() {
+                                    "Any tile".to_string()
+                                } else {
+                                    format!("{} tiles", allowed.len())
+                                };
+                                let input_width = ui_frame.push_item_width(100.0);
+                                if let Some(combo) =
+                                    ui_frame.begin_combo(format!("{direction}"), preview)
+                                {
+                                    for (index, image) in images.iter().enumerate() {
+                                        let mut selected = allowed.contains(&index);
+                                        if ui_frame
+                                            .selectable_config(format!(
+                                                "{}##{index}",
+                                                image.label(index)
+                                            ))
+                                            .selected(selected)
+                                            .flags(imgui::SelectableFlags::DONT_CLOSE_POPUPS)
+                                            .build()
+                                        {
+                                            selected = !selected;
+                                            if selected {
+                                                allowed.push(index);
+                                            } else {
+                                                allowed.retain(|other| *other != index);
+                                            }
+                                        }
+                                    }
+                                    combo.end();
+                                }
+                                input_width.end();
+                            }
+                            edge_constraints.retain(|_, allowed| !allowed.is_empty());
+
+                            if &edge_constraints != self.solver.edge_constraints() {
+                                self.remember_run();
+                                self.solving = None;
+                                self.solver.set_edge_constraints(edge_constraints);
+                                self.last_collapsed = None;
+                            }
+                        }
+
+                        if ui_frame.checkbox("Paint regions", &mut self.painting_regions)
+                            && self.painting_regions
+                        {
+                            self.painting_tiles = false;
+                        }
+                        if self.painting_regions {
+                            ui_frame.same_line();
+                            let input_width = ui_frame.push_item_width(100.0);
+                            ui_frame
+                                .input_text("##Brush", &mut self.brush)
+                                .hint("Category")
+                                .build();
+                            input_width.end();
+                        }
+
+                        if ui_frame.checkbox("Paint tiles", &mut self.painting_tiles)
+                            && self.painting_tiles
+                        {
+                            self.painting_regions = false;
+                        }
+                        if self.painting_tiles {
+                            ui_frame.same_line();
+                            let input_width = ui_frame.push_item_width(100.0);
+                            let tile_labels = self
+                                .solver
+                                .tiles()
+                                .iter()
+                                .enumerate()
+                                .map(|(index, tile)| {
+                                    format!("{}##{index}", tile_label(&images, tile, index))
+                                })
+                                .collect::<Vec<_>>();
+                            ui_frame.combo_simple_string(
+                                "##Paint tile",
+                                &mut self.paint_tile,
+                                &tile_labels,
+                            );
+                            input_width.end();
+                        }
+
+                        ui_frame.checkbox("Forbid tiles", &mut self.forbidding_tiles);
+                        if self.forbidding_tiles {
+                            ui_frame.same_line();
+                            let input_width = ui_frame.push_item_width(100.0);
+                            let tile_labels = self
+                                .solver
+                                .tiles()
+                                .iter()
+                                .enumerate()
+                                .map(|(index, tile)| {
+                                    format!("{}##{index}", tile_label(&images, tile, index))
+                                })
+                                .collect::<Vec<_>>();
+                            ui_frame.combo_simple_string(
+                                "##Forbidden tile",
+                                &mut self.forbidden_tile,
+                                &tile_labels,
+                            );
+                            input_width.end();
+                            ui_frame.same_line();
+                            if ui_frame.button("Allow everywhere") {
+                                let mut forbidden = self.solver.forbidden().to_vec();
+                                for cell in forbidden.iter_mut().flatten() {
+                                    cell.remove(&self.forbidden_tile);
+                                }
+                                self.remember_run();
+                                self.solving = None;
+                                self.solver.set_forbidden(forbidden);
+                                self.last_collapsed = None;
+                            }
+                        }
+
+                        ui_frame.checkbox("Show diff from last run", &mut self.show_diff);
+                        ui_frame.same_line();
+                        ui_frame.checkbox("Blend candidates", &mut self.preview_candidates);
+                        ui_frame.same_line();
+                        ui_frame.checkbox("Candidate counts", &mut self.show_candidate_counts);
+                        // Runs the seed of the remembered board again, so the diff only shows the effect of edits
+                        if let Some(seed) = self.last_seed {
+                            ui_frame.same_line();
+                            if ui_frame.button(format!("Rerun seed {seed}")) {
+                                self.remember_run();
+                                self.solving = None;
+                                self.solver.rerun_seed(seed);
+                                self.last_collapsed = None;
+                                self.stalled_for = Duration::ZERO;
+                                self.show_diff = true;
+                            }
+                        }
+
+                        let mut show_heatmap = self.heatmap_tile.is_some();
+                        if ui_frame.checkbox("Probability heatmap", &mut show_heatmap) {
+                            self.heatmap_tile = show_heatmap.then_some(0);
+                        }
+                        if let Some(heatmap_tile) = &mut self.heatmap_tile {
+                            ui_frame.same_line();
+                            let input_width = ui_frame.push_item_width(100.0);
+                            let tile_labels = self
+                                .solver
+                                .tiles()
+                                .iter()
+                                .enumerate()
+                                .map(|(index, tile)| {
+                                    format!("{}##{index}", tile_label(&images, tile, index))
+                                })
+                                .collect::<Vec<_>>();
+                            ui_frame.combo_simple_string(
+                                "##Heatmap tile",
+                                heatmap_tile,
+                                &tile_labels,
+                            );
+                            input_width.end();
+                        }
+
+                        // Generates the rest of the board on another thread, keeping the window responsive
+                        if self.solving.is_some() {
+                            ui_frame.text("Generating...");
+                        } else if ui_frame.button("Generate now") {
+                            let mut solver = self.solver.clone();
+                            let (sender, receiver) = mpsc::channel();
+                            tokio::task::spawn_blocking(move || {
+                                let result = solver.solve_all().map(|_| solver);
+                                // The canvas may have been restarted in the meantime
+                                let _ = sender.send(result);
+                            });
+                            self.solving = Some(Rc::new(receiver));
+                        }
+                        ui_frame.same_line();
+
+                        // Fills the rest of the board without animating it
+                        if ui_frame.button("Finish") {
+                            self.solving = None;
+                            self.canvas_error =
+                                self.solver.finalize().err().map(|err| err.to_string());
+                            self.last_collapsed = None;
+                        }
+
+                        // Advances a fixed number of collapses, stopping early at a contradiction
+                        if self.solving.is_none() {
+                            ui_frame.same_line();
+                            let input_width = ui_frame.push_item_width(60.0);
+                            ui_frame.input_scalar("##Steps", &mut self.steps_at_once).build();
+                            input_width.end();
+                            self.steps_at_once = self.steps_at_once.max(1);
+                            ui_frame.same_line();
+                            if ui_frame.button(format!("Step {}##Step", self.steps_at_once)) {
+                                for _ in 0..self.steps_at_once {
+                                    let contradictions = self.solver.contradictions();
+                                    let attempt = self.solver.attempt();
+                                    match self.step() {
+                                        Ok(Some(_)) => {}
+                                        Ok(None) => break,
+                                        Err(err) => {
+                                            self.canvas_error = Some(err.to_string());
+                                            break;
+                                        }
+                                    }
+                                    if self.solver.contradictions() > contradictions
+                                        || self.solver.attempt() != attempt
+                                    {
+                                        break;
+                                    }
+                                }
+                                self.last_update = Instant::now();
+                            }
+                        }
+
+                        // Numbered images of every step, for turning the generation into an animation
+                        let frames_written = self
+                            .frame_recorder
+                            .as_ref()
+                            .map(FrameRecorder::frames_written);
+                        if let Some(frames_written) = frames_written {
+                            if ui_frame.button("Stop recording frames") {
+                                self.frame_recorder = None;
+                            }
+                            ui_frame.same_line();
+                            ui_frame.text(format!("{frames_written} frames written"));
+                        } else if ui_frame.button("Record frames") {
+                            if let Some(directory) = FileDialog::new().pick_folder() {
+                                match FrameRecorder::new(directory, &images, pattern_size) {
+                                    Ok(recorder) => {
+                                        self.frame_recorder = Some(recorder);
+                                        self.canvas_error = None;
+                                    }
+                                    Err(err) => self.canvas_error = Some(err.to_string()),
+                                }
+                            }
+                        }
+
+                        // Saved canvases keep their collapsed cells, the rest continues generating
+                        if ui_frame.button("Save canvas") {
+                            if let Some(path) = FileDialog::new()
+                                .add_filter("Canvas", &["json"])
+                                .save_file()
+                            {
+                                self.canvas_error = self
+                                    .solver
+                                    .save_partial_canvas(path)
+                                    .err()
+                                    .map(|err| err.to_string());
+                            }
+                        }
+                        ui_frame.same_line();
+                        // Maps can be read back with Load canvas, their layers are generated again
+                        if ui_frame.button("Export map") {
+                            if let Some(path) = FileDialog::new()
+                                .add_filter("Map", &["json"])
+                                .save_file()
+                            {
+                                let tileset_path = self.tileset.tileset_path(&concept_manager).clone();
+                                self.canvas_error = self
+                                    .export_map_json(path, tileset_path, &images, &layer_images)
+                                    .err()
+                                    .map(|err| err.to_string());
+                            }
+                        }
+                        ui_frame.same_line();
+                        if ui_frame.button("Load canvas") {
+                            if let Some(path) = FileDialog::new()
+                                .add_filter("Canvas", &["json"])
+                                .pick_file()
+                            {
+                                self.remember_run();
+                                self.solving = None;
+                                self.canvas_error = self
+                                    .solver
+                                    .load_partial_canvas(path)
+                                    .err()
+                                    .map(|err| err.to_string());
+                                self.settings = self.solver.settings();
+                                self.last_collapsed = None;
+                            }
+                        }
+                        if let Some(err) = &self.canvas_error {
+                            ui_frame.text_colored([0.8, 0.1, 0.1, 1.0], err);
+                        }
+
+                        // Replays place the recorded tiles again regardless of the seed
+                        let mut recording = self.solver.is_recording();
+                        if ui_frame.checkbox("Record decisions", &mut recording) {
+                            self.solving = None;
+                            self.solver.set_recording(recording);
+                        }
+                        if !self.solver.decisions().is_empty() {
+                            ui_frame.same_line();
+                            if ui_frame.button("Save replay") {
+                                if let Some(path) = FileDialog::new()
+                                    .add_filter("Replay", &["json"])
+                                    .save_file()
+                                {
+                                    self.canvas_error =
+                                        serde_json::to_string(self.solver.decisions())
+                                            .map_err(std::io::Error::from)
+                                            .and_then(|json| std::fs::write(path, json))
+                                            .err()
+                                            .map(|err| err.to_string());
+                                }
+                            }
+                        }
+                        ui_frame.same_line();
+                        if ui_frame.button("Replay") {
+                            if let Some(path) = FileDialog::new()
+                                .add_filter("Replay", &["json"])
+                                .pick_file()
+                            {
+                                self.solving = None;
+                                let decisions = std::fs::read_to_string(path).and_then(|json| {
+                                    Ok(serde_json::from_str::<Vec<(usize, usize, usize)>>(&json)?)
+                                });
+                                self.canvas_error = match decisions {
+                                    Ok(decisions) => self
+                                        .solver
+                                        .replay(&decisions)
+                                        .err()
+                                        .map(|err| err.to_string()),
+                                    Err(err) => Some(err.to_string()),
+                                };
+                                self.last_collapsed = None;
+                            }
+                        }
+
+                        // Feeds the board back into the tileset, so a model can be refined from its own output
+                        // Its neighbors become connections and its tile counts the new weights
+                        // Tiles forced in by a contradiction don't fit their neighbors, so they aren't learned from
+                        if ui_frame.button("Learn from canvas") {
+                            if self.solver.contradictions() > 0 {
+                                self.canvas_error = Some(
+                                    "Can't learn from a board with contradictions".to_string(),
+                                );
                             } else {
-                                // Fallback value for tiles not yet collapsed
-                                0
+                                let mut tiles = self.solver.tiles().to_vec();
+                                learn_from_board(&self.solver.cells(), &mut tiles);
+                                self.tileset.set_tiles(&mut concept_manager, tiles);
+                            }
+                        }
+                        if ui_frame.is_item_hovered() {
+                            ui_frame.tooltip_text(
+                                "Adds the connections and weights found on the board to the tileset",
+                            );
+                        }
+                        ui_frame.same_line();
+                        if ui_frame.button("Copy as text") {
+                            let text = self.solver.export_text();
+                            self.canvas_error = engine_systems
+                                .lock()
+                                .unwrap()
+                                .sdl_context
+                                .video()
+                                .and_then(|video| video.clipboard().set_clipboard_text(&text))
+                                .err();
+                        }
+                        ui_frame.same_line();
+                        if ui_frame.button("Copy as image") {
+                            self.canvas_error = self
+                                .copy_canvas_image(&images, pattern_size)
+                                .err()
+                                .map(|err| err.to_string());
+                        }
+                    }
+
+                    // Ctrl + mouse wheel zooms the canvas
+                    let io = ui_frame.io();
+                    if ui_frame
+                        .is_window_hovered_with_flags(imgui::WindowHoveredFlags::CHILD_WINDOWS)
+                        && io.key_ctrl
+                        && io.mouse_wheel != 0.0
+                    {
+                        self.zoom = (self.zoom + io.mouse_wheel * 4.0).clamp(16.0, 128.0);
+                    }
+
+                    // Bar per tile showing how often it was used so far
+                    if ui_frame.collapsing_header("Tile usage", imgui::TreeNodeFlags::empty()) {
+                        let usage = self.solver.tile_usage();
+                        let most_used = usage.iter().copied().max().unwrap_or(0).max(1);
+                        for (index, count) in usage.iter().enumerate() {
+                            let Some(image) = images.get(index) else {
+                                continue;
                             };
-                            imgui::Image::new(images[image_index].id, [50.0, 50.0]).build(ui_frame);
+                            imgui::Image::new(image.id, [20.0, 20.0]).build(ui_frame);
+                            ui_frame.same_line();
+                            imgui::ProgressBar::new(*count as f32 / most_used as f32)
+                                .size([150.0, 20.0])
+                                .overlay_text(format!("{count}"))
+                                .build(ui_frame);
+                            ui_frame.same_line();
+                            ui_frame.text(image.label(index));
+                        }
+                    }
+
+                    // Average time per frame of every phase, to see where the time goes
+                    if ui_frame.collapsing_header("Timings", imgui::TreeNodeFlags::empty()) {
+                        let frames = self.frame_timings.len().max(1) as f64;
+                        let mut total = PhaseTimings::default();
+                        let mut drawing = Duration::ZERO;
+                        for (timings, draw_time) in &self.frame_timings {
+                            total += *timings;
+                            drawing += *draw_time;
+                        }
+                        let average = |time: Duration| time.as_secs_f64() * 1000.0 / frames;
+                        ui_frame.text(format!(
+                            "Entropy scan: {:.3} ms",
+                            average(total.entropy_scan)
+                        ));
+                        ui_frame.text(format!("Collapse: {:.3} ms", average(total.collapse)));
+                        ui_frame.text(format!(
+                            "Propagation: {:.3} ms",
+                            average(total.propagation)
+                        ));
+                        ui_frame.text(format!("Drawing: {:.3} ms", average(drawing)));
+                        ui_frame.text_disabled(format!(
+                            "Averaged over the last {} frames",
+                            self.frame_timings.len()
+                        ));
+                    }
+
+                    // Progress of the retries, and whether they all failed
+                    let finished = self.solver.remaining_cells() == 0;
+                    let stall_time = Duration::from_millis(
+                        self.settings.step_interval.max(STALL_STEP_MS)
+                            * self.settings.stall_limit as u64,
+                    );
+                    if !finished && self.stalled_for >= stall_time {
+                        ui_frame.text_colored(
+                            [0.8, 0.6, 0.1, 1.0],
+                            format!("Stalled with {} cells left", self.solver.remaining_cells()),
+                        );
+                    } else if finished && self.solver.contradictions() > 0 {
+                        ui_frame.text_colored(
+                            [0.8, 0.1, 0.1, 1.0],
+                            format!(
+                                "Contradicted {} times after {} attempts",
+                                self.solver.contradictions(),
+                                self.solver.attempt()
+                            ),
+                        );
+                    } else if self.settings.max_attempts > 1 {
+                        ui_frame.text(format!(
+                            "Attempt {}/{}",
+                            self.solver.attempt(),
+                            self.settings.max_attempts
+                        ));
+                    }
+
+                    // Dragging with the right mouse button selects cells to generate again
+                    if let Some(((y0, x0), (y1, x1))) = self.selection {
+                        if ui_frame.button("Regenerate selection") {
+                            self.remember_run();
+                            self.solving = None;
+                            self.solver.regenerate_region(x0, y0, x1, y1);
+                            self.last_collapsed = None;
+                        }
+                        ui_frame.same_line();
+                        if ui_frame.button("Clear selection") {
+                            self.selection = None;
+                        }
+                    } else if self.forbidding_tiles {
+                        ui_frame.text_disabled(
+                            "Drag with the right mouse button to forbid the tile, hold Ctrl to allow it again",
+                        );
+                    } else {
+                        ui_frame.text_disabled(
+                            "Drag with the right mouse button to select cells, right click an open cell to pick its tile",
+                        );
+                    }
+
+                    // Ctrl + C copies the board as an image while this canvas has focus
+                    if ui_frame.is_window_focused_with_flags(imgui::WindowFocusedFlags::CHILD_WINDOWS)
+                        && !ui_frame.io().want_text_input
+                        && ui_frame.io().key_ctrl
+                        && ui_frame.is_key_pressed_no_repeat(imgui::Key::C)
+                    {
+                        self.canvas_error = self
+                            .copy_canvas_image(&images, pattern_size)
+                            .err()
+                            .map(|err| err.to_string());
+                    }
+
+                    // Number keys pick the paint tile while this canvas has focus
+                    if self.painting_tiles {
+                        if ui_frame
+                            .is_window_focused_with_flags(imgui::WindowFocusedFlags::CHILD_WINDOWS)
+                            && !ui_frame.io().want_text_input
+                        {
+                            let number_keys = [
+                                imgui::Key::Alpha0,
+                                imgui::Key::Alpha1,
+                                imgui::Key::Alpha2,
+                                imgui::Key::Alpha3,
+                                imgui::Key::Alpha4,
+                                imgui::Key::Alpha5,
+                                imgui::Key::Alpha6,
+                                imgui::Key::Alpha7,
+                                imgui::Key::Alpha8,
+                                imgui::Key::Alpha9,
+                            ];
+                            if let Some(index) = number_keys
+                                .into_iter()
+                                .position(|key| ui_frame.is_key_pressed_no_repeat(key))
+                                .filter(|&index| index < self.solver.tiles().len())
+                            {
+                                self.paint_tile = index;
+                            }
+                        }
+
+                        if let Some(image) = self
+                            .solver
+                            .tiles()
+                            .get(self.paint_tile)
+                            .and_then(|tile| images.get(tile.image_index))
+                        {
+                            imgui::Image::new(image.id, [20.0, 20.0]).build(ui_frame);
+                            ui_frame.same_line();
+                            ui_frame.text(format!(
+                                "Painting {}, click an open cell to place it",
+                                image.label(self.paint_tile)
+                            ));
+                        }
+                    }
+
+                    // The blend only changes along with the board, so it isn't worked out every frame
+                    let revision = self.solver.revision();
+                    if self.preview_candidates
+                        && self.blend.as_ref().is_none_or(|(cached, _)| *cached != revision)
+                    {
+                        let distributions = (0..self.solver.representation().len())
+                            .map(|row| {
+                                (0..self.solver.representation()[row].len())
+                                    .map(|col| self.solver.cell_distribution(col, row))
+                                    .collect()
+                            })
+                            .collect();
+                        self.blend = Some((revision, distributions));
+                    }
+
+                    // Grid of tiles, scrolls when it doesn't fit in the window
+                    // Dragging with the middle mouse button pans around
+                    let cell_size = [self.zoom, self.zoom * images[0].size[1] / images[0].size[0]];
+                    let mut painted = None;
+                    let mut placed = None;
+                    let mut forbidden_in = None;
+                    ui_frame
+                        .child_window("Canvas view")
+                        .horizontal_scrollbar(true)
+                        .scrollable(!ui_frame.io().key_ctrl)
+                        .build(|| {
+                            let origin = ui_frame.cursor_screen_pos();
+                            let mouse = ui_frame.io().mouse_pos;
+                            let view_hovered = ui_frame.is_window_hovered();
+                            let mut hovered_cell = None;
+                            let draw_list = ui_frame.get_window_draw_list();
+                            for (row_index, row) in self.solver.representation().iter().enumerate()
+                            {
+                                for (col_index, tile) in row.iter().enumerate() {
+                                    let min = [
+                                        origin[0] + col_index as f32 * cell_size[0],
+                                        origin[1] + row_index as f32 * cell_size[1],
+                                    ];
+                                    let max = [min[0] + cell_size[0], min[1] + cell_size[1]];
+
+                                    // Actual data from collapse
+                                    // Open cells blend the images of their candidates by chance, so the board
+                                    // sharpens as it collapses, without the preview they show the first image
+                                    let images_drawn = match tile {
+                                        Some(tile) => vec![(tile.image_index, tile.rotation, 1.0)],
+                                        None if self.preview_candidates => {
+                                            let mut covered = 0.0;
+                                            self.blend
+                                                .iter()
+                                                .flat_map(|(_, cells)| &cells[row_index][col_index])
+                                                .filter_map(|&(index, chance)| {
+                                                    let tile = self.solver.tiles().get(index)?;
+                                                    // Every image takes its share of everything drawn so far,
+                                                    // which ends up as the average weighted by chance
+                                                    covered += chance;
+                                                    let alpha = if covered > 0.0 {
+                                                        chance / covered
+                                                    } else {
+                                                        1.0
+                                                    };
+                                                    Some((tile.image_index, tile.rotation, alpha))
+                                                })
+                                                .collect()
+                                        }
+                                        None => Vec::new(),
+                                    };
+                                    let mut images_drawn = if images_drawn.is_empty() {
+                                        vec![(0, 0, 1.0)]
+                                    } else {
+                                        images_drawn
+                                    }
+                                    .into_iter()
+                                    .map(|(index, rotation, alpha)| {
+                                        (images.get(index), rotation, alpha, uv_max)
+                                    })
+                                    .collect::<Vec<_>>();
+
+                                    // Layers are stacked on top, their tilesets are never made of patterns
+                                    for (solver, layer_images) in
+                                        self.layer_solvers.iter().zip(&layer_images)
+                                    {
+                                        if let Some(tile) =
+                                            &solver.representation()[row_index][col_index]
+                                        {
+                                            images_drawn.push((
+                                                layer_images.get(tile.image_index),
+                                                tile.rotation,
+                                                1.0,
+                                                1.0,
+                                            ));
+                                        }
+                                    }
+
+                                    // Tiles whose image is missing get a placeholder instead
+                                    for (image, rotation, alpha, uv_max) in images_drawn {
+                                        let Some(image) = image else {
+                                            draw_list
+                                                .add_rect(min, max, [1.0, 0.0, 1.0, alpha])
+                                                .filled(true)
+                                                .build();
+                                            continue;
+                                        };
+                                        let texture = image.texture_at(ui_frame.time());
+                                        // Rotated tiles take their corners from further around the image
+                                        let rotation = rotation as usize % 4;
+                                        let uvs = [
+                                            [0.0, 0.0],
+                                            [uv_max, 0.0],
+                                            [uv_max, uv_max],
+                                            [0.0, uv_max],
+                                        ];
+                                        draw_list
+                                            .add_image_quad(
+                                                texture,
+                                                min,
+                                                [max[0], min[1]],
+                                                max,
+                                                [min[0], max[1]],
+                                            )
+                                            .uv(
+                                                uvs[(4 - rotation) % 4],
+                                                uvs[(5 - rotation) % 4],
+                                                uvs[(6 - rotation) % 4],
+                                                uvs[(7 - rotation) % 4],
+                                            )
+                                            .col([1.0, 1.0, 1.0, alpha])
+                                            .build();
+                                    }
+
+                                    if view_hovered
+                                        && (min[0]..max[0]).contains(&mouse[0])
+                                        && (min[1]..max[1]).contains(&mouse[1])
+                                    {
+                                        hovered_cell = Some((row_index, col_index));
+                                    }
+
+                                    // Regions are only shown while painting them
+                                    if self.painting_regions {
+                                        if let Some(category) =
+                                            &self.solver.mask()[row_index][col_index]
+                                        {
+                                            draw_list
+                                                .add_rect(min, max, region_color(category))
+                                                .filled(true)
+                                                .build();
+                                        }
+                                    }
+
+                                    // Cells the forbidden tile is kept out of are crossed out while forbidding it
+                                    if self.forbidding_tiles
+                                        && self.solver.forbidden()[row_index][col_index]
+                                            .contains(&self.forbidden_tile)
+                                    {
+                                        let color = [0.9, 0.1, 0.1, 0.8];
+                                        draw_list.add_line(min, max, color).thickness(2.0).build();
+                                        draw_list
+                                            .add_line([min[0], max[1]], [max[0], min[1]], color)
+                                            .thickness(2.0)
+                                            .build();
+                                    }
+
+                                    // Cells the hovered candidate of the collapse menu would narrow down,
+                                    // red where nothing would fit anymore
+                                    let narrowed = self.what_if.as_ref().and_then(|(_, _, narrowed)| {
+                                        narrowed.get(&(row_index, col_index))
+                                    });
+                                    if let Some(&(before, after)) = narrowed {
+                                        let color = if after == 0 {
+                                            [0.9, 0.1, 0.1, 0.5]
+                                        } else {
+                                            let drop = 1.0 - after as f32 / before as f32;
+                                            [1.0, 0.6, 0.1, 0.15 + 0.45 * drop]
+                                        };
+                                        draw_list.add_rect(min, max, color).filled(true).build();
+                                    }
+
+                                    // Tints the cells that turned out differently than last time
+                                    let previous = self
+                                        .previous_run
+                                        .as_ref()
+                                        .and_then(|board| board.get(row_index))
+                                        .and_then(|row| row.get(col_index));
+                                    if let (Some(tile), Some(previous)) = (tile, previous) {
+                                        if self.show_diff && *previous != Some(tile.image_index) {
+                                            draw_list
+                                                .add_rect(min, max, [1.0, 0.9, 0.1, 0.4])
+                                                .filled(true)
+                                                .build();
+                                        }
+                                    }
+
+                                    // Chance of the heatmap tile ending up in each open cell
+                                    if let (None, Some(heatmap_tile)) = (tile, self.heatmap_tile) {
+                                        let probability = self
+                                            .solver
+                                            .probability((row_index, col_index), heatmap_tile);
+                                        draw_list
+                                            .add_rect(
+                                                min,
+                                                max,
+                                                [
+                                                    probability,
+                                                    0.2,
+                                                    1.0 - probability,
+                                                    0.3 + probability * 0.5,
+                                                ],
+                                            )
+                                            .filled(true)
+                                            .build();
+                                    }
+
+                                    if self.distribution_cell == Some((row_index, col_index)) {
+                                        draw_list
+                                            .add_rect(min, max, [1.0, 0.9, 0.1, 1.0])
+                                            .thickness(2.0)
+                                            .build();
+                                    }
+
+                                    // Outlines the cell that was just collapsed
+                                    if self.last_collapsed == Some((row_index, col_index)) {
+                                        draw_list
+                                            .add_rect(min, max, [1.0, 0.2, 0.2, 1.0])
+                                            .thickness(2.0)
+                                            .build();
+                                    }
+                                }
+                            }
+                            // The most constrained cells stand out, the lowest entropy order collapses those next
+                            if self.show_candidate_counts {
+                                let counts = (0..self.settings.height)
+                                    .flat_map(|row| (0..self.settings.width).map(move |col| (row, col)))
+                                    .filter(|&(row, col)| {
+                                        self.solver.representation()[row][col].is_none()
+                                    })
+                                    .map(|pos| (pos, self.solver.possible_tiles(pos).len()))
+                                    .collect::<Vec<_>>();
+                                let fewest = counts.iter().map(|(_, count)| *count).min();
+                                for ((row, col), count) in counts {
+                                    let text = count.to_string();
+                                    let corner = [
+                                        origin[0] + col as f32 * cell_size[0] + 2.0,
+                                        origin[1] + row as f32 * cell_size[1] + 2.0,
+                                    ];
+                                    let text_size = ui_frame.calc_text_size(&text);
+                                    draw_list
+                                        .add_rect(
+                                            corner,
+                                            [corner[0] + text_size[0] + 2.0, corner[1] + text_size[1]],
+                                            [0.0, 0.0, 0.0, 0.6],
+                                        )
+                                        .filled(true)
+                                        .build();
+                                    let color = if Some(count) == fewest {
+                                        [1.0, 0.8, 0.2, 1.0]
+                                    } else {
+                                        [1.0, 1.0, 1.0, 1.0]
+                                    };
+                                    draw_list.add_text([corner[0] + 1.0, corner[1]], color, &text);
+                                }
+                            }
+                            if self.show_grid {
+                                let size = [
+                                    self.settings.width as f32 * cell_size[0],
+                                    self.settings.height as f32 * cell_size[1],
+                                ];
+                                for col_index in 0..=self.settings.width {
+                                    let x = origin[0] + col_index as f32 * cell_size[0];
+                                    draw_list
+                                        .add_line(
+                                            [x, origin[1]],
+                                            [x, origin[1] + size[1]],
+                                            self.grid_color,
+                                        )
+                                        .thickness(self.grid_thickness)
+                                        .build();
+                                }
+                                for row_index in 0..=self.settings.height {
+                                    let y = origin[1] + row_index as f32 * cell_size[1];
+                                    draw_list
+                                        .add_line(
+                                            [origin[0], y],
+                                            [origin[0] + size[0], y],
+                                            self.grid_color,
+                                        )
+                                        .thickness(self.grid_thickness)
+                                        .build();
+                                }
+                            }
+                            if let Some(((y0, x0), (y1, x1))) = self.selection {
+                                let min = [
+                                    origin[0] + x0.min(x1) as f32 * cell_size[0],
+                                    origin[1] + y0.min(y1) as f32 * cell_size[1],
+                                ];
+                                let max = [
+                                    origin[0] + (x0.max(x1) + 1) as f32 * cell_size[0],
+                                    origin[1] + (y0.max(y1) + 1) as f32 * cell_size[1],
+                                ];
+                                draw_list
+                                    .add_rect(min, max, [0.2, 0.6, 1.0, 1.0])
+                                    .thickness(2.0)
+                                    .build();
+                            }
+                            drop(draw_list);
+
+                            // Reserves the space taken by the grid so the view can scroll over it
+                            ui_frame.dummy([
+                                self.settings.width as f32 * cell_size[0],
+                                self.settings.height as f32 * cell_size[1],
+                            ]);
+
+                            if let Some(pos) = hovered_cell {
+                                if self.forbidding_tiles {
+                                    if ui_frame.is_mouse_down(imgui::MouseButton::Right) {
+                                        forbidden_in = Some(pos);
+                                    }
+                                } else if ui_frame.is_mouse_clicked(imgui::MouseButton::Right) {
+                                    self.selection = Some((pos, pos));
+                                    self.selecting = true;
+                                } else if self.selecting {
+                                    if let Some((start, _)) = self.selection {
+                                        self.selection = Some((start, pos));
+                                    }
+                                }
+                                // Shows what is left to choose from when hovering an open cell
+                                if self.solver.representation()[pos.0][pos.1].is_none() {
+                                    let possible_tiles = self
+                                        .solver
+                                        .possible_tiles(pos)
+                                        .iter()
+                                        .map(|index| index.to_string())
+                                        .collect::<Vec<_>>()
+                                        .join(", ");
+                                    let mut tooltip = format!(
+                                        "Possible tiles: {possible_tiles}\nEntropy: {}",
+                                        self.solver.entropy(pos)
+                                    );
+                                    if let Some(heatmap_tile) = self.heatmap_tile {
+                                        tooltip += &format!(
+                                            "\nChance of tile {heatmap_tile}: {:.1}%",
+                                            self.solver.probability(pos, heatmap_tile) * 100.0
+                                        );
+                                    }
+                                    ui_frame.tooltip_text(tooltip);
+                                }
+                                if self.painting_regions
+                                    && ui_frame.is_mouse_down(imgui::MouseButton::Left)
+                                {
+                                    painted = Some(pos);
+                                } else if self.painting_tiles
+                                    && ui_frame.is_mouse_clicked(imgui::MouseButton::Left)
+                                    && self.solver.representation()[pos.0][pos.1].is_none()
+                                {
+                                    placed = Some(pos);
+                                } else if ui_frame.is_mouse_clicked(imgui::MouseButton::Left) {
+                                    if self.solver.representation()[pos.0][pos.1].is_some() {
+                                        self.inspected_cell = Some(pos);
+                                        ui_frame.open_popup("Tile inspector");
+                                    } else {
+                                        self.distribution_cell = Some(pos);
+                                    }
+                                }
+                            }
+
+                            // Read only view of the tile in a clicked cell
+                            if let Some(_popup) = ui_frame.begin_popup("Tile inspector") {
+                                let tile = self.inspected_cell.and_then(|(row, col)| {
+                                    self.solver.representation().get(row)?.get(col)?.as_ref()
+                                });
+                                if let Some(tile) = tile {
+                                    let positions = tile_positions(self.solver.tiles());
+                                    let label = |index: usize| {
+                                        images
+                                            .get(index)
+                                            .map(|image| image.label(index))
+                                            .unwrap_or_else(|| format!("{index}"))
+                                    };
+                                    ui_frame.text(format!("Tile {}", tile.image_index));
+                                    ui_frame.text(format!("Name: {}", label(tile.image_index)));
+                                    ui_frame.text(format!("Weight: {}", tile.weight));
+                                    ui_frame.separator();
+                                    for direction in DIRECTIONS {
+                                        let connections = tile
+                                            .valid_tiles(direction)
+                                            .iter()
+                                            .map(|(other, side)| match positions.get(other) {
+                                                Some(&index) => {
+                                                    format!("{} ({side})", label(index))
+                                                }
+                                                None => format!("Missing tile {other} ({side})"),
+                                            })
+                                            .collect::<Vec<_>>();
+                                        ui_frame.text(format!(
+                                            "{direction}: {}",
+                                            if connections.is_empty() {
+                                                "none".to_owned()
+                                            } else {
+                                                connections.join(", ")
+                                            }
+                                        ));
+                                    }
+                                }
+                            }
+
+                            if ui_frame.is_mouse_released(imgui::MouseButton::Right)
+                                && self.selecting
+                            {
+                                self.selecting = false;
+                                // Right clicking an open cell without dragging picks its tile instead of selecting it
+                                if let Some((start, end)) = self.selection {
+                                    if start == end
+                                        && self.solver.representation()[start.0][start.1].is_none()
+                                    {
+                                        self.selection = None;
+                                        self.overridden_cell = Some(start);
+                                        ui_frame.open_popup("Collapse cell");
+                                    }
+                                }
+                            }
+
+                            // Tiles that still fit the cell, picking one collapses it and propagates from there
+                            if let Some(_popup) = ui_frame.begin_popup("Collapse cell") {
+                                if let Some((row, col)) = self.overridden_cell {
+                                    let mut distribution = self.solver.cell_distribution(col, row);
+                                    distribution.sort_by(|a, b| b.1.total_cmp(&a.1));
+                                    if distribution.is_empty() {
+                                        ui_frame.text_disabled("Nothing fits here");
+                                    }
+                                    let mut hovered_candidate = None;
+                                    for (index, chance) in distribution {
+                                        let Some(tile) = self.solver.tiles().get(index) else {
+                                            continue;
+                                        };
+                                        if let Some(image) = images.get(tile.image_index) {
+                                            imgui::Image::new(image.id, [20.0, 20.0])
+                                                .build(ui_frame);
+                                            ui_frame.same_line();
+                                        }
+                                        let label = format!(
+                                            "{} ({:.1}%)##{index}",
+                                            tile_label(&images, tile, index),
+                                            chance * 100.0
+                                        );
+                                        if ui_frame.selectable(label) {
+                                            self.solving = None;
+                                            match self.solver.place_tile((row, col), index) {
+                                                Ok(()) => self.last_collapsed = Some((row, col)),
+                                                Err(err) => {
+                                                    self.canvas_error = Some(err.to_string())
+                                                }
+                                            }
+                                            self.overridden_cell = None;
+                                        }
+                                        if ui_frame.is_item_hovered() {
+                                            hovered_candidate = Some(index);
+                                        }
+                                    }
+
+                                    // Previews the hovered candidate on the canvas without placing it
+                                    // Only worked out again once another candidate is hovered
+                                    match hovered_candidate {
+                                        Some(index)
+                                            if self.what_if.as_ref().is_none_or(
+                                                |(cell, tile, _)| (*cell, *tile) != ((row, col), index),
+                                            ) =>
+                                        {
+                                            let narrowed = self.what_if((row, col), index);
+                                            self.what_if = Some(((row, col), index, narrowed));
+                                        }
+                                        Some(_) => {}
+                                        None => self.what_if = None,
+                                    }
+                                    if let Some((_, _, narrowed)) = &self.what_if {
+                                        let emptied = narrowed
+                                            .values()
+                                            .filter(|(_, after)| *after == 0)
+                                            .count();
+                                        ui_frame.separator();
+                                        ui_frame.text_disabled(format!(
+                                            "Narrows down {} cells, {emptied} without any tile left",
+                                            narrowed.len()
+                                        ));
+                                    }
+                                }
+                            } else {
+                                self.what_if = None;
+                            }
+
+                            if view_hovered
+                                && ui_frame.is_mouse_dragging(imgui::MouseButton::Middle)
+                            {
+                                let delta = ui_frame.io().mouse_delta;
+                                ui_frame.set_scroll_x(ui_frame.scroll_x() - delta[0]);
+                                ui_frame.set_scroll_y(ui_frame.scroll_y() - delta[1]);
+                            }
+                        });
+
+                    if let Some(pos) = placed {
+                        self.solving = None;
+                        match self.solver.place_tile(pos, self.paint_tile) {
+                            Ok(()) => self.last_collapsed = Some(pos),
+                            Err(err) => self.canvas_error = Some(err.to_string()),
+                        }
+                    }
+
+                    if let Some((row_index, col_index)) = forbidden_in {
+                        let allow = ui_frame.io().key_ctrl;
+                        let cell = &self.solver.forbidden()[row_index][col_index];
+                        if cell.contains(&self.forbidden_tile) == allow {
+                            let mut forbidden = self.solver.forbidden().to_vec();
+                            if allow {
+                                forbidden[row_index][col_index].remove(&self.forbidden_tile);
+                            } else {
+                                forbidden[row_index][col_index].insert(self.forbidden_tile);
+                            }
+                            self.remember_run();
+                            self.solving = None;
+                            self.solver.set_forbidden(forbidden);
+                            self.last_collapsed = None;
+                        }
+                    }
+
+                    if let Some((row_index, col_index)) = painted {
+                        let category =
+                            (!self.brush.trim().is_empty()).then(|| self.brush.trim().to_owned());
+                        if self.solver.mask()[row_index][col_index] != category {
+                            let mut mask = self.solver.mask().to_vec();
+                            mask[row_index][col_index] = category;
+                            self.remember_run();
+                            self.solving = None;
+                            self.solver.set_mask(mask);
+                            self.last_collapsed = None;
                         }
                     }
-                    image_table.end();
                 });
-            style.pop();
+
+            // Weighted candidates of an open cell, which is what the next collapse there draws from
+            // Loading a canvas can shrink the board under the cell
+            self.distribution_cell = self
+                .distribution_cell
+                .filter(|&(row, col)| row < self.settings.height && col < self.settings.width);
+            if let Some((row, col)) = self.distribution_cell {
+                let mut opened = true;
+                ui_frame
+                    .window(format!("Cell distribution##{}", self.parent))
+                    .opened(&mut opened)
+                    .always_auto_resize(true)
+                    .build(|| {
+                        ui_frame.text(format!("Row {row}, column {col}"));
+                        let mut distribution = self.solver.cell_distribution(col, row);
+                        distribution.sort_by(|a, b| b.1.total_cmp(&a.1));
+                        if distribution.is_empty() {
+                            ui_frame.text_colored(
+                                [0.8, 0.1, 0.1, 1.0],
+                                "Nothing fits, collapsing here is a contradiction",
+                            );
+                        }
+                        ui_frame.separator();
+
+                        for (index, chance) in distribution {
+                            let Some(image) = self
+                                .solver
+                                .tiles()
+                                .get(index)
+                                .and_then(|tile| images.get(tile.image_index))
+                            else {
+                                continue;
+                            };
+                            imgui::Image::new(image.id, [20.0, 20.0]).build(ui_frame);
+                            ui_frame.same_line();
+                            imgui::ProgressBar::new(chance)
+                                .size([150.0, 20.0])
+                                .overlay_text(format!("{:.1}%", chance * 100.0))
+                                .build(ui_frame);
+                            ui_frame.same_line();
+                            ui_frame.text(image.label(index));
+                        }
+                    });
+                if !opened {
+                    self.distribution_cell = None;
+                }
+            }
+        }
+
+        *concept_manager
+            .get_concept_mut::<GenerationSettings>(self.id, "generation_settings".to_string())
+            .unwrap() = self.settings;
+
+        if let Some((_, drawing)) = self.frame_timings.back_mut() {
+            *drawing += draw_started.elapsed();
         }
     }
 
@@ -452,3 +1801,43 @@ impl ComponentSystem for ImageCanvasComponent {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use gamezap::ecs::scene::Scene;
+
+    use super::*;
+    use crate::components::tile_creation_component::TileCreationComponent;
+
+    #[test]
+    fn reads_the_tileset_of_any_entity() {
+        let concept_manager = Scene::default().get_concept_manager();
+        // The tile creation component isn't on the first entity
+        let source = (7, TypeId::of::<TileCreationComponent>(), 0);
+        let concepts: HashMap<String, Box<dyn Any>> = HashMap::from([
+            (
+                "loaded_tiles".to_string(),
+                Box::new(vec![TileData::new(0), TileData::new(1)]) as Box<dyn Any>,
+            ),
+            ("pattern_size".to_string(), Box::new(1_u32) as Box<dyn Any>),
+        ]);
+        concept_manager
+            .lock()
+            .unwrap()
+            .register_component_concepts(source, concepts);
+
+        let tileset = TilesetHandle::new(7);
+        assert_ne!(tileset, TilesetHandle::new(0));
+        let canvas = ImageCanvasComponent::new(
+            concept_manager.clone(),
+            GenerationSettings::default(),
+            tileset,
+            [0.0, 0.0],
+        );
+        assert_eq!(canvas.tileset, tileset);
+
+        let concept_manager = concept_manager.lock().unwrap();
+        assert_eq!(canvas.tileset.tiles(&concept_manager).len(), 2);
+        assert_eq!(canvas.tileset.pattern_size(&concept_manager), 1);
+    }
+}
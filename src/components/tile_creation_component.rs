@@ -1,6 +1,6 @@
 use std::{
     any::{Any, TypeId},
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     rc::Rc,
     sync::{Arc, Mutex},
 };
@@ -19,81 +19,169 @@ use gamezap::{
 use rfd::FileDialog;
 use wgpu::{Device, Queue};
 
+use crate::{
+    animation::{frames_in_folder, split_sprite_sheet},
+    bundle::{export_bundle, import_bundle},
+    clipboard::save_clipboard_image,
+    config::{Config, Model},
+    layer::Layer,
+    overlapping::overlapping_tileset,
+    sample::{infer_weights_from_sample, learn_from_ascii},
+    socket::{connect_sockets, Socket},
+    spritesheet::{split_tile_sheet, SheetGrid},
+    template::ConnectionTemplate,
+    tile::{new_tile_id, tile_positions, Direction, TileConnection, TileData, TileId},
+    tiled::import_tiled,
+    tileset::{connect_corners, default_frame_duration, Adjacency, TileImage, Tileset},
+    validation::{precheck_solvability, validate, Severity, ValidationIssue},
+    watcher::TilesetWatcher,
+    wfc::DIRECTIONS,
+};
+
+const SHORTCUTS_HELP: &str = "Keyboard shortcuts
+Space: toggle \"Run algorithm\"
+N / S / E / W: switch the direction tab
+Enter: add the connection
+Delete: remove the selected connection
+Ctrl + V: add the image on the clipboard as a tile
+Ctrl + C on the canvas: copy the board as an image";
+
+// Images uploaded as textures every frame while loading many at once
+const IMAGES_PER_FRAME: usize = 8;
+
 #[derive(Debug, Clone)]
 pub struct ImageData {
-    _path: String,
+    path: String,
     pub id: imgui::TextureId,
-    size: [f32; 2],
+    pub size: [f32; 2],
+    pub name: String,
+    // Extra frames of animated tiles, shown after the main image
+    frames: Vec<(String, imgui::TextureId)>,
+    pub frame_duration: u64,
 }
 
 impl ImageData {
     pub fn new(path: String, id: imgui::TextureId, size: [f32; 2]) -> Self {
+        // Default to the file name so tiles are recognizable straight away
+        let name = std::path::Path::new(&path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
         Self {
-            _path: path,
+            path,
             id,
             size,
+            name,
+            frames: Vec::new(),
+            frame_duration: default_frame_duration(),
         }
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Hash, Eq)]
-pub enum Direction {
-    North,
-    South,
-    East,
-    West,
-}
+    pub fn add_frame(&mut self, path: String, id: imgui::TextureId) {
+        self.frames.push((path, id));
+    }
 
-impl From<usize> for Direction {
-    fn from(value: usize) -> Self {
-        match value {
-            0 => Direction::North,
-            1 => Direction::South,
-            2 => Direction::East,
-            3 => Direction::West,
-            _ => Direction::North,
+    pub fn is_animated(&self) -> bool {
+        !self.frames.is_empty()
+    }
+
+    // Texture to show at a point in time, given in seconds
+    pub fn texture_at(&self, time: f64) -> imgui::TextureId {
+        if self.frames.is_empty() || self.frame_duration == 0 {
+            return self.id;
+        }
+        let frame = (time * 1000.0) as u64 / self.frame_duration % (self.frames.len() as u64 + 1);
+        match frame {
+            0 => self.id,
+            frame => self.frames[frame as usize - 1].1,
         }
     }
-}
 
-impl std::fmt::Display for Direction {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Direction::North => write!(f, "North"),
-            Direction::South => write!(f, "South"),
-            Direction::East => write!(f, "East"),
-            Direction::West => write!(f, "West"),
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    // Name shown in the UI, falls back to the index for unnamed tiles
+    pub fn label(&self, index: usize) -> String {
+        if self.name.is_empty() {
+            format!("{index}")
+        } else {
+            self.name.clone()
         }
     }
 }
 
-pub type TileConnection = (usize, Direction);
-
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct TileData {
-    pub image_index: usize,
-    pub north_valid_tiles: Vec<TileConnection>,
-    pub south_valid_tiles: Vec<TileConnection>,
-    pub east_valid_tiles: Vec<TileConnection>,
-    pub west_valid_tiles: Vec<TileConnection>,
+// Typed access to the tileset a tile creation component shares through its concepts
+// Other components hold on to this instead of knowing where the tile creation component lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TilesetHandle {
+    source: ComponentId,
 }
 
-impl TileData {
-    pub fn new(image_index: usize) -> Self {
+impl TilesetHandle {
+    // Tile creation component of an entity
+    pub fn new(entity: EntityId) -> Self {
         Self {
-            image_index,
-            north_valid_tiles: Vec::new(),
-            south_valid_tiles: Vec::new(),
-            east_valid_tiles: Vec::new(),
-            west_valid_tiles: Vec::new(),
+            source: (entity, TypeId::of::<TileCreationComponent>(), 0),
         }
     }
 
-    pub fn total_connections(&self) -> usize {
-        self.north_valid_tiles.len()
-            + self.south_valid_tiles.len()
-            + self.east_valid_tiles.len()
-            + self.west_valid_tiles.len()
+    pub fn images<'a>(&self, concept_manager: &'a ConceptManager) -> &'a Vec<ImageData> {
+        concept_manager
+            .get_concept::<Vec<ImageData>>(self.source, "loaded_images".to_string())
+            .unwrap()
+    }
+
+    pub fn tiles<'a>(&self, concept_manager: &'a ConceptManager) -> &'a Vec<TileData> {
+        concept_manager
+            .get_concept::<Vec<TileData>>(self.source, "loaded_tiles".to_string())
+            .unwrap()
+    }
+
+    // Replaces the tiles, the tile creation component edits the new ones from its next frame on
+    pub fn set_tiles(&self, concept_manager: &mut ConceptManager, tiles: Vec<TileData>) {
+        *concept_manager
+            .get_concept_mut::<Vec<TileData>>(self.source, "loaded_tiles".to_string())
+            .unwrap() = tiles;
+    }
+
+    // Size of the patterns of the overlapping model, 1 for tiles made by hand
+    pub fn pattern_size(&self, concept_manager: &ConceptManager) -> u32 {
+        *concept_manager
+            .get_concept::<u32>(self.source, "pattern_size".to_string())
+            .unwrap()
+    }
+
+    pub fn tileset_path<'a>(&self, concept_manager: &'a ConceptManager) -> &'a Option<String> {
+        concept_manager
+            .get_concept::<Option<String>>(self.source, "tileset_path".to_string())
+            .unwrap()
+    }
+
+    pub fn run_algorithm(&self, concept_manager: &ConceptManager) -> bool {
+        *concept_manager
+            .get_concept::<bool>(self.source, "run_algorithm".to_string())
+            .unwrap()
+    }
+
+    // Tilesets generated on top of the main one, bottom layer first
+    pub fn layers<'a>(&self, concept_manager: &'a ConceptManager) -> &'a Vec<Layer> {
+        concept_manager
+            .get_concept::<Vec<Layer>>(self.source, "layers".to_string())
+            .unwrap()
+    }
+
+    // Images of every layer, in the same order as the layers
+    pub fn layer_images<'a>(&self, concept_manager: &'a ConceptManager) -> &'a Vec<Vec<ImageData>> {
+        concept_manager
+            .get_concept::<Vec<Vec<ImageData>>>(self.source, "layer_images".to_string())
+            .unwrap()
+    }
+
+    pub fn model(&self, concept_manager: &ConceptManager) -> Model {
+        *concept_manager
+            .get_concept::<Model>(self.source, "model".to_string())
+            .unwrap()
     }
 }
 
@@ -105,12 +193,60 @@ pub struct TileCreationComponent {
     tile_being_modified: Option<usize>,
     selected_direction: Option<Direction>,
     tile_selected: usize,
+    tile_search: String,
+    // Categories of the tile being modified, separated by commas
+    categories_text: String,
+    connection_error: Option<String>,
     direction_selected: usize,
+    // Neighbor and its direction for the next contextual weight
+    context_tile: usize,
+    context_direction: usize,
     run_algorithm: bool,
+    tileset_path: Option<String>,
+    tileset_to_load: Option<String>,
+    tileset_error: Option<String>,
+    // Reloads the tileset file when it is edited outside of the app
+    tileset_watcher: Option<TilesetWatcher>,
+    show_adjacency_matrix: bool,
+    show_weights: bool,
+    show_layers: bool,
+    layer_error: Option<String>,
+    // Sheet waiting for its grid to be set before it is cut into tiles
+    sheet_to_load: Option<String>,
+    // Images waiting to become tiles, uploaded a few every frame so large folders don't freeze the window
+    image_queue: VecDeque<String>,
+    // Images queued since the queue was last empty, for the progress bar
+    images_queued: usize,
+    // Disables everything that edits the tileset while the algorithm runs,
+    // since any change restarts the generation
+    lock_tileset: bool,
+    sheet_grid: SheetGrid,
+    // Text of a picked ASCII map and the tile chosen for each of its characters, 0 leaves it out
+    ascii_map: Option<(String, Vec<(char, usize)>)>,
+    // Whether mirrored tiles get a flipped copy of the image or share the original one
+    flip_mirrored_image: bool,
+    // Index and path of the image the rotation preview was made for and its textures turned by 90, 180 and 270 degrees
+    // Empty if the image couldn't be rotated
+    rotation_preview: Option<((usize, String), Vec<imgui::TextureId>)>,
+    // Directions waiting for confirmation before their connections are cleared
+    directions_to_clear: Vec<Direction>,
+    // Tile whose connections were copied
+    connection_clipboard: Option<TileData>,
+    // Problems found by the last validation, the report window is open while this is set
+    validation_issues: Option<Vec<ValidationIssue>>,
+    // Reasons the tileset is likely to contradict, shown along with the validation
+    solvability_notes: Vec<String>,
+    model: Model,
+    adjacency: Adjacency,
+    // Sockets and rotation of every tile the connections were last worked out from
+    socket_snapshot: Vec<([Socket; 4], u8)>,
+    pattern_size: u32,
+    // Size of the patterns currently loaded, 1 when the tiles are regular images
+    loaded_pattern_size: u32,
 }
 
 impl TileCreationComponent {
-    pub fn new(concept_manager: Rc<Mutex<ConceptManager>>) -> Self {
+    pub fn new(concept_manager: Rc<Mutex<ConceptManager>>, config: &Config) -> Self {
         let mut comp = Self {
             parent: EntityId::MAX,
             id: (EntityId::MAX, TypeId::of::<Self>(), 0),
@@ -118,8 +254,39 @@ impl TileCreationComponent {
             tile_being_modified: None,
             selected_direction: None,
             tile_selected: 0,
+            tile_search: String::new(),
+            categories_text: String::new(),
+            connection_error: None,
             direction_selected: 0,
-            run_algorithm: false,
+            context_tile: 0,
+            context_direction: 0,
+            run_algorithm: config.run_algorithm,
+            tileset_path: None,
+            // Textures can only be uploaded once the renderer is available
+            tileset_to_load: config.tileset_path.clone(),
+            tileset_error: None,
+            tileset_watcher: None,
+            show_adjacency_matrix: false,
+            show_weights: false,
+            show_layers: false,
+            layer_error: None,
+            sheet_to_load: None,
+            image_queue: VecDeque::new(),
+            images_queued: 0,
+            lock_tileset: true,
+            sheet_grid: SheetGrid::default(),
+            ascii_map: None,
+            flip_mirrored_image: true,
+            rotation_preview: None,
+            directions_to_clear: Vec::new(),
+            connection_clipboard: None,
+            validation_issues: None,
+            solvability_notes: Vec::new(),
+            model: config.model,
+            adjacency: Adjacency::Edge,
+            socket_snapshot: Vec::new(),
+            pattern_size: 3,
+            loaded_pattern_size: 1,
         };
 
         let mut concepts: HashMap<String, Box<dyn Any>> = HashMap::new();
@@ -129,11 +296,518 @@ impl TileCreationComponent {
             Box::<Vec<ImageData>>::default(),
         );
         concepts.insert("loaded_tiles".to_string(), Box::<Vec<TileData>>::default());
+        concepts.insert("layers".to_string(), Box::<Vec<Layer>>::default());
+        concepts.insert(
+            "layer_images".to_string(),
+            Box::<Vec<Vec<ImageData>>>::default(),
+        );
+        concepts.insert("tileset_path".to_string(), Box::<Option<String>>::default());
+        concepts.insert("run_algorithm".to_string(), Box::new(comp.run_algorithm));
+        concepts.insert("model".to_string(), Box::new(comp.model));
+        concepts.insert(
+            "pattern_size".to_string(),
+            Box::new(comp.loaded_pattern_size),
+        );
 
         comp.register_component(concept_manager, concepts);
 
         comp
     }
+
+    // Replaces the current images and tiles with the contents of a tileset file
+    fn load_tileset(
+        &mut self,
+        path: &str,
+        device: &Device,
+        queue: &Queue,
+        ui_manager: &mut gamezap::ui_manager::UiManager,
+        images: &mut Vec<ImageData>,
+        tiles: &mut Vec<TileData>,
+    ) -> std::io::Result<()> {
+        let tileset = Tileset::load(path)?;
+        self.use_tileset(tileset, device, queue, ui_manager, images, tiles)?;
+
+        self.tileset_path = Some(path.to_owned());
+        self.loaded_pattern_size = 1;
+        self.watch_tileset();
+        Ok(())
+    }
+
+    // Replaces the current images and tiles with a tileset made in Tiled
+    // It isn't remembered as the session tileset until it is saved in our own format
+    fn import_tiled_tileset(
+        &mut self,
+        path: &str,
+        device: &Device,
+        queue: &Queue,
+        ui_manager: &mut gamezap::ui_manager::UiManager,
+        images: &mut Vec<ImageData>,
+        tiles: &mut Vec<TileData>,
+    ) -> std::io::Result<()> {
+        let tileset = import_tiled(path)?;
+        self.use_tileset(tileset, device, queue, ui_manager, images, tiles)?;
+
+        self.tileset_path = None;
+        self.tileset_watcher = None;
+        self.loaded_pattern_size = 1;
+        Ok(())
+    }
+
+    // Replaces the current images and tiles with the ones packed in a bundle
+    // The unpacked copy is temporary, so it isn't remembered as the session tileset until it is saved
+    fn import_bundle_tileset(
+        &mut self,
+        path: &str,
+        device: &Device,
+        queue: &Queue,
+        ui_manager: &mut gamezap::ui_manager::UiManager,
+        images: &mut Vec<ImageData>,
+        tiles: &mut Vec<TileData>,
+    ) -> std::io::Result<()> {
+        let tileset = import_bundle(path)?;
+        self.use_tileset(tileset, device, queue, ui_manager, images, tiles)?;
+
+        self.tileset_path = None;
+        self.tileset_watcher = None;
+        self.loaded_pattern_size = 1;
+        Ok(())
+    }
+
+    // Replaces the current images and tiles with the patterns of an example image
+    fn load_example(
+        &mut self,
+        path: &str,
+        device: &Device,
+        queue: &Queue,
+        ui_manager: &mut gamezap::ui_manager::UiManager,
+        images: &mut Vec<ImageData>,
+        tiles: &mut Vec<TileData>,
+    ) -> std::io::Result<()> {
+        let tileset = overlapping_tileset(path, self.pattern_size)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        self.use_tileset(tileset, device, queue, ui_manager, images, tiles)?;
+
+        self.tileset_path = None;
+        self.tileset_watcher = None;
+        self.loaded_pattern_size = self.pattern_size;
+        Ok(())
+    }
+
+    fn use_tileset(
+        &mut self,
+        tileset: Tileset,
+        device: &Device,
+        queue: &Queue,
+        ui_manager: &mut gamezap::ui_manager::UiManager,
+        images: &mut Vec<ImageData>,
+        tiles: &mut Vec<TileData>,
+    ) -> std::io::Result<()> {
+        tileset.check_images()?;
+
+        images.clear();
+        for TileImage {
+            path,
+            name,
+            frames,
+            frame_duration,
+        } in tileset.images
+        {
+            let mut image = Self::load_animation(device, queue, ui_manager, path, frames);
+            image.name = name;
+            image.frame_duration = frame_duration;
+            images.push(image);
+        }
+        *tiles = tileset.tiles;
+
+        self.adjacency = tileset.adjacency;
+        // The saved connections may have been edited by hand, so they are kept until a socket changes
+        self.socket_snapshot = Self::sockets_of(tiles);
+        self.tile_being_modified = None;
+        self.tile_selected = 0;
+        Ok(())
+    }
+
+    // Forgets the whole tileset, including the file it came from
+    fn clear_workspace(&mut self, images: &mut Vec<ImageData>, tiles: &mut Vec<TileData>) {
+        images.clear();
+        tiles.clear();
+        self.tileset_path = None;
+        self.tileset_watcher = None;
+        self.tileset_error = None;
+        self.tile_being_modified = None;
+        self.tile_selected = 0;
+        self.connection_clipboard = None;
+        self.validation_issues = None;
+        self.solvability_notes.clear();
+        self.adjacency = Adjacency::Edge;
+        self.socket_snapshot.clear();
+        self.image_queue.clear();
+        self.loaded_pattern_size = 1;
+    }
+
+    fn sockets_of(tiles: &[TileData]) -> Vec<([Socket; 4], u8)> {
+        tiles
+            .iter()
+            .map(|tile| (tile.sockets.clone(), tile.rotation))
+            .collect()
+    }
+
+    // Turns a tile to a new rotation, along with its connections
+    // Connections other tiles list to it are given the side that now faces them,
+    // so every connection still holds after the turn
+    fn set_rotation(tiles: &mut [TileData], index: usize, rotation: u8) {
+        let quarter_turns = (rotation + 4 - tiles[index].rotation % 4) % 4;
+        tiles[index].rotate_connections(quarter_turns);
+        tiles[index].rotation = rotation;
+        let id = tiles[index].id;
+        for tile in tiles.iter_mut() {
+            for direction in DIRECTIONS {
+                for (other, side) in tile.valid_tiles_mut(direction) {
+                    if *other == id {
+                        *side = side.rotated(4 - quarter_turns);
+                    }
+                }
+            }
+        }
+    }
+
+    // Adds a connection to the list of a side, unless it is already there
+    // The tile is picked by its position, and the connection keeps the id of the tile there
+    // Connections to missing tiles would break the generation, so they are rejected
+    fn add_connection(
+        connections: &mut Vec<TileConnection>,
+        (tile, side): (usize, Direction),
+        ids: &[TileId],
+    ) -> Result<(), String> {
+        let Some(&id) = ids.get(tile) else {
+            return Err(format!("Tile {tile} doesn't exist"));
+        };
+        let connection = (id, side);
+        if !connections.contains(&connection) {
+            connections.push(connection);
+        }
+        Ok(())
+    }
+
+    // Connections go by id, so the other tiles only drop the ones to the removed tile
+    // The tiles after it move down along with their images
+    fn remove_tile(tiles: &mut Vec<TileData>, index: usize) {
+        let removed = tiles.remove(index);
+        for (index, tile) in tiles.iter_mut().enumerate() {
+            tile.image_index = index;
+            tile.forget_tile(removed.id);
+        }
+    }
+
+    // The gallery shows tile i with image i, so there is one tile for every image
+    // Tiles past the last image are dropped and images without a tile get a new one
+    fn match_tiles_to_images(tiles: &mut Vec<TileData>, image_count: usize) {
+        tiles.truncate(image_count);
+        while tiles.len() < image_count {
+            tiles.push(TileData::new(tiles.len()));
+        }
+    }
+
+    // Opens the modification window for a tile
+    fn modify_tile(&mut self, index: usize, tiles: &[TileData]) {
+        self.tile_being_modified = Some(index);
+        let mut categories = tiles[index].categories.iter().cloned().collect::<Vec<_>>();
+        categories.sort();
+        self.categories_text = categories.join(", ");
+    }
+
+    // Adds a single animated tile out of the frames that were picked
+    fn add_animation(
+        &mut self,
+        frames: std::io::Result<Option<Vec<String>>>,
+        device: &Device,
+        queue: &Queue,
+        ui_manager: &mut gamezap::ui_manager::UiManager,
+        images: &mut Vec<ImageData>,
+        tiles: &mut Vec<TileData>,
+    ) {
+        let mut frames = match frames {
+            Ok(Some(frames)) if !frames.is_empty() => frames,
+            Ok(Some(_)) => {
+                self.tileset_error = Some("No frames found".to_string());
+                return;
+            }
+            Ok(None) => return,
+            Err(err) => {
+                self.tileset_error = Some(err.to_string());
+                return;
+            }
+        };
+
+        let first = frames.remove(0);
+        images.push(Self::load_animation(
+            device, queue, ui_manager, first, frames,
+        ));
+        tiles.push(TileData::new(images.len() - 1));
+        self.tileset_error = None;
+    }
+
+    // Adds the image on the system clipboard as a new tile
+    fn paste_image(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        ui_manager: &mut gamezap::ui_manager::UiManager,
+        images: &mut Vec<ImageData>,
+        tiles: &mut Vec<TileData>,
+    ) {
+        match save_clipboard_image() {
+            Ok(Some(path)) => {
+                let mut renderer = ui_manager.imgui_renderer.lock().unwrap();
+                let (id, size) = Texture::load_ui_image(device, queue, &mut renderer, path.clone());
+                images.push(ImageData::new(path, id, size));
+                tiles.push(TileData::new(images.len() - 1));
+                self.tileset_error = None;
+            }
+            Ok(None) => {
+                self.tileset_error = Some("There is no image on the clipboard".to_string());
+            }
+            Err(err) => self.tileset_error = Some(err.to_string()),
+        }
+    }
+
+    // Uploads the first image and every following frame of a tile
+    fn load_animation(
+        device: &Device,
+        queue: &Queue,
+        ui_manager: &mut gamezap::ui_manager::UiManager,
+        path: String,
+        frames: Vec<String>,
+    ) -> ImageData {
+        let mut renderer = ui_manager.imgui_renderer.lock().unwrap();
+        let (id, size) = Texture::load_ui_image(device, queue, &mut renderer, path.clone());
+        let mut image = ImageData::new(path, id, size);
+        for frame in frames {
+            let (id, _) = Texture::load_ui_image(device, queue, &mut renderer, frame.clone());
+            image.add_frame(frame, id);
+        }
+        image
+    }
+
+    // Reads a tileset file as a layer to generate on top of the board
+    fn load_layer(
+        path: &str,
+        device: &Device,
+        queue: &Queue,
+        ui_manager: &mut gamezap::ui_manager::UiManager,
+    ) -> std::io::Result<(Layer, Vec<ImageData>)> {
+        let tileset = Tileset::load(path)?;
+        tileset.check_images()?;
+
+        let mut images = Vec::new();
+        for TileImage {
+            path,
+            name,
+            frames,
+            frame_duration,
+        } in tileset.images
+        {
+            let mut image = Self::load_animation(device, queue, ui_manager, path, frames);
+            image.name = name;
+            image.frame_duration = frame_duration;
+            images.push(image);
+        }
+        let mut tiles = tileset.tiles;
+        if tileset.adjacency == Adjacency::Corner {
+            connect_corners(&mut tiles);
+        }
+
+        let name = std::path::Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok((Layer::new(name, path.to_owned(), tiles), images))
+    }
+
+    // Adds every tile of a sheet as a separate tile
+    #[allow(clippy::too_many_arguments)]
+    fn load_spritesheet(
+        &mut self,
+        path: &str,
+        grid: SheetGrid,
+        device: &Device,
+        queue: &Queue,
+        ui_manager: &mut gamezap::ui_manager::UiManager,
+        images: &mut Vec<ImageData>,
+        tiles: &mut Vec<TileData>,
+    ) -> image::ImageResult<()> {
+        let mut renderer = ui_manager.imgui_renderer.lock().unwrap();
+        for tile_path in split_tile_sheet(path, grid)? {
+            let (id, size) =
+                Texture::load_ui_image(device, queue, &mut renderer, tile_path.clone());
+            images.push(ImageData::new(tile_path, id, size));
+            tiles.push(TileData::new(images.len() - 1));
+        }
+        Ok(())
+    }
+
+    // Uploads the image of a tile turned by 90, 180 and 270 degrees clockwise
+    fn rotated_textures(
+        index: usize,
+        device: &Device,
+        queue: &Queue,
+        ui_manager: &mut gamezap::ui_manager::UiManager,
+        images: &[ImageData],
+    ) -> image::ImageResult<Vec<imgui::TextureId>> {
+        let path = images[index].path();
+        let image = image::open(path)?;
+        let directory = std::env::temp_dir().join("wfc_rotations");
+        std::fs::create_dir_all(&directory)?;
+        let stem = std::path::Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut textures = Vec::new();
+        for (degrees, rotated) in [
+            (90, image.rotate90()),
+            (180, image.rotate180()),
+            (270, image.rotate270()),
+        ] {
+            let rotated_path = directory.join(format!("{stem}_{index}_{degrees}.png"));
+            rotated.save(&rotated_path)?;
+            let (id, _) = Texture::load_ui_image(
+                device,
+                queue,
+                &mut ui_manager.imgui_renderer.lock().unwrap(),
+                rotated_path.to_string_lossy().into_owned(),
+            );
+            textures.push(id);
+        }
+        Ok(textures)
+    }
+
+    // Adds a mirror image of a tile as a new tile
+    // Tiles the original connects to get the mirrored connections back, so the new tile fits in right away
+    #[allow(clippy::too_many_arguments)]
+    fn add_mirrored_tile(
+        &mut self,
+        index: usize,
+        horizontal: bool,
+        device: &Device,
+        queue: &Queue,
+        ui_manager: &mut gamezap::ui_manager::UiManager,
+        images: &mut Vec<ImageData>,
+        tiles: &mut Vec<TileData>,
+    ) -> image::ImageResult<()> {
+        let path = images[index].path().to_owned();
+        let image_path = if self.flip_mirrored_image {
+            let image = image::open(&path)?;
+            let flipped = if horizontal {
+                image.fliph()
+            } else {
+                image.flipv()
+            };
+            let directory = std::env::temp_dir().join("wfc_mirrored");
+            std::fs::create_dir_all(&directory)?;
+            let stem = std::path::Path::new(&path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let suffix = if horizontal { "h" } else { "v" };
+            let flipped_path = directory.join(format!("{stem}_{index}_{suffix}.png"));
+            flipped.save(&flipped_path)?;
+            flipped_path.to_string_lossy().into_owned()
+        } else {
+            path
+        };
+
+        let (id, size) = Texture::load_ui_image(
+            device,
+            queue,
+            &mut ui_manager.imgui_renderer.lock().unwrap(),
+            image_path.clone(),
+        );
+        let mut image = ImageData::new(image_path, id, size);
+        let suffix = if horizontal {
+            "mirrored H"
+        } else {
+            "mirrored V"
+        };
+        image.name = format!("{} {suffix}", images[index].label(index));
+        images.push(image);
+
+        let positions = tile_positions(tiles);
+        let original = tiles[index].id;
+        let new_id = new_tile_id();
+        let mut tile = tiles[index].clone();
+        tile.image_index = tiles.len();
+        tile.id = new_id;
+        tile.mirror(horizontal);
+        tile.contextual_weight.clear();
+        // The original next to itself becomes the mirrored tile next to itself
+        for direction in DIRECTIONS {
+            for (other, _) in tile.valid_tiles_mut(direction) {
+                if *other == original {
+                    *other = new_id;
+                }
+            }
+        }
+        for direction in DIRECTIONS {
+            for (other, _) in tile.valid_tiles(direction).clone() {
+                let side = tile.side_facing(direction);
+                let connections = if other == new_id {
+                    tile.valid_tiles_mut(direction.opposite())
+                } else if let Some(&position) = positions.get(&other) {
+                    tiles[position].valid_tiles_mut(direction.opposite())
+                } else {
+                    continue;
+                };
+                if !connections.contains(&(new_id, side)) {
+                    connections.push((new_id, side));
+                }
+            }
+        }
+        tiles.push(tile);
+        Ok(())
+    }
+
+    fn save_tileset(
+        &mut self,
+        path: &str,
+        images: &[ImageData],
+        tiles: &[TileData],
+    ) -> std::io::Result<()> {
+        self.tileset(images, tiles).save(path)?;
+
+        self.tileset_path = Some(path.to_owned());
+        self.watch_tileset();
+        Ok(())
+    }
+
+    // Starts over watching the current tileset file, which also forgets our own writes to it
+    fn watch_tileset(&mut self) {
+        self.tileset_watcher = None;
+        if let Some(path) = &self.tileset_path {
+            match TilesetWatcher::new(path) {
+                Ok(watcher) => self.tileset_watcher = Some(watcher),
+                Err(err) => self.tileset_error = Some(format!("Can't watch the tileset: {err}")),
+            }
+        }
+    }
+
+    // Bundles the loaded images and tiles the way they are stored on disk
+    fn tileset(&self, images: &[ImageData], tiles: &[TileData]) -> Tileset {
+        Tileset {
+            images: images
+                .iter()
+                .map(|image| TileImage {
+                    path: image.path.clone(),
+                    name: image.name.clone(),
+                    frames: image.frames.iter().map(|(path, _)| path.clone()).collect(),
+                    frame_duration: image.frame_duration,
+                })
+                .collect(),
+            tiles: tiles.to_vec(),
+            adjacency: self.adjacency,
+            ..Default::default()
+        }
+    }
 }
 
 impl ComponentSystem for TileCreationComponent {
@@ -166,11 +840,18 @@ impl ComponentSystem for TileCreationComponent {
         entities: &mut Vec<Entity>,
     ) {
         let concept_manager = concept_manager.lock().unwrap();
-        entities[1].enabled = !concept_manager
+        let enabled = !concept_manager
             .get_concept::<Vec<TileData>>(self.id, "loaded_tiles".to_string())
             .unwrap()
             .is_empty()
             && self.run_algorithm;
+
+        // Every other entity holds a canvas
+        for (index, entity) in entities.iter_mut().enumerate() {
+            if index as EntityId != self.parent {
+                entity.enabled = enabled;
+            }
+        }
     }
 
     // Main UI draw method
@@ -205,6 +886,87 @@ impl ComponentSystem for TileCreationComponent {
                 .unwrap()
                 .clone();
 
+            let mut layers = concept_manager
+                .get_concept::<Vec<Layer>>(self.id, "layers".to_string())
+                .unwrap()
+                .clone();
+            let mut layer_images = concept_manager
+                .get_concept::<Vec<Vec<ImageData>>>(self.id, "layer_images".to_string())
+                .unwrap()
+                .clone();
+
+            let was_running = self.run_algorithm;
+            let locked = self.run_algorithm && self.lock_tileset;
+
+            // Keyboard shortcuts, ignored while typing into a text field
+            let shortcuts_enabled = !ui_frame.io().want_text_input;
+            let key_pressed =
+                |key: imgui::Key| shortcuts_enabled && ui_frame.is_key_pressed_no_repeat(key);
+            let direction_pressed = [imgui::Key::N, imgui::Key::S, imgui::Key::E, imgui::Key::W]
+                .into_iter()
+                .position(key_pressed)
+                .map(Direction::from);
+            let add_pressed =
+                !locked && (key_pressed(imgui::Key::Enter) || key_pressed(imgui::Key::KeypadEnter));
+            let remove_pressed = !locked && key_pressed(imgui::Key::Delete);
+            let paste_pressed = !locked && ui_frame.io().key_ctrl && key_pressed(imgui::Key::V);
+            if key_pressed(imgui::Key::Space) {
+                self.run_algorithm = !self.run_algorithm;
+            }
+
+            // Tileset remembered from the last session
+            if let Some(path) = self.tileset_to_load.take() {
+                if let Err(err) =
+                    self.load_tileset(&path, &device, &queue, ui_manager, &mut images, &mut tiles)
+                {
+                    self.tileset_error = Some(err.to_string());
+                }
+            }
+
+            if paste_pressed {
+                self.paste_image(&device, &queue, ui_manager, &mut images, &mut tiles);
+            }
+
+            // Waits with the rest of the images while the tileset is locked
+            let batch = if locked {
+                0
+            } else {
+                self.image_queue.len().min(IMAGES_PER_FRAME)
+            };
+            for path in self.image_queue.drain(..batch) {
+                let (id, size) = Texture::load_ui_image(
+                    &device,
+                    &queue,
+                    &mut ui_manager.imgui_renderer.lock().unwrap(),
+                    path.clone(),
+                );
+                images.push(ImageData::new(path, id, size));
+                tiles.push(TileData::new(images.len() - 1));
+            }
+            if self.image_queue.is_empty() {
+                self.images_queued = 0;
+            }
+
+            // Picks up edits made to the tileset file in another program
+            if self
+                .tileset_watcher
+                .as_mut()
+                .is_some_and(TilesetWatcher::poll)
+            {
+                if let Some(path) = self.tileset_path.clone() {
+                    if let Err(err) = self.load_tileset(
+                        &path,
+                        &device,
+                        &queue,
+                        ui_manager,
+                        &mut images,
+                        &mut tiles,
+                    ) {
+                        self.tileset_error = Some(format!("Reloading failed: {err}"));
+                    }
+                }
+            }
+
             ui_frame
                 .window("Main window")
                 .title_bar(false)
@@ -232,15 +994,12 @@ impl ComponentSystem for TileCreationComponent {
                         .unwrap();
                     ui_frame.table_next_row();
                     ui_frame.table_set_column_index(0);
-                    for (i, ImageData { id, size, .. }) in images.clone().iter().enumerate() {
+                    for (i, image) in images.clone().iter().enumerate() {
+                        let ImageData { id, .. } = image;
                         if i % 4 == 0 {
                             ui_frame.table_next_row();
                             ui_frame.table_set_column_index(0);
                         }
-                        let aspect_ratio = size[1] / size[0];
-                        if aspect_ratio != 1.0 {
-                            panic!("Select a square image");
-                        }
 
                         let text = format!("{i}");
                         let frame_padding = unsafe { ui_frame.style().frame_padding[0] * 2.0 };
@@ -258,9 +1017,67 @@ impl ComponentSystem for TileCreationComponent {
 
                         // Button that marks the tile for modification, opens up the modification window
                         if ui_frame.image_button(format!("Image button {i}"), *id, [100.0, 100.0]) {
-                            self.tile_being_modified = Some(i);
+                            self.modify_tile(i, &tiles);
+                        }
+                        // Badge with the number of connections, red when a side has none
+                        if let Some(tile) = tiles.get(i) {
+                            let missing = DIRECTIONS
+                                .into_iter()
+                                .filter(|&direction| tile.valid_tiles(direction).is_empty())
+                                .map(|direction| direction.to_string())
+                                .collect::<Vec<_>>();
+                            let badge = tile.total_connections().to_string();
+                            let badge_size = ui_frame.calc_text_size(&badge);
+                            let corner = ui_frame.item_rect_max();
+                            let min = [
+                                corner[0] - badge_size[0] - 8.0,
+                                ui_frame.item_rect_min()[1] + 2.0,
+                            ];
+                            let max = [corner[0] - 2.0, min[1] + badge_size[1] + 2.0];
+                            let color = if missing.is_empty() {
+                                [0.2, 0.2, 0.2, 0.8]
+                            } else {
+                                [0.8, 0.1, 0.1, 0.9]
+                            };
+                            let draw_list = ui_frame.get_window_draw_list();
+                            draw_list
+                                .add_rect(min, max, color)
+                                .filled(true)
+                                .rounding(4.0)
+                                .build();
+                            draw_list.add_text(
+                                [min[0] + 3.0, min[1] + 1.0],
+                                [1.0, 1.0, 1.0, 1.0],
+                                &badge,
+                            );
+                            if !missing.is_empty() && ui_frame.is_item_hovered() {
+                                ui_frame.tooltip_text(format!(
+                                    "No {} connections\nIt only fits along the matching edges of the canvas and causes contradictions anywhere else",
+                                    missing.join(", ")
+                                ));
+                            }
+                        }
+                        // Dropping the thumbnail on a direction tab of the tile being modified connects them
+                        if let Some(tooltip) =
+                            ui_frame.drag_drop_source_config("TILE").begin_payload(i)
+                        {
+                            imgui::Image::new(*id, [50.0, 50.0]).build(ui_frame);
+                            ui_frame.text(image.label(i));
+                            tooltip.end();
                         }
 
+                        // Tile name, centered under the thumbnail
+                        let name_size = ui_frame.calc_text_size(&image.name)[0];
+                        let available = ui_frame.content_region_avail()[0];
+                        let off = (available - name_size) * 0.5;
+                        if off > 0.0 {
+                            ui_frame.set_cursor_pos([
+                                ui_frame.cursor_pos()[0] + off,
+                                ui_frame.cursor_pos()[1],
+                            ]);
+                        }
+                        ui_frame.text(&image.name);
+
                         ui_frame.separator();
                         let frame_padding = unsafe { ui_frame.style().frame_padding[0] * 2.0 };
                         let size =
@@ -275,9 +1092,20 @@ impl ComponentSystem for TileCreationComponent {
                         }
 
                         // Button that deletes the current image and its corresponding tile
-                        if ui_frame.button(format!("Remove image {i}")) {
+                        // The other tiles stop referring to it
+                        let lock = ui_frame.begin_disabled(locked);
+                        let remove = ui_frame.button(format!("Remove image {i}"));
+                        lock.end();
+                        if remove {
                             images.remove(i);
-                            tiles.remove(i);
+                            Self::remove_tile(&mut tiles, i);
+                            self.tile_being_modified = match self.tile_being_modified {
+                                Some(index) if index == i => None,
+                                Some(index) if index > i => Some(index - 1),
+                                index => index,
+                            };
+                            self.tile_selected =
+                                self.tile_selected.min(tiles.len().saturating_sub(1));
                         }
                         ui_frame.spacing();
                         ui_frame.table_next_column();
@@ -287,7 +1115,7 @@ impl ComponentSystem for TileCreationComponent {
                     ui_frame.table_next_row();
 
                     // Button that opens a file dialogue for image selection
-                    // The program will panic if a non-square image is selected
+                    // Non-square images are reported by the validator
                     ui_frame
                         .window("image selector")
                         .title_bar(false)
@@ -295,39 +1123,406 @@ impl ComponentSystem for TileCreationComponent {
                         .movable(false)
                         .draw_background(false)
                         .always_auto_resize(true)
-                        .position([450.0, 180.0], imgui::Condition::Always)
+                        .position([400.0, 150.0], imgui::Condition::Always)
                         .build(|| {
-                            if ui_frame.button("Load image") {
-                                let file = FileDialog::new().pick_files();
-                                if let Some(paths) = file {
-                                    for path in paths {
-                                        let (id, size) = Texture::load_ui_image(
-                                            &device,
-                                            &queue,
-                                            &mut ui_manager.imgui_renderer.lock().unwrap(),
-                                            (*path.to_str().unwrap()).to_owned(),
-                                        );
-                                        images.push(ImageData::new(
-                                            path.to_str().unwrap().to_owned(),
-                                            id,
-                                            size,
-                                        ));
-                                        tiles.push(TileData::new(images.len() - 1));
+                            let lock = ui_frame.begin_disabled(locked);
+                            ui_frame.radio_button("Tiled", &mut self.model, Model::Tiled);
+                            ui_frame.same_line();
+                            ui_frame.radio_button(
+                                "Overlapping",
+                                &mut self.model,
+                                Model::Overlapping,
+                            );
+
+                            if self.model == Model::Tiled {
+                                ui_frame.radio_button(
+                                    "Edge adjacency",
+                                    &mut self.adjacency,
+                                    Adjacency::Edge,
+                                );
+                                ui_frame.same_line();
+                                ui_frame.radio_button(
+                                    "Corner adjacency",
+                                    &mut self.adjacency,
+                                    Adjacency::Corner,
+                                );
+                                ui_frame.same_line();
+                                ui_frame.radio_button(
+                                    "Socket adjacency",
+                                    &mut self.adjacency,
+                                    Adjacency::Socket,
+                                );
+
+                                let mut picked = Vec::new();
+                                if ui_frame.button("Load image") {
+                                    picked = FileDialog::new()
+                                        .pick_files()
+                                        .into_iter()
+                                        .flatten()
+                                        .map(|path| path.to_string_lossy().into_owned())
+                                        .collect();
+                                }
+                                ui_frame.same_line();
+                                // Every image in the folder becomes a tile, in the order of their names
+                                if ui_frame.button("Load folder") {
+                                    match FileDialog::new().pick_folder().map(frames_in_folder) {
+                                        Some(Ok(paths)) => picked = paths,
+                                        Some(Err(err)) => self.tileset_error = Some(err.to_string()),
+                                        None => {}
+                                    }
+                                }
+                                self.images_queued += picked.len();
+                                self.image_queue.extend(picked);
+                                ui_frame.same_line();
+                                if ui_frame.button("Paste image") {
+                                    self.paste_image(
+                                        &device,
+                                        &queue,
+                                        ui_manager,
+                                        &mut images,
+                                        &mut tiles,
+                                    );
+                                }
+
+                                // Animated tiles, from a folder of frames or a strip of square frames
+                                ui_frame.same_line();
+                                if ui_frame.button("Load animation") {
+                                    let frames = FileDialog::new()
+                                        .pick_folder()
+                                        .map(frames_in_folder)
+                                        .transpose();
+                                    self.add_animation(
+                                        frames,
+                                        &device,
+                                        &queue,
+                                        ui_manager,
+                                        &mut images,
+                                        &mut tiles,
+                                    );
+                                }
+                                ui_frame.same_line();
+                                if ui_frame.button("Load sprite sheet") {
+                                    let frames = FileDialog::new()
+                                        .add_filter("Image", &["png", "jpg", "jpeg", "bmp"])
+                                        .pick_file()
+                                        .map(|path| {
+                                            split_sprite_sheet(path.to_str().unwrap()).map_err(
+                                                |err| {
+                                                    std::io::Error::new(
+                                                        std::io::ErrorKind::InvalidData,
+                                                        err,
+                                                    )
+                                                },
+                                            )
+                                        })
+                                        .transpose();
+                                    self.add_animation(
+                                        frames,
+                                        &device,
+                                        &queue,
+                                        ui_manager,
+                                        &mut images,
+                                        &mut tiles,
+                                    );
+                                }
+
+                                // A sheet with every tile laid out on a grid
+                                if ui_frame.button("Load tile sheet") {
+                                    self.sheet_to_load = FileDialog::new()
+                                        .add_filter("Image", &["png", "jpg", "jpeg", "bmp"])
+                                        .pick_file()
+                                        .map(|path| path.to_string_lossy().into_owned());
+                                }
+
+                                if !self.image_queue.is_empty() {
+                                    let loaded = self.images_queued - self.image_queue.len();
+                                    imgui::ProgressBar::new(loaded as f32 / self.images_queued as f32)
+                                        .overlay_text(format!(
+                                            "Loading images {loaded}/{}",
+                                            self.images_queued
+                                        ))
+                                        .build(ui_frame);
+                                }
+
+                                // Buttons for reading and writing the whole tileset as a file
+                                if ui_frame.button("Load tileset") {
+                                    let file = FileDialog::new()
+                                        .add_filter("Tileset", &["json"])
+                                        .pick_file();
+                                    if let Some(path) = file {
+                                        self.tileset_error = self
+                                            .load_tileset(
+                                                path.to_str().unwrap(),
+                                                &device,
+                                                &queue,
+                                                ui_manager,
+                                                &mut images,
+                                                &mut tiles,
+                                            )
+                                            .err()
+                                            .map(|err| err.to_string());
+                                    }
+                                }
+                                ui_frame.same_line();
+                                if ui_frame.button("Import from Tiled") {
+                                    let file = FileDialog::new()
+                                        .add_filter("Tiled tileset", &["tsx", "tmx"])
+                                        .pick_file();
+                                    if let Some(path) = file {
+                                        self.tileset_error = self
+                                            .import_tiled_tileset(
+                                                path.to_str().unwrap(),
+                                                &device,
+                                                &queue,
+                                                ui_manager,
+                                                &mut images,
+                                                &mut tiles,
+                                            )
+                                            .err()
+                                            .map(|err| err.to_string());
+                                    }
+                                }
+                                ui_frame.same_line();
+                                if ui_frame.button("Import bundle") {
+                                    let file = FileDialog::new()
+                                        .add_filter("Tileset bundle", &["zip"])
+                                        .pick_file();
+                                    if let Some(path) = file {
+                                        self.tileset_error = self
+                                            .import_bundle_tileset(
+                                                path.to_str().unwrap(),
+                                                &device,
+                                                &queue,
+                                                ui_manager,
+                                                &mut images,
+                                                &mut tiles,
+                                            )
+                                            .err()
+                                            .map(|err| err.to_string());
+                                    }
+                                }
+                            } else {
+                                // Patterns are read from an example image instead
+                                let input_width = ui_frame.push_item_width(100.0);
+                                ui_frame.slider("Pattern size", 2, 4, &mut self.pattern_size);
+                                input_width.end();
+                                if ui_frame.button("Load example") {
+                                    let file = FileDialog::new()
+                                        .add_filter("Image", &["png", "jpg", "jpeg", "bmp"])
+                                        .pick_file();
+                                    if let Some(path) = file {
+                                        self.tileset_error = self
+                                            .load_example(
+                                                path.to_str().unwrap(),
+                                                &device,
+                                                &queue,
+                                                ui_manager,
+                                                &mut images,
+                                                &mut tiles,
+                                            )
+                                            .err()
+                                            .map(|err| err.to_string());
+                                    }
+                                }
+                            }
+                            ui_frame.same_line();
+                            // Starts over with an empty tileset, which also clears the canvases
+                            if ui_frame.button("New tileset") {
+                                ui_frame.open_popup("New tileset");
+                            }
+                            ui_frame
+                                .modal_popup_config("New tileset")
+                                .always_auto_resize(true)
+                                .build(|| {
+                                    ui_frame.text(
+                                        "Remove every image and tile? Unsaved changes are lost.",
+                                    );
+                                    if ui_frame.button("Remove all") {
+                                        self.clear_workspace(&mut images, &mut tiles);
+                                        layers.clear();
+                                        layer_images.clear();
+                                        ui_frame.close_current_popup();
                                     }
+                                    ui_frame.same_line();
+                                    if ui_frame.button("Cancel") {
+                                        ui_frame.close_current_popup();
+                                    }
+                                });
+                            lock.end();
+                            ui_frame.same_line();
+                            if ui_frame.button("Save tileset") {
+                                let file = FileDialog::new()
+                                    .add_filter("Tileset", &["json"])
+                                    .save_file();
+                                if let Some(path) = file {
+                                    self.tileset_error = self
+                                        .save_tileset(path.to_str().unwrap(), &images, &tiles)
+                                        .err()
+                                        .map(|err| err.to_string());
+                                }
+                            }
+                            ui_frame.same_line();
+                            // Graph of the connections, to be rendered with Graphviz
+                            if ui_frame.button("Export graph") {
+                                let file = FileDialog::new()
+                                    .add_filter("Graphviz", &["dot", "gv"])
+                                    .save_file();
+                                if let Some(path) = file {
+                                    let dot = self.tileset(&images, &tiles).export_adjacency_dot();
+                                    self.tileset_error =
+                                        std::fs::write(path, dot).err().map(|err| err.to_string());
                                 }
                             }
+                            ui_frame.same_line();
+                            // The rules and every image in one file, for moving the tileset to another machine
+                            if ui_frame.button("Export bundle") {
+                                let file = FileDialog::new()
+                                    .add_filter("Tileset bundle", &["zip"])
+                                    .save_file();
+                                if let Some(path) = file {
+                                    self.tileset_error =
+                                        export_bundle(&self.tileset(&images, &tiles), path)
+                                            .err()
+                                            .map(|err| err.to_string());
+                                }
+                            }
+
+                            // Counts how often each tile shows up in an example image
+                            let lock = ui_frame.begin_disabled(locked);
+                            if ui_frame.button("Learn weights from sample") {
+                                let file = FileDialog::new()
+                                    .add_filter("Image", &["png", "jpg", "jpeg", "bmp"])
+                                    .pick_file();
+                                if let Some(path) = file {
+                                    match infer_weights_from_sample(path.to_str().unwrap(), &images)
+                                    {
+                                        Ok(weights) => {
+                                            for (tile, weight) in tiles.iter_mut().zip(weights) {
+                                                tile.weight = weight;
+                                            }
+                                            self.tileset_error = None;
+                                        }
+                                        Err(err) => self.tileset_error = Some(err.to_string()),
+                                    }
+                                }
+                            }
+                            ui_frame.same_line();
+                            // Reads connections from a map drawn with characters
+                            if ui_frame.button("Learn rules from ASCII map") {
+                                let file =
+                                    FileDialog::new().add_filter("Text", &["txt"]).pick_file();
+                                if let Some(path) = file {
+                                    match std::fs::read_to_string(path) {
+                                        Ok(text) => {
+                                            let mut characters = text
+                                                .chars()
+                                                .filter(|character| !character.is_whitespace())
+                                                .collect::<Vec<_>>();
+                                            characters.sort();
+                                            characters.dedup();
+                                            let mapping = characters
+                                                .into_iter()
+                                                .map(|character| (character, 0))
+                                                .collect();
+                                            self.ascii_map = Some((text, mapping));
+                                            self.tileset_error = None;
+                                        }
+                                        Err(err) => self.tileset_error = Some(err.to_string()),
+                                    }
+                                }
+                            }
+                            lock.end();
+                            if let Some(err) = &self.tileset_error {
+                                ui_frame.text_colored([0.8, 0.1, 0.1, 1.0], err);
+                            }
+
+                            if ui_frame.button("Adjacency matrix") {
+                                self.show_adjacency_matrix = !self.show_adjacency_matrix;
+                            }
+                            ui_frame.same_line();
+                            if ui_frame.button("Weights") {
+                                self.show_weights = !self.show_weights;
+                            }
+                            ui_frame.same_line();
+                            if ui_frame.button("Layers") {
+                                self.show_layers = !self.show_layers;
+                            }
+
+                            if ui_frame.button("Validate") {
+                                self.validation_issues = Some(validate(&images, &tiles));
+                                self.solvability_notes = precheck_solvability(&tiles);
+                            }
 
                             ui_frame.checkbox("Run algorithm", &mut self.run_algorithm);
+                            ui_frame.same_line();
+                            ui_frame.checkbox("Lock tileset", &mut self.lock_tileset);
+                            if ui_frame.is_item_hovered() {
+                                ui_frame.tooltip_text(
+                                    "Prevents edits to the tileset while the algorithm runs, since they restart the generation",
+                                );
+                            }
+                            ui_frame.same_line();
+                            ui_frame.text_disabled("(?)");
+                            if ui_frame.is_item_hovered() {
+                                ui_frame.tooltip_text(SHORTCUTS_HELP);
+                            }
                         });
                     button_style.pop();
                     button_style_2.pop();
                     image_table.end();
                 });
 
+            // The rotations are let go of once their image isn't being modified anymore
+            let modified_image = self
+                .tile_being_modified
+                .map(|index| (index, images[index].path().to_owned()));
+            if self
+                .rotation_preview
+                .as_ref()
+                .is_some_and(|(image, _)| Some(image) != modified_image.as_ref())
+            {
+                if let Some((_, textures)) = self.rotation_preview.take() {
+                    let mut renderer = ui_manager.imgui_renderer.lock().unwrap();
+                    for id in textures {
+                        renderer.textures.remove(id);
+                    }
+                }
+            }
+
             // Image modification window
             if let Some(tile_index) = self.tile_being_modified {
                 let ImageData { id, size, .. } = images[tile_index];
+                let tile_labels = images
+                    .iter()
+                    .enumerate()
+                    .map(|(i, image)| image.label(i))
+                    .collect::<Vec<_>>();
+                // Connections hold tile ids, the lists below show them by position
+                let tile_ids = tiles.iter().map(|tile| tile.id).collect::<Vec<_>>();
+                let positions = tile_positions(&tiles);
+                let label_of = |id: TileId| match positions.get(&id) {
+                    Some(&position) => tile_labels
+                        .get(position)
+                        .cloned()
+                        .unwrap_or_else(|| format!("{position}")),
+                    None => format!("Missing tile {id}"),
+                };
 
+                // Rotations are only made again once another image is being modified
+                if self.rotation_preview.is_none() {
+                    let textures =
+                        Self::rotated_textures(tile_index, &device, &queue, ui_manager, &images)
+                            .unwrap_or_default();
+                    let path = images[tile_index].path().to_owned();
+                    self.rotation_preview = Some(((tile_index, path), textures));
+                }
+                let rotations = self
+                    .rotation_preview
+                    .as_ref()
+                    .map(|(_, textures)| textures.clone())
+                    .unwrap_or_default();
+
+                let mut mirror = None;
                 ui_frame
                     .window("Modifying tile")
                     .collapsible(false)
@@ -335,6 +1530,8 @@ impl ComponentSystem for TileCreationComponent {
                     .position([20.0, 240.0], imgui::Condition::Always)
                     .size([560.0, 250.0], imgui::Condition::Always)
                     .build(|| {
+                        let lock = ui_frame.begin_disabled(locked);
+                        let mut open_clear_popup = false;
                         if let Some(main_table) = ui_frame.begin_table_with_flags(
                             "Main table",
                             2,
@@ -345,11 +1542,200 @@ impl ComponentSystem for TileCreationComponent {
                             ui_frame.table_set_column_index(0);
                             let aspect_ratio = size[1] / size[0];
                             imgui::Image::new(id, [100.0, 100.0 * aspect_ratio]).build(ui_frame);
+                            // The image next to its rotations, to check its symmetry before adding rotated tiles
+                            if !rotations.is_empty() {
+                                imgui::Image::new(id, [22.0, 22.0 * aspect_ratio]).build(ui_frame);
+                                for (turns, rotated) in rotations.iter().enumerate() {
+                                    // Width and height swap every quarter turn
+                                    let rotated_size = if turns % 2 == 0 {
+                                        [22.0 * aspect_ratio, 22.0]
+                                    } else {
+                                        [22.0, 22.0 * aspect_ratio]
+                                    };
+                                    ui_frame.same_line_with_spacing(0.0, 4.0);
+                                    imgui::Image::new(*rotated, rotated_size).build(ui_frame);
+                                }
+                            }
+                            let input_width = ui_frame.push_item_width(100.0);
+                            ui_frame
+                                .input_text("##Tile name", &mut images[tile_index].name)
+                                .hint("Name")
+                                .build();
+                            ui_frame
+                                .input_float("Weight", &mut tiles[tile_index].weight)
+                                .build();
+                            tiles[tile_index].weight = tiles[tile_index].weight.max(0.0);
+                            let mut rotation = tiles[tile_index].rotation as usize;
+                            if ui_frame.combo_simple_string(
+                                "Rotation",
+                                &mut rotation,
+                                &["0°", "90°", "180°", "270°"],
+                            ) {
+                                Self::set_rotation(&mut tiles, tile_index, rotation as u8);
+                            }
+                            ui_frame.checkbox(
+                                "Not next to itself",
+                                &mut tiles[tile_index].forbid_self_adjacency,
+                            );
+                            // Exactly once is a minimum and maximum count of 1
+                            ui_frame
+                                .input_scalar("Min count", &mut tiles[tile_index].min_count)
+                                .build();
+                            let min_count = tiles[tile_index].min_count;
+                            let mut limited = tiles[tile_index].max_count.is_some();
+                            if ui_frame.checkbox("Limit count", &mut limited) {
+                                tiles[tile_index].max_count = limited.then_some(min_count.max(1));
+                            }
+                            if let Some(max_count) = &mut tiles[tile_index].max_count {
+                                ui_frame.input_scalar("Max count", max_count).build();
+                                *max_count = (*max_count).max(min_count);
+                            }
+                            if images[tile_index].is_animated() {
+                                ui_frame.slider(
+                                    "Frame (ms)",
+                                    10,
+                                    1000,
+                                    &mut images[tile_index].frame_duration,
+                                );
+                            }
+                            if ui_frame
+                                .input_text("##Categories", &mut self.categories_text)
+                                .hint("Categories")
+                                .build()
+                            {
+                                tiles[tile_index].categories = self
+                                    .categories_text
+                                    .split(',')
+                                    .map(|category| category.trim().to_owned())
+                                    .filter(|category| !category.is_empty())
+                                    .collect();
+                            }
+                            input_width.end();
+                            // For tiles that are a rotated copy of another
+                            if ui_frame.button("Rotate CW") {
+                                tiles[tile_index].rotate_connections(1);
+                            }
+                            ui_frame.same_line();
+                            if ui_frame.button("Rotate CCW") {
+                                tiles[tile_index].rotate_connections(3);
+                            }
+                            // Adds a new tile that is a mirror image of this one
+                            if ui_frame.button("Mirror H") {
+                                mirror = Some(true);
+                            }
+                            ui_frame.same_line();
+                            if ui_frame.button("Mirror V") {
+                                mirror = Some(false);
+                            }
+                            ui_frame.checkbox("Flip image", &mut self.flip_mirrored_image);
+                            if let Some(_node) = ui_frame.tree_node("Context weights") {
+                                let mut contexts = tiles[tile_index]
+                                    .contextual_weight
+                                    .keys()
+                                    .copied()
+                                    .collect::<Vec<_>>();
+                                contexts.sort();
+                                let input_width = ui_frame.push_item_width(60.0);
+                                for (other, direction) in contexts {
+                                    let label = label_of(other);
+                                    let weight = tiles[tile_index]
+                                        .contextual_weight
+                                        .get_mut(&(other, direction))
+                                        .unwrap();
+                                    ui_frame
+                                        .input_float(
+                                            format!("{label} {direction}##{other}{direction}"),
+                                            weight,
+                                        )
+                                        .build();
+                                    *weight = weight.max(0.0);
+                                    ui_frame.same_line();
+                                    if ui_frame.small_button(format!("X##{other}{direction}")) {
+                                        tiles[tile_index]
+                                            .contextual_weight
+                                            .remove(&(other, direction));
+                                    }
+                                }
+                                input_width.end();
+
+                                let input_width = ui_frame.push_item_width(100.0);
+                                ui_frame.combo_simple_string(
+                                    "##Context tile",
+                                    &mut self.context_tile,
+                                    &tile_labels,
+                                );
+                                ui_frame.combo_simple_string(
+                                    "##Context direction",
+                                    &mut self.context_direction,
+                                    &["North", "South", "East", "West"],
+                                );
+                                input_width.end();
+                                ui_frame.same_line();
+                                if ui_frame.small_button("Add context") {
+                                    if let Some(&other) = tile_ids.get(self.context_tile) {
+                                        let weight = tiles[tile_index].weight;
+                                        tiles[tile_index]
+                                            .contextual_weight
+                                            .entry((other, Direction::from(self.context_direction)))
+                                            .or_insert(weight);
+                                    }
+                                }
+                            }
                             ui_frame.table_next_column();
-                            if let Some(directions_bar) = ui_frame.tab_bar("Tile directions") {
+                            if self.adjacency == Adjacency::Corner {
+                                // Connections follow from the corners, so only those are edited
+                                let corner_names =
+                                    ["North west", "North east", "South east", "South west"];
+                                let input_width = ui_frame.push_item_width(100.0);
+                                for (corner, name) in corner_names.iter().enumerate() {
+                                    ui_frame
+                                        .input_scalar(*name, &mut tiles[tile_index].corners[corner])
+                                        .build();
+                                }
+                                input_width.end();
+                            } else if self.adjacency == Adjacency::Socket {
+                                // Connections follow from the sockets, so only those are edited
+                                let input_width = ui_frame.push_item_width(100.0);
+                                for (side, name) in
+                                    ["North", "South", "East", "West"].iter().enumerate()
+                                {
+                                    let socket = &mut tiles[tile_index].sockets[side];
+                                    ui_frame
+                                        .input_text(format!("{name}##Socket"), &mut socket.id)
+                                        .build();
+                                    ui_frame.same_line();
+                                    ui_frame.checkbox(
+                                        format!("Symmetric##{name}"),
+                                        &mut socket.symmetric,
+                                    );
+                                    if !socket.symmetric {
+                                        ui_frame.same_line();
+                                        ui_frame.checkbox(
+                                            format!("Flipped##{name}"),
+                                            &mut socket.flipped,
+                                        );
+                                    }
+                                }
+                                input_width.end();
+                                // Also throws away connections edited by hand with edge adjacency
+                                if ui_frame.button("Compute adjacency") {
+                                    self.socket_snapshot.clear();
+                                }
+                            } else if let Some(directions_bar) = ui_frame.tab_bar("Tile directions")
+                            {
                                 // Tab bar for different image directions
                                 for dir in 0..4 {
                                     let mut temp_vec = Vec::new();
+                                    // Side of the tile being added that would face back, given its rotation
+                                    let facing_side = tiles.get(self.tile_selected).map(|tile| {
+                                        tile.side_facing(Direction::from(dir).opposite())
+                                    });
+                                    let facing_sides = tiles
+                                        .iter()
+                                        .map(|tile| {
+                                            tile.side_facing(Direction::from(dir).opposite())
+                                        })
+                                        .collect::<Vec<_>>();
                                     let tab_data = match dir {
                                         0 => ("North", &mut tiles[tile_index].north_valid_tiles),
                                         1 => ("South", &mut tiles[tile_index].south_valid_tiles),
@@ -357,20 +1743,55 @@ impl ComponentSystem for TileCreationComponent {
                                         3 => ("West", &mut tiles[tile_index].west_valid_tiles),
                                         _ => ("", &mut temp_vec),
                                     };
-                                    if let Some(dir_tab) = ui_frame.tab_item(tab_data.0) {
+                                    let tab_flags =
+                                        if direction_pressed == Some(Direction::from(dir)) {
+                                            imgui::TabItemFlags::SET_SELECTED
+                                        } else {
+                                            imgui::TabItemFlags::empty()
+                                        };
+                                    let dir_tab =
+                                        ui_frame.tab_item_with_flags(tab_data.0, None, tab_flags);
+                                    // Tiles dragged from the gallery onto the tab are connected in its direction
+                                    if let Some(target) = ui_frame.drag_drop_target() {
+                                        if let Some(Ok(payload)) = target
+                                            .accept_payload::<usize, _>(
+                                                "TILE",
+                                                imgui::DragDropFlags::empty(),
+                                            )
+                                        {
+                                            if let Some(side) = facing_sides.get(payload.data) {
+                                                let connection = (tile_ids[payload.data], *side);
+                                                if !tab_data.1.contains(&connection) {
+                                                    tab_data.1.push(connection);
+                                                }
+                                            }
+                                        }
+                                        target.pop();
+                                    }
+                                    if let Some(dir_tab) = dir_tab {
+                                        if self.selected_direction != Some(Direction::from(dir)) {
+                                            if let Some(side) = facing_side {
+                                                self.direction_selected = DIRECTIONS
+                                                    .iter()
+                                                    .position(|direction| *direction == side)
+                                                    .unwrap();
+                                            }
+                                        }
+                                        self.selected_direction = Some(Direction::from(dir));
                                         if tab_data.1.is_empty() {
                                             ui_frame
                                                 .text("No existing connections for this direction");
                                         } else {
                                             let bar = ui_frame.tab_bar("Thing").unwrap();
-                                            for (i, (index, direction)) in
+                                            for (i, (other, direction)) in
                                                 tab_data.1.clone().iter().enumerate()
                                             {
+                                                let label = label_of(*other);
                                                 if let Some(item) =
-                                                    ui_frame.tab_item(format!("{index}"))
+                                                    ui_frame.tab_item(format!("{label}##{i}"))
                                                 {
                                                     ui_frame.text(format!("{direction}"));
-                                                    if ui_frame.button("Remove") {
+                                                    if ui_frame.button("Remove") || remove_pressed {
                                                         tab_data.1.remove(i);
                                                     }
                                                     ui_frame.separator();
@@ -390,12 +1811,36 @@ impl ComponentSystem for TileCreationComponent {
                                         ) {
                                             ui_frame.table_next_row();
                                             ui_frame.table_set_column_index(0);
-                                            ui_frame.text("Tile id: ");
+                                            ui_frame.text("Tile: ");
                                             ui_frame.table_next_column();
-                                            let input_width = ui_frame.push_item_width(50.0);
-                                            ui_frame
-                                                .input_scalar("|", &mut self.tile_selected)
-                                                .build();
+                                            let input_width = ui_frame.push_item_width(150.0);
+                                            let preview = tile_labels
+                                                .get(self.tile_selected)
+                                                .cloned()
+                                                .unwrap_or_default();
+                                            // Searchable list of tile names
+                                            if let Some(combo) =
+                                                ui_frame.begin_combo("##Tile", preview)
+                                            {
+                                                ui_frame
+                                                    .input_text("##Search", &mut self.tile_search)
+                                                    .hint("Search")
+                                                    .build();
+                                                let search = self.tile_search.to_lowercase();
+                                                for (i, label) in tile_labels.iter().enumerate() {
+                                                    if !label.to_lowercase().contains(&search) {
+                                                        continue;
+                                                    }
+                                                    if ui_frame
+                                                        .selectable_config(format!("{label}##{i}"))
+                                                        .selected(i == self.tile_selected)
+                                                        .build()
+                                                    {
+                                                        self.tile_selected = i;
+                                                    }
+                                                }
+                                                combo.end();
+                                            }
                                             input_width.end();
                                             ui_frame.table_next_column();
                                             ui_frame.combo_simple_string(
@@ -409,13 +1854,24 @@ impl ComponentSystem for TileCreationComponent {
                                                 self.tile_selected,
                                                 Direction::from(self.direction_selected),
                                             );
-                                            if ui_frame.button("Add")
-                                                && !tab_data.1.contains(&tile_being_added)
-                                            {
-                                                tab_data.1.push(tile_being_added);
+                                            if ui_frame.button("Add") || add_pressed {
+                                                self.connection_error = Self::add_connection(
+                                                    tab_data.1,
+                                                    tile_being_added,
+                                                    &tile_ids,
+                                                )
+                                                .err();
+                                            }
+                                            if let Some(err) = &self.connection_error {
+                                                ui_frame.text_colored([0.8, 0.1, 0.1, 1.0], err);
                                             }
                                             table.end();
                                         }
+
+                                        if ui_frame.button(format!("Clear {}", tab_data.0)) {
+                                            self.directions_to_clear = vec![Direction::from(dir)];
+                                            open_clear_popup = true;
+                                        }
                                         dir_tab.end();
                                     }
                                 }
@@ -423,11 +1879,515 @@ impl ComponentSystem for TileCreationComponent {
                             }
                             main_table.end();
                         }
+                        lock.end();
                         if ui_frame.button("Close") {
                             self.tile_being_modified = None;
                             self.selected_direction = None;
                         }
+                        let _locked = ui_frame.begin_disabled(locked);
+                        if ui_frame.button("Copy connections") {
+                            self.connection_clipboard = Some(tiles[tile_index].clone());
+                        }
+                        if let Some(source) = &self.connection_clipboard {
+                            // Connections of the copied tile to itself become connections to this one
+                            let target = tiles[tile_index].id;
+                            let reindex = |connections: &Vec<TileConnection>| {
+                                connections
+                                    .iter()
+                                    .map(|&(other, direction)| {
+                                        if other == source.id {
+                                            (target, direction)
+                                        } else {
+                                            (other, direction)
+                                        }
+                                    })
+                                    .collect::<Vec<_>>()
+                            };
+                            ui_frame.same_line();
+                            if ui_frame.button("Paste connections") {
+                                for direction in [
+                                    Direction::North,
+                                    Direction::South,
+                                    Direction::East,
+                                    Direction::West,
+                                ] {
+                                    *tiles[tile_index].valid_tiles_mut(direction) =
+                                        reindex(source.valid_tiles(direction));
+                                }
+                            }
+                            if let Some(direction) = self.selected_direction {
+                                ui_frame.same_line();
+                                if ui_frame.button(format!("Paste {direction}")) {
+                                    *tiles[tile_index].valid_tiles_mut(direction) =
+                                        reindex(source.valid_tiles(direction));
+                                }
+                            }
+                        }
+                        // Copied connections kept in a file, to reuse them in other tilesets
+                        if ui_frame.button("Save connection template") {
+                            let file = FileDialog::new()
+                                .add_filter("Connection template", &["json"])
+                                .save_file();
+                            if let Some(path) = file {
+                                self.tileset_error =
+                                    ConnectionTemplate::from_tile(&tiles, tile_index)
+                                        .save(path)
+                                        .err()
+                                        .map(|err| err.to_string());
+                            }
+                        }
+                        ui_frame.same_line();
+                        if ui_frame.button("Load connection template") {
+                            let file = FileDialog::new()
+                                .add_filter("Connection template", &["json"])
+                                .pick_file();
+                            if let Some(path) = file {
+                                match ConnectionTemplate::load(path) {
+                                    Ok(template) => {
+                                        template.apply(&mut tiles, tile_index);
+                                        self.tileset_error = None;
+                                    }
+                                    Err(err) => self.tileset_error = Some(err.to_string()),
+                                }
+                            }
+                        }
+                        ui_frame.same_line();
+                        if ui_frame.button("Clear all") {
+                            self.directions_to_clear = vec![
+                                Direction::North,
+                                Direction::South,
+                                Direction::East,
+                                Direction::West,
+                            ];
+                            open_clear_popup = true;
+                        }
+                        ui_frame.same_line();
+                        ui_frame.text_disabled("(?)");
+                        if ui_frame.is_item_hovered() {
+                            ui_frame.tooltip_text(SHORTCUTS_HELP);
+                        }
+
+                        // Clearing can't be undone, so it has to be confirmed first
+                        if open_clear_popup {
+                            ui_frame.open_popup("Clear connections");
+                        }
+                        ui_frame
+                            .modal_popup_config("Clear connections")
+                            .always_auto_resize(true)
+                            .build(|| {
+                                let directions = self
+                                    .directions_to_clear
+                                    .iter()
+                                    .map(|direction| direction.to_string())
+                                    .collect::<Vec<_>>()
+                                    .join(", ");
+                                ui_frame.text(format!(
+                                    "Remove every {directions} connection of this tile?"
+                                ));
+                                if ui_frame.button("Clear") {
+                                    for direction in self.directions_to_clear.drain(..) {
+                                        tiles[tile_index].valid_tiles_mut(direction).clear();
+                                    }
+                                    ui_frame.close_current_popup();
+                                }
+                                ui_frame.same_line();
+                                if ui_frame.button("Cancel") {
+                                    self.directions_to_clear.clear();
+                                    ui_frame.close_current_popup();
+                                }
+                            });
+                    });
+
+                if let Some(horizontal) = mirror {
+                    self.tileset_error = self
+                        .add_mirrored_tile(
+                            tile_index,
+                            horizontal,
+                            &device,
+                            &queue,
+                            ui_manager,
+                            &mut images,
+                            &mut tiles,
+                        )
+                        .err()
+                        .map(|err| err.to_string());
+                }
+            }
+
+            // Grid of the picked tile sheet, the sheet is only cut once it is confirmed
+            if let Some(path) = self.sheet_to_load.clone() {
+                let mut opened = true;
+                let mut load = false;
+                ui_frame
+                    .window("Tile sheet")
+                    .opened(&mut opened)
+                    .always_auto_resize(true)
+                    .build(|| {
+                        ui_frame.text(&path);
+                        let input_width = ui_frame.push_item_width(100.0);
+                        let grid = &mut self.sheet_grid;
+                        ui_frame
+                            .input_scalar("Tile width", &mut grid.tile_width)
+                            .build();
+                        ui_frame
+                            .input_scalar("Tile height", &mut grid.tile_height)
+                            .build();
+                        ui_frame.input_scalar("Margin", &mut grid.margin).build();
+                        ui_frame.input_scalar("Spacing", &mut grid.spacing).build();
+                        input_width.end();
+                        grid.tile_width = grid.tile_width.max(1);
+                        grid.tile_height = grid.tile_height.max(1);
+                        let lock = ui_frame.begin_disabled(locked);
+                        load = ui_frame.button("Load");
+                        lock.end();
+                    });
+
+                if load {
+                    self.tileset_error = self
+                        .load_spritesheet(
+                            &path,
+                            self.sheet_grid,
+                            &device,
+                            &queue,
+                            ui_manager,
+                            &mut images,
+                            &mut tiles,
+                        )
+                        .err()
+                        .map(|err| err.to_string());
+                }
+                if load || !opened {
+                    self.sheet_to_load = None;
+                }
+            }
+
+            // Tile for every character of the picked ASCII map, the rules are only learned once confirmed
+            if let Some((text, mapping)) = &mut self.ascii_map {
+                let mut opened = true;
+                let mut learn = false;
+                ui_frame
+                    .window("ASCII map")
+                    .opened(&mut opened)
+                    .always_auto_resize(true)
+                    .build(|| {
+                        let tile_labels = std::iter::once("Unused".to_string())
+                            .chain(tiles.iter().enumerate().map(|(index, tile)| {
+                                match images.get(tile.image_index) {
+                                    Some(image) => image.label(index),
+                                    None => format!("{index}"),
+                                }
+                            }))
+                            .collect::<Vec<_>>();
+                        let input_width = ui_frame.push_item_width(100.0);
+                        for (character, tile) in mapping.iter_mut() {
+                            ui_frame.combo_simple_string(
+                                format!("'{character}'"),
+                                tile,
+                                &tile_labels,
+                            );
+                        }
+                        input_width.end();
+                        let lock = ui_frame.begin_disabled(locked);
+                        learn = ui_frame.button("Learn");
+                        lock.end();
                     });
+
+                if learn {
+                    let char_to_tile = mapping
+                        .iter()
+                        .filter(|(_, tile)| *tile > 0)
+                        .map(|&(character, tile)| (character, tile - 1))
+                        .collect();
+                    learn_from_ascii(text, &char_to_tile, &mut tiles);
+                }
+                if learn || !opened {
+                    self.ascii_map = None;
+                }
+            }
+
+            // Chance of every tile being picked when all of them fit
+            if self.show_weights {
+                let mut opened = true;
+                ui_frame
+                    .window("Tile weights")
+                    .opened(&mut opened)
+                    .size([320.0, 300.0], imgui::Condition::FirstUseEver)
+                    .build(|| {
+                        let _locked = ui_frame.begin_disabled(locked);
+                        let total = tiles.iter().map(|tile| tile.weight.max(0.0)).sum::<f32>();
+                        // Rescales the weights to add up to 1, which keeps their ratios
+                        if ui_frame.button("Normalize") && total > 0.0 {
+                            for tile in tiles.iter_mut() {
+                                tile.weight = tile.weight.max(0.0) / total;
+                            }
+                        }
+                        ui_frame.same_line();
+                        ui_frame.text(format!("Total: {total}"));
+                        ui_frame.separator();
+
+                        for (index, tile) in tiles.iter().enumerate() {
+                            let Some(image) = images.get(index) else {
+                                continue;
+                            };
+                            let probability = if total > 0.0 {
+                                tile.weight.max(0.0) / total
+                            } else {
+                                0.0
+                            };
+                            imgui::Image::new(image.id, [20.0, 20.0]).build(ui_frame);
+                            ui_frame.same_line();
+                            imgui::ProgressBar::new(probability)
+                                .size([150.0, 20.0])
+                                .overlay_text(format!("{:.1}%", probability * 100.0))
+                                .build(ui_frame);
+                            ui_frame.same_line();
+                            ui_frame.text(image.label(index));
+                        }
+                    });
+                self.show_weights = opened;
+            }
+
+            // Tilesets generated on top of the main one, each layer restricted by the one below it
+            if self.show_layers {
+                let mut opened = true;
+                let mut removed = None;
+                ui_frame
+                    .window("Layers")
+                    .opened(&mut opened)
+                    .size([420.0, 300.0], imgui::Condition::FirstUseEver)
+                    .build(|| {
+                        if ui_frame.button("Add layer") {
+                            let file = FileDialog::new()
+                                .add_filter("Tileset", &["json"])
+                                .pick_file();
+                            if let Some(path) = file {
+                                match Self::load_layer(
+                                    path.to_str().unwrap(),
+                                    &device,
+                                    &queue,
+                                    ui_manager,
+                                ) {
+                                    Ok((layer, images)) => {
+                                        layers.push(layer);
+                                        layer_images.push(images);
+                                        self.layer_error = None;
+                                    }
+                                    Err(err) => self.layer_error = Some(err.to_string()),
+                                }
+                            }
+                        }
+                        if let Some(err) = &self.layer_error {
+                            ui_frame.text_colored([0.8, 0.1, 0.1, 1.0], err);
+                        }
+                        if layers.is_empty() {
+                            ui_frame.text_disabled("Only the main tileset is generated");
+                        }
+
+                        for (index, layer) in layers.iter_mut().enumerate() {
+                            let below_images = match index {
+                                0 => &images,
+                                _ => &layer_images[index - 1],
+                            };
+                            let Some(_node) =
+                                ui_frame.tree_node(format!("{}##Layer {index}", layer.name))
+                            else {
+                                continue;
+                            };
+                            ui_frame
+                                .input_text(format!("Name##{index}"), &mut layer.name)
+                                .build();
+                            ui_frame.text_disabled(&layer.tileset_path);
+                            if ui_frame.small_button(format!("Remove##{index}")) {
+                                removed = Some(index);
+                            }
+
+                            // Every tile lists the tiles below it may be placed on, nothing checked fits anywhere
+                            ui_frame.separator();
+                            for (tile, image) in layer_images[index].iter().enumerate() {
+                                let Some(allowed) = layer.allowed_below.get_mut(tile) else {
+                                    continue;
+                                };
+                                imgui::Image::new(image.id, [20.0, 20.0]).build(ui_frame);
+                                ui_frame.same_line();
+                                ui_frame.text(format!("{} on", image.label(tile)));
+                                for (below, below_image) in below_images.iter().enumerate() {
+                                    ui_frame.same_line();
+                                    let mut checked = allowed.contains(&below);
+                                    if ui_frame
+                                        .checkbox(format!("##{index} {tile} {below}"), &mut checked)
+                                    {
+                                        if checked {
+                                            allowed.push(below);
+                                        } else {
+                                            allowed.retain(|&other| other != below);
+                                        }
+                                    }
+                                    ui_frame.same_line();
+                                    imgui::Image::new(below_image.id, [20.0, 20.0]).build(ui_frame);
+                                    if ui_frame.is_item_hovered() {
+                                        ui_frame.tooltip_text(below_image.label(below));
+                                    }
+                                }
+                            }
+                        }
+                    });
+
+                // The layer above now sits on a different tileset, so its rules no longer apply
+                if let Some(index) = removed {
+                    layers.remove(index);
+                    layer_images.remove(index);
+                    if let Some(layer) = layers.get_mut(index) {
+                        layer.allowed_below.iter_mut().for_each(Vec::clear);
+                    }
+                }
+                self.show_layers = opened;
+            }
+
+            // Every connection of the tileset at once, one tab per direction
+            // Row i and column j says whether tile i allows tile j on that side
+            if self.show_adjacency_matrix {
+                let tile_labels = images
+                    .iter()
+                    .enumerate()
+                    .map(|(i, image)| image.label(i))
+                    .collect::<Vec<_>>();
+                let mut opened = true;
+
+                ui_frame
+                    .window("Adjacency matrix")
+                    .opened(&mut opened)
+                    .size([500.0, 400.0], imgui::Condition::FirstUseEver)
+                    .horizontal_scrollbar(true)
+                    .build(|| {
+                        let _locked = ui_frame.begin_disabled(locked);
+                        let Some(directions_bar) = ui_frame.tab_bar("Matrix directions") else {
+                            return;
+                        };
+                        for dir in 0..4 {
+                            let direction = Direction::from(dir);
+                            let Some(dir_tab) = ui_frame.tab_item(format!("{direction}")) else {
+                                continue;
+                            };
+                            if let Some(matrix) = ui_frame.begin_table_with_flags(
+                                "Matrix",
+                                tiles.len() + 1,
+                                imgui::TableFlags::SIZING_FIXED_FIT
+                                    | imgui::TableFlags::BORDERS_INNER
+                                    | imgui::TableFlags::ROW_BG,
+                            ) {
+                                ui_frame.table_next_row();
+                                ui_frame.table_next_column();
+                                for label in &tile_labels {
+                                    ui_frame.table_next_column();
+                                    ui_frame.text(label);
+                                }
+
+                                for i in 0..tiles.len() {
+                                    ui_frame.table_next_row();
+                                    ui_frame.table_next_column();
+                                    ui_frame.text(&tile_labels[i]);
+                                    for j in 0..tiles.len() {
+                                        ui_frame.table_next_column();
+                                        let mut allowed = tiles[i].allows(direction, &tiles[j]);
+                                        if ui_frame
+                                            .checkbox(format!("##{dir} {i} {j}"), &mut allowed)
+                                        {
+                                            // The other tile gets the mirrored connection
+                                            let opposite = direction.opposite();
+                                            let (id_i, id_j) = (tiles[i].id, tiles[j].id);
+                                            tiles[i]
+                                                .valid_tiles_mut(direction)
+                                                .retain(|(other, _)| *other != id_j);
+                                            tiles[j]
+                                                .valid_tiles_mut(opposite)
+                                                .retain(|(other, _)| *other != id_i);
+                                            if allowed {
+                                                let side_j = tiles[j].side_facing(opposite);
+                                                let side_i = tiles[i].side_facing(direction);
+                                                tiles[i]
+                                                    .valid_tiles_mut(direction)
+                                                    .push((id_j, side_j));
+                                                tiles[j]
+                                                    .valid_tiles_mut(opposite)
+                                                    .push((id_i, side_i));
+                                            }
+                                        }
+                                    }
+                                }
+                                matrix.end();
+                            }
+                            dir_tab.end();
+                        }
+                        directions_bar.end();
+                    });
+                self.show_adjacency_matrix = opened;
+            }
+
+            if self.adjacency == Adjacency::Corner {
+                connect_corners(&mut tiles);
+            } else if self.adjacency == Adjacency::Socket {
+                let sockets = Self::sockets_of(&tiles);
+                if sockets != self.socket_snapshot {
+                    connect_sockets(&mut tiles);
+                    self.socket_snapshot = sockets;
+                }
+            }
+
+            // A broken tileset can't be run, the report shows what has to be fixed
+            // Tilesets that are only likely to contradict still run, but the report says why
+            if self.run_algorithm && !was_running {
+                let issues = validate(&images, &tiles);
+                self.solvability_notes = precheck_solvability(&tiles);
+                if issues.iter().any(|issue| issue.severity == Severity::Error) {
+                    self.run_algorithm = false;
+                    self.validation_issues = Some(issues);
+                } else if !self.solvability_notes.is_empty() {
+                    self.validation_issues = Some(issues);
+                }
+            }
+
+            if let Some(issues) = self.validation_issues.clone() {
+                let mut opened = true;
+                ui_frame
+                    .window("Validation")
+                    .opened(&mut opened)
+                    .size([500.0, 250.0], imgui::Condition::FirstUseEver)
+                    .build(|| {
+                        if issues.is_empty() && self.solvability_notes.is_empty() {
+                            ui_frame.text("No problems found");
+                        }
+                        for (i, issue) in issues.iter().enumerate() {
+                            let color = match issue.severity {
+                                Severity::Error => [0.8, 0.1, 0.1, 1.0],
+                                Severity::Warning => [0.8, 0.6, 0.1, 1.0],
+                            };
+                            if ui_frame.small_button(format!("Go to tile##{i}"))
+                                && issue.tile < tiles.len()
+                            {
+                                self.modify_tile(issue.tile, &tiles);
+                            }
+                            ui_frame.same_line();
+                            ui_frame.text_colored(color, &issue.message);
+                        }
+
+                        if !self.solvability_notes.is_empty() {
+                            ui_frame.separator();
+                            ui_frame.text("Likely to contradict:");
+                            for note in &self.solvability_notes {
+                                ui_frame.bullet_text(note);
+                            }
+                        }
+                    });
+                if !opened {
+                    self.validation_issues = None;
+                }
+            }
+
+            if tiles.len() != images.len() {
+                Self::match_tiles_to_images(&mut tiles, images.len());
+                self.tile_being_modified = self
+                    .tile_being_modified
+                    .filter(|&index| index < tiles.len());
             }
 
             // Assigns the modified data to its corresponding concepts
@@ -438,6 +2398,30 @@ impl ComponentSystem for TileCreationComponent {
             *concept_manager
                 .get_concept_mut::<Vec<TileData>>(self.id, "loaded_tiles".to_string())
                 .unwrap() = tiles;
+
+            *concept_manager
+                .get_concept_mut::<Vec<Layer>>(self.id, "layers".to_string())
+                .unwrap() = layers;
+
+            *concept_manager
+                .get_concept_mut::<Vec<Vec<ImageData>>>(self.id, "layer_images".to_string())
+                .unwrap() = layer_images;
+
+            *concept_manager
+                .get_concept_mut::<Option<String>>(self.id, "tileset_path".to_string())
+                .unwrap() = self.tileset_path.clone();
+
+            *concept_manager
+                .get_concept_mut::<bool>(self.id, "run_algorithm".to_string())
+                .unwrap() = self.run_algorithm;
+
+            *concept_manager
+                .get_concept_mut::<Model>(self.id, "model".to_string())
+                .unwrap() = self.model;
+
+            *concept_manager
+                .get_concept_mut::<u32>(self.id, "pattern_size".to_string())
+                .unwrap() = self.loaded_pattern_size;
         }
     }
 
@@ -463,3 +2447,89 @@ impl ComponentSystem for TileCreationComponent {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changing_the_rotation_keeps_connections_facing_back() {
+        // Every side of both tiles connects to every side of the other that faces back
+        let mut tiles = vec![TileData::new(0), TileData::new(1)];
+        let snapshot = tiles.clone();
+        for tile in &mut tiles {
+            for direction in DIRECTIONS {
+                *tile.valid_tiles_mut(direction) = snapshot
+                    .iter()
+                    .map(|other| (other.id, other.side_facing(direction.opposite())))
+                    .collect();
+            }
+        }
+
+        let positions = tile_positions(&tiles);
+        for rotation in [1, 3, 2, 0] {
+            TileCreationComponent::set_rotation(&mut tiles, 1, rotation);
+            assert_eq!(tiles[1].rotation, rotation);
+            for tile in &tiles {
+                for direction in DIRECTIONS {
+                    for &(other, side) in tile.valid_tiles(direction) {
+                        assert_eq!(tiles[positions[&other]].facing(side), direction.opposite());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn connections_to_missing_tiles_are_rejected() {
+        let ids = [10, 11, 12];
+        let mut connections = Vec::new();
+        assert!(TileCreationComponent::add_connection(
+            &mut connections,
+            (3, Direction::South),
+            &ids
+        )
+        .is_err());
+        assert!(connections.is_empty());
+
+        TileCreationComponent::add_connection(&mut connections, (2, Direction::South), &ids)
+            .unwrap();
+        TileCreationComponent::add_connection(&mut connections, (2, Direction::South), &ids)
+            .unwrap();
+        assert_eq!(connections, vec![(12, Direction::South)]);
+    }
+
+    #[test]
+    fn tiles_follow_the_images() {
+        let mut tiles = vec![TileData::new(0), TileData::new(1), TileData::new(2)];
+        TileCreationComponent::match_tiles_to_images(&mut tiles, 1);
+        assert_eq!(tiles.len(), 1);
+
+        TileCreationComponent::match_tiles_to_images(&mut tiles, 3);
+        assert_eq!(
+            tiles
+                .iter()
+                .map(|tile| tile.image_index)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn removing_a_tile_keeps_connections_to_the_ones_after_it() {
+        let mut tiles = vec![TileData::new(0), TileData::new(1), TileData::new(2)];
+        let ids = tiles.iter().map(|tile| tile.id).collect::<Vec<_>>();
+        tiles[0].north_valid_tiles = vec![(ids[1], Direction::South), (ids[2], Direction::South)];
+        tiles[2].south_valid_tiles = vec![(ids[0], Direction::North), (ids[2], Direction::North)];
+
+        TileCreationComponent::remove_tile(&mut tiles, 1);
+        assert_eq!(tiles.len(), 2);
+        assert_eq!(tiles[1].id, ids[2]);
+        assert_eq!(tiles[1].image_index, 1);
+        assert_eq!(tiles[0].north_valid_tiles, vec![(ids[2], Direction::South)]);
+        assert_eq!(
+            tiles[1].south_valid_tiles,
+            vec![(ids[0], Direction::North), (ids[2], Direction::North)]
+        );
+    }
+}
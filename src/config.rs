@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use crate::wfc::GenerationSettings;
+
+const CONFIG_PATH: &str = "wfc_config.toml";
+
+// Where the tiles and their connections come from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Model {
+    // Tiles and connections are set up by hand
+    #[default]
+    Tiled,
+    // Tiles are the patterns found in an example image
+    Overlapping,
+}
+
+// Session state that is remembered between launches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub tileset_path: Option<String>,
+    pub run_algorithm: bool,
+    // Number of canvases generating side by side from the same tileset
+    pub canvas_count: usize,
+    pub generation: GenerationSettings,
+    pub model: Model,
+    // Multisampling of the window, slow on low-end GPUs
+    pub antialiasing: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            tileset_path: None,
+            run_algorithm: false,
+            canvas_count: 1,
+            generation: GenerationSettings::default(),
+            model: Model::Tiled,
+            antialiasing: true,
+        }
+    }
+}
+
+impl Config {
+    // Reads the config file, falling back to the defaults if it is missing or invalid
+    pub fn load() -> Self {
+        std::fs::read_to_string(CONFIG_PATH)
+            .ok()
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let text = toml::to_string(self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        std::fs::write(CONFIG_PATH, text)
+    }
+}
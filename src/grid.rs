@@ -0,0 +1,160 @@
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    Rng,
+};
+
+use crate::{
+    tile::Direction,
+    wfc::{CollapseBias, GenerationSettings, DIRECTIONS},
+};
+
+// Shape of a board: the directions every cell has and the cell that lies in each of them
+// The solvers only reach other cells through this, so square and hexagonal boards collapse the same way
+pub trait Grid {
+    type Direction: Copy + 'static;
+
+    fn directions(&self) -> &'static [Self::Direction];
+
+    // Neighboring cell in a direction, None past the edge of the board
+    fn neighbor(&self, pos: (usize, usize), direction: Self::Direction) -> Option<(usize, usize)>;
+}
+
+// Rows and columns of squares, with opposite edges connected if wrapping is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SquareGrid {
+    pub width: usize,
+    pub height: usize,
+    pub wrap: bool,
+}
+
+impl SquareGrid {
+    pub fn new(settings: &GenerationSettings) -> Self {
+        Self {
+            width: settings.width,
+            height: settings.height,
+            wrap: settings.wrap,
+        }
+    }
+}
+
+impl Grid for SquareGrid {
+    type Direction = Direction;
+
+    fn directions(&self) -> &'static [Direction] {
+        &DIRECTIONS
+    }
+
+    fn neighbor(&self, pos: (usize, usize), direction: Direction) -> Option<(usize, usize)> {
+        let Self {
+            width,
+            height,
+            wrap,
+        } = *self;
+        match direction {
+            Direction::North if pos.0 > 0 => Some((pos.0 - 1, pos.1)),
+            Direction::South if pos.0 + 1 < height => Some((pos.0 + 1, pos.1)),
+            Direction::West if pos.1 > 0 => Some((pos.0, pos.1 - 1)),
+            Direction::East if pos.1 + 1 < width => Some((pos.0, pos.1 + 1)),
+            _ if !wrap => None,
+            Direction::North => Some((height - 1, pos.1)),
+            Direction::South => Some((0, pos.1)),
+            Direction::West => Some((pos.0, width - 1)),
+            Direction::East => Some((pos.0, 0)),
+        }
+    }
+}
+
+// Open cell the next collapse goes to, out of the open cells in row order
+// Each one comes with the amount of tiles it can still take and its collapse bias, the bias goes first
+// Ties are broken by the noise setting, or picked evenly with the uniform tie break
+pub(crate) fn lowest_entropy(
+    rng: &mut StdRng,
+    settings: &GenerationSettings,
+    open_cells: &[((usize, usize), usize, f32)],
+) -> Option<(usize, usize)> {
+    if settings.collapse_order == CollapseBias::Scanline {
+        return open_cells.first().map(|&(pos, _, _)| pos);
+    }
+
+    let mut lowest = None;
+    let mut ties = Vec::new();
+    for &(pos, candidates, bias) in open_cells {
+        let mut entropy = candidates as f32;
+        if !settings.uniform_tie_break {
+            entropy += rng.gen::<f32>() * settings.noise;
+        }
+        let key = (bias, entropy);
+        if lowest.is_none_or(|(_, lowest_key)| key < lowest_key) {
+            lowest = Some((pos, key));
+            ties.clear();
+        }
+        if lowest.is_some_and(|(_, lowest_key)| key == lowest_key) {
+            ties.push(pos);
+        }
+    }
+    if settings.uniform_tie_break && !ties.is_empty() {
+        return Some(ties[rng.gen_range(0..ties.len())]);
+    }
+    lowest.map(|(pos, _)| pos)
+}
+
+// Picks one of the weights, favoring the higher ones
+// Negative weights count as zero, and if every weight is zero they are all as likely
+pub(crate) fn weighted_pick(rng: &mut StdRng, weights: &[f32]) -> usize {
+    match WeightedIndex::new(weights.iter().map(|weight| weight.max(0.0))) {
+        Ok(distribution) => distribution.sample(rng),
+        Err(_) => rng.gen_range(0..weights.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn square_neighbors_wrap_only_when_asked() {
+        let mut grid = SquareGrid {
+            width: 3,
+            height: 2,
+            wrap: false,
+        };
+        assert_eq!(grid.neighbor((0, 0), Direction::North), None);
+        assert_eq!(grid.neighbor((0, 2), Direction::East), None);
+        assert_eq!(grid.neighbor((0, 1), Direction::South), Some((1, 1)));
+
+        grid.wrap = true;
+        assert_eq!(grid.neighbor((0, 0), Direction::North), Some((1, 0)));
+        assert_eq!(grid.neighbor((0, 2), Direction::East), Some((0, 0)));
+        assert_eq!(grid.neighbor((1, 0), Direction::West), Some((1, 2)));
+    }
+
+    #[test]
+    fn lowest_entropy_goes_by_bias_then_candidates() {
+        let settings = GenerationSettings {
+            noise: 0.0,
+            ..Default::default()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let cells = [((0, 0), 3, 0.0), ((0, 1), 1, 1.0), ((1, 0), 2, 0.0)];
+        assert_eq!(lowest_entropy(&mut rng, &settings, &cells), Some((1, 0)));
+
+        let scanline = GenerationSettings {
+            collapse_order: CollapseBias::Scanline,
+            ..settings
+        };
+        assert_eq!(lowest_entropy(&mut rng, &scanline, &cells), Some((0, 0)));
+        assert_eq!(lowest_entropy(&mut rng, &settings, &[]), None);
+    }
+
+    #[test]
+    fn zero_weights_are_never_picked_over_positive_ones() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..50 {
+            assert_eq!(weighted_pick(&mut rng, &[0.0, -1.0, 2.0]), 2);
+        }
+        assert!(weighted_pick(&mut rng, &[0.0, 0.0]) < 2);
+    }
+}
@@ -0,0 +1,500 @@
+use std::{collections::VecDeque, path::Path};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    grid::{lowest_entropy, weighted_pick, Grid},
+    wfc::{GenerationSettings, SolverError},
+};
+
+// Sides of a pointy topped hexagon
+#[derive(Debug, Clone, Copy, PartialEq, Hash, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum HexDirection {
+    East,
+    West,
+    NorthEast,
+    SouthWest,
+    NorthWest,
+    SouthEast,
+}
+
+pub const HEX_DIRECTIONS: [HexDirection; 6] = [
+    HexDirection::East,
+    HexDirection::West,
+    HexDirection::NorthEast,
+    HexDirection::SouthWest,
+    HexDirection::NorthWest,
+    HexDirection::SouthEast,
+];
+
+impl HexDirection {
+    pub fn opposite(&self) -> Self {
+        match self {
+            HexDirection::East => HexDirection::West,
+            HexDirection::West => HexDirection::East,
+            HexDirection::NorthEast => HexDirection::SouthWest,
+            HexDirection::SouthWest => HexDirection::NorthEast,
+            HexDirection::NorthWest => HexDirection::SouthEast,
+            HexDirection::SouthEast => HexDirection::NorthWest,
+        }
+    }
+}
+
+impl std::fmt::Display for HexDirection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HexDirection::East => write!(f, "East"),
+            HexDirection::West => write!(f, "West"),
+            HexDirection::NorthEast => write!(f, "North east"),
+            HexDirection::SouthWest => write!(f, "South west"),
+            HexDirection::NorthWest => write!(f, "North west"),
+            HexDirection::SouthEast => write!(f, "South east"),
+        }
+    }
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+// Tile of a hexagonal map, with the tiles allowed next to each of its six edges
+// Like on square grids, two tiles only fit together if both list each other
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HexTile {
+    pub image_index: usize,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    pub east_valid_tiles: Vec<usize>,
+    pub west_valid_tiles: Vec<usize>,
+    pub north_east_valid_tiles: Vec<usize>,
+    pub south_west_valid_tiles: Vec<usize>,
+    pub north_west_valid_tiles: Vec<usize>,
+    pub south_east_valid_tiles: Vec<usize>,
+}
+
+impl HexTile {
+    pub fn new(image_index: usize) -> Self {
+        Self {
+            image_index,
+            weight: default_weight(),
+            east_valid_tiles: Vec::new(),
+            west_valid_tiles: Vec::new(),
+            north_east_valid_tiles: Vec::new(),
+            south_west_valid_tiles: Vec::new(),
+            north_west_valid_tiles: Vec::new(),
+            south_east_valid_tiles: Vec::new(),
+        }
+    }
+
+    pub fn valid_tiles(&self, direction: HexDirection) -> &Vec<usize> {
+        match direction {
+            HexDirection::East => &self.east_valid_tiles,
+            HexDirection::West => &self.west_valid_tiles,
+            HexDirection::NorthEast => &self.north_east_valid_tiles,
+            HexDirection::SouthWest => &self.south_west_valid_tiles,
+            HexDirection::NorthWest => &self.north_west_valid_tiles,
+            HexDirection::SouthEast => &self.south_east_valid_tiles,
+        }
+    }
+
+    pub fn valid_tiles_mut(&mut self, direction: HexDirection) -> &mut Vec<usize> {
+        match direction {
+            HexDirection::East => &mut self.east_valid_tiles,
+            HexDirection::West => &mut self.west_valid_tiles,
+            HexDirection::NorthEast => &mut self.north_east_valid_tiles,
+            HexDirection::SouthWest => &mut self.south_west_valid_tiles,
+            HexDirection::NorthWest => &mut self.north_west_valid_tiles,
+            HexDirection::SouthEast => &mut self.south_east_valid_tiles,
+        }
+    }
+}
+
+// Hexagonal tiles with their images, the tile at each position uses the image at the same position
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HexTileset {
+    pub images: Vec<String>,
+    pub tiles: Vec<HexTile>,
+}
+
+impl HexTileset {
+    // Image paths are relative to the file
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let mut tileset = Self::parse(&std::fs::read_to_string(path)?)?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        for image in &mut tileset.images {
+            *image = directory.join(&*image).to_string_lossy().into_owned();
+        }
+        Ok(tileset)
+    }
+
+    // Connections to missing tiles would break the generation
+    pub fn parse(text: &str) -> std::io::Result<Self> {
+        let invalid_data =
+            |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let tileset: Self = serde_json::from_str(text)?;
+        if tileset.images.len() != tileset.tiles.len() {
+            return Err(invalid_data(
+                "Tileset has a different number of images and tiles".to_owned(),
+            ));
+        }
+        for (index, tile) in tileset.tiles.iter().enumerate() {
+            for direction in HEX_DIRECTIONS {
+                if let Some(missing) = tile
+                    .valid_tiles(direction)
+                    .iter()
+                    .find(|&&other| other >= tileset.tiles.len())
+                {
+                    return Err(invalid_data(format!(
+                        "Tile {index} connects to missing tile {missing}"
+                    )));
+                }
+            }
+        }
+        Ok(tileset)
+    }
+}
+
+// Rows of hexagons with every odd row shifted right by half a hexagon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HexGrid {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl HexGrid {
+    pub fn new(settings: &GenerationSettings) -> Self {
+        Self {
+            width: settings.width,
+            height: settings.height,
+        }
+    }
+}
+
+impl Grid for HexGrid {
+    type Direction = HexDirection;
+
+    fn directions(&self) -> &'static [HexDirection] {
+        &HEX_DIRECTIONS
+    }
+
+    fn neighbor(&self, pos: (usize, usize), direction: HexDirection) -> Option<(usize, usize)> {
+        let (row, col) = (pos.0 as isize, pos.1 as isize);
+        // Rows above and below an odd row reach one column further to the east
+        let odd = row % 2;
+        let (row, col) = match direction {
+            HexDirection::East => (row, col + 1),
+            HexDirection::West => (row, col - 1),
+            HexDirection::NorthEast => (row - 1, col + odd),
+            HexDirection::NorthWest => (row - 1, col + odd - 1),
+            HexDirection::SouthEast => (row + 1, col + odd),
+            HexDirection::SouthWest => (row + 1, col + odd - 1),
+        };
+        let row = usize::try_from(row).ok()?;
+        let col = usize::try_from(col).ok()?;
+        (row < self.height && col < self.width).then_some((row, col))
+    }
+}
+
+// Center of a hexagon with the given distance from center to corner, relative to the top left of the map
+// Odd rows are shifted right by half a hexagon
+pub fn hex_center(pos: (usize, usize), size: f32) -> [f32; 2] {
+    let width = 3.0_f32.sqrt() * size;
+    let shift = if pos.0 % 2 == 1 { 0.5 } else { 0.0 };
+    [
+        width * (pos.1 as f32 + 0.5 + shift),
+        size * (1.0 + 1.5 * pos.0 as f32),
+    ]
+}
+
+// Wave function collapse on a hexagonal grid, stored as rows with every odd row shifted right
+// Picks cells and tiles the same way as the square solver, only the neighbors differ
+// Wrapping, regions, a propagation radius and collapse orders that favor parts of the board aren't supported,
+// those settings are ignored
+#[derive(Debug, Clone)]
+pub struct HexSolver {
+    settings: GenerationSettings,
+    rng: StdRng,
+    tiles: Vec<HexTile>,
+    cells: Vec<Vec<Option<usize>>>,
+    // Tiles every cell can still take
+    candidates: Vec<Vec<Vec<usize>>>,
+    contradictions: usize,
+    // Starts at 1, every retry uses the seed after the previous one
+    attempt: u32,
+    seed: u64,
+}
+
+impl HexSolver {
+    pub fn new(tiles: Vec<HexTile>, settings: GenerationSettings) -> Self {
+        let mut solver = Self {
+            settings,
+            rng: StdRng::seed_from_u64(settings.seed),
+            tiles,
+            cells: Vec::new(),
+            candidates: Vec::new(),
+            contradictions: 0,
+            attempt: 1,
+            seed: settings.seed,
+        };
+        solver.reset();
+        solver
+    }
+
+    // Clears the board and restarts the generation from the seed
+    pub fn reset(&mut self) {
+        self.attempt = 1;
+        self.restart_from(self.settings.seed);
+    }
+
+    fn restart_from(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+        self.contradictions = 0;
+        self.cells = vec![vec![None; self.settings.width]; self.settings.height];
+        let all = (0..self.tiles.len()).collect::<Vec<_>>();
+        self.candidates = vec![vec![all; self.settings.width]; self.settings.height];
+    }
+
+    // Neighboring cell in a direction, None past the edge of the map
+    pub fn neighbor(&self, pos: (usize, usize), direction: HexDirection) -> Option<(usize, usize)> {
+        HexGrid::new(&self.settings).neighbor(pos, direction)
+    }
+
+    // Whether a tile may sit next to another one in a direction, both have to list each other
+    fn fits(&self, tile: usize, direction: HexDirection, other: usize) -> bool {
+        self.tiles[tile].valid_tiles(direction).contains(&other)
+            && self.tiles[other]
+                .valid_tiles(direction.opposite())
+                .contains(&tile)
+    }
+
+    pub fn tiles(&self) -> &[HexTile] {
+        &self.tiles
+    }
+
+    // Image index of every cell, None for the open cells
+    pub fn cells(&self) -> &[Vec<Option<usize>>] {
+        &self.cells
+    }
+
+    // Amount of tiles left at a position
+    pub fn entropy(&self, pos: (usize, usize)) -> usize {
+        self.candidates[pos.0][pos.1].len()
+    }
+
+    pub fn remaining_cells(&self) -> usize {
+        self.cells
+            .iter()
+            .flatten()
+            .filter(|cell| cell.is_none())
+            .count()
+    }
+
+    pub fn contradictions(&self) -> usize {
+        self.contradictions
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Collapses the open cell with the lowest entropy and propagates the result
+    // Returns the collapsed position, or None once the board is complete
+    pub fn step(&mut self) -> Result<Option<(usize, usize)>, SolverError> {
+        if self.tiles.is_empty() {
+            return Err(SolverError::EmptyTileset);
+        }
+        let Some(pos) = self.lowest_entropy() else {
+            return Ok(None);
+        };
+
+        let candidates = self.candidates[pos.0][pos.1].clone();
+        let tile = if candidates.is_empty() {
+            // Contradiction, nothing fits here anymore
+            self.contradictions += 1;
+            self.rng.gen_range(0..self.tiles.len())
+        } else {
+            let weights = candidates
+                .iter()
+                .map(|&index| self.tiles[index].weight)
+                .collect::<Vec<_>>();
+            candidates[weighted_pick(&mut self.rng, &weights)]
+        };
+        self.cells[pos.0][pos.1] = Some(tile);
+        self.candidates[pos.0][pos.1] = vec![tile];
+        self.propagate(pos);
+
+        // A contradicted board is thrown away right away while there are attempts left
+        if self.contradictions > 0 && self.attempt < self.settings.max_attempts {
+            self.attempt += 1;
+            self.restart_from(self.seed.wrapping_add(1));
+        }
+        Ok(Some(pos))
+    }
+
+    // Runs the generation to the end in one go
+    pub fn solve_all(&mut self) -> Result<(), SolverError> {
+        while self.step()?.is_some() {}
+        Ok(())
+    }
+
+    // Every open cell counts the same, only the entropy decides the order
+    fn lowest_entropy(&mut self) -> Option<(usize, usize)> {
+        let open_cells = (0..self.settings.height)
+            .flat_map(|row| (0..self.settings.width).map(move |col| (row, col)))
+            .filter(|&(row, col)| self.cells[row][col].is_none())
+            .map(|(row, col)| ((row, col), self.candidates[row][col].len(), 0.0))
+            .collect::<Vec<_>>();
+        lowest_entropy(&mut self.rng, &self.settings, &open_cells)
+    }
+
+    // Removes every tile of the open cells that no tile left next to them fits
+    fn propagate(&mut self, start: (usize, usize)) {
+        let grid = HexGrid::new(&self.settings);
+        let mut queue = VecDeque::from([start]);
+        while let Some(pos) = queue.pop_front() {
+            for &direction in grid.directions() {
+                let Some(neighbor) = grid.neighbor(pos, direction) else {
+                    continue;
+                };
+                if self.cells[neighbor.0][neighbor.1].is_some() {
+                    continue;
+                }
+
+                let own = &self.candidates[pos.0][pos.1];
+                let remaining = self.candidates[neighbor.0][neighbor.1]
+                    .iter()
+                    .copied()
+                    .filter(|&other| own.iter().any(|&tile| self.fits(tile, direction, other)))
+                    .collect::<Vec<_>>();
+                if remaining.len() != self.candidates[neighbor.0][neighbor.1].len() {
+                    self.candidates[neighbor.0][neighbor.1] = remaining;
+                    if !queue.contains(&neighbor) {
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every tile fits next to itself, and next to the following tile to the east, north east and north west
+    fn chained_tiles() -> Vec<HexTile> {
+        let forward = [
+            HexDirection::East,
+            HexDirection::NorthEast,
+            HexDirection::NorthWest,
+        ];
+        (0..3)
+            .map(|index| {
+                let mut tile = HexTile::new(index);
+                for direction in forward {
+                    *tile.valid_tiles_mut(direction) = vec![index, (index + 1) % 3];
+                    *tile.valid_tiles_mut(direction.opposite()) = vec![index, (index + 2) % 3];
+                }
+                tile
+            })
+            .collect()
+    }
+
+    #[test]
+    fn neighbors_lead_back_on_odd_and_even_rows() {
+        let grid = HexGrid {
+            width: 5,
+            height: 4,
+        };
+        for row in 0..grid.height {
+            for col in 0..grid.width {
+                for &direction in grid.directions() {
+                    if let Some(neighbor) = grid.neighbor((row, col), direction) {
+                        assert_eq!(
+                            grid.neighbor(neighbor, direction.opposite()),
+                            Some((row, col)),
+                            "{direction} of {:?}",
+                            (row, col)
+                        );
+                    }
+                }
+            }
+        }
+
+        // Odd rows are shifted right, so their diagonals reach one column further east
+        assert_eq!(grid.neighbor((2, 2), HexDirection::NorthEast), Some((1, 2)));
+        assert_eq!(grid.neighbor((2, 2), HexDirection::NorthWest), Some((1, 1)));
+        assert_eq!(grid.neighbor((1, 2), HexDirection::NorthEast), Some((0, 3)));
+        assert_eq!(grid.neighbor((1, 2), HexDirection::SouthWest), Some((2, 2)));
+        assert_eq!(grid.neighbor((0, 0), HexDirection::NorthWest), None);
+        assert_eq!(grid.neighbor((3, 4), HexDirection::SouthEast), None);
+    }
+
+    #[test]
+    fn seeded_boards_follow_the_rules_in_every_direction() {
+        for seed in 0..5 {
+            let settings = GenerationSettings {
+                width: 6,
+                height: 5,
+                seed,
+                max_attempts: 50,
+                ..Default::default()
+            };
+            let mut solver = HexSolver::new(chained_tiles(), settings);
+            solver.solve_all().unwrap();
+            assert_eq!(solver.contradictions(), 0, "seed {seed}");
+            assert_eq!(solver.remaining_cells(), 0);
+
+            let cells = solver.cells();
+            for row in 0..settings.height {
+                for col in 0..settings.width {
+                    let tile = cells[row][col].unwrap();
+                    for direction in HEX_DIRECTIONS {
+                        let Some(neighbor) = solver.neighbor((row, col), direction) else {
+                            continue;
+                        };
+                        let other = cells[neighbor.0][neighbor.1].unwrap();
+                        assert!(
+                            solver.fits(tile, direction, other),
+                            "Tile {other} {direction} of tile {tile} at {:?} with seed {seed}",
+                            (row, col)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_board() {
+        let settings = GenerationSettings {
+            width: 5,
+            height: 4,
+            seed: 7,
+            ..Default::default()
+        };
+        let mut first = HexSolver::new(chained_tiles(), settings);
+        let mut second = HexSolver::new(chained_tiles(), settings);
+        first.solve_all().unwrap();
+        second.solve_all().unwrap();
+        assert_eq!(first.cells(), second.cells());
+    }
+
+    #[test]
+    fn tilesets_with_missing_tiles_are_rejected() {
+        let mut tiles = chained_tiles();
+        let tileset = HexTileset {
+            images: vec!["a.png".to_owned(), "b.png".to_owned(), "c.png".to_owned()],
+            tiles: tiles.clone(),
+        };
+        let text = serde_json::to_string(&tileset).unwrap();
+        assert_eq!(HexTileset::parse(&text).unwrap(), tileset);
+
+        tiles[1].south_east_valid_tiles.push(3);
+        let text = serde_json::to_string(&HexTileset { tiles, ..tileset }).unwrap();
+        assert!(HexTileset::parse(&text).is_err());
+    }
+}
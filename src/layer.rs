@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+use crate::tile::TileData;
+
+// Tileset generated on top of the board of the layer below it, like trees and rocks on terrain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    pub name: String,
+    // Tileset file the tiles were loaded from
+    pub tileset_path: String,
+    pub tiles: Vec<TileData>,
+    // Tiles of the layer below that every tile of this layer may be placed on, indexed like the tiles
+    // Tiles without any listed fit on anything
+    #[serde(default)]
+    pub allowed_below: Vec<Vec<usize>>,
+}
+
+impl Layer {
+    pub fn new(name: String, tileset_path: String, tiles: Vec<TileData>) -> Self {
+        Self {
+            name,
+            tileset_path,
+            allowed_below: vec![Vec::new(); tiles.len()],
+            tiles,
+        }
+    }
+
+    // Whether a tile of this layer may be placed on a tile of the layer below
+    pub fn fits_on(&self, tile: usize, below: usize) -> bool {
+        self.allowed_below
+            .get(tile)
+            .is_none_or(|allowed| allowed.is_empty() || allowed.contains(&below))
+    }
+
+    // Tiles of this layer every cell is limited to, given the board of the layer below
+    // Cells left open below don't limit the cell above them
+    pub fn restrictions(&self, below: &[Vec<Option<TileData>>]) -> Vec<Vec<Option<Vec<usize>>>> {
+        below
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|tile| {
+                        let below = tile.as_ref()?.image_index;
+                        Some(
+                            (0..self.tiles.len())
+                                .filter(|&index| self.fits_on(index, below))
+                                .collect(),
+                        )
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
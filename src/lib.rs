@@ -0,0 +1,51 @@
+// The solver and the tileset format are usable on their own
+// Everything else belongs to the editor and is only built with the gui feature
+pub mod grid;
+pub mod hex;
+pub mod layer;
+pub mod map;
+pub mod reproduce;
+pub mod socket;
+pub mod template;
+pub mod tile;
+pub mod tileset;
+pub mod wfc;
+
+#[cfg(feature = "gui")]
+pub mod animation;
+#[cfg(feature = "gui")]
+pub mod bundle;
+#[cfg(feature = "gui")]
+pub mod clipboard;
+#[cfg(feature = "gui")]
+pub mod components {
+    pub mod image_canvas_component;
+    pub mod tile_creation_component;
+}
+#[cfg(feature = "gui")]
+pub mod config;
+#[cfg(feature = "gui")]
+pub mod overlapping;
+#[cfg(feature = "gui")]
+pub mod recording;
+#[cfg(feature = "gui")]
+pub mod sample;
+#[cfg(feature = "gui")]
+pub mod spritesheet;
+#[cfg(feature = "gui")]
+pub mod tiled;
+#[cfg(feature = "gui")]
+pub mod validation;
+#[cfg(feature = "gui")]
+pub mod watcher;
+
+pub use grid::{Grid, SquareGrid};
+pub use hex::{HexDirection, HexGrid, HexSolver, HexTile, HexTileset};
+pub use layer::Layer;
+pub use map::{Map, MapLayer, MapTile};
+pub use reproduce::{reproduce, Contradiction, Outcome};
+pub use socket::{Socket, SocketTile};
+pub use template::ConnectionTemplate;
+pub use tile::{Direction, TileConnection, TileData};
+pub use tileset::{Adjacency, TileImage, Tileset};
+pub use wfc::{CollapseBias, GenerationSettings, Solver, SolverError};
@@ -1,15 +1,153 @@
-use components::{
-    image_canvas_component::ImageCanvasComponent, tile_creation_component::TileCreationComponent,
+use std::any::TypeId;
+
+use gamezap::{
+    ecs::{entity::EntityId, scene::Scene},
+    GameZap,
+};
+use wave_function_collapser::{
+    components::{
+        image_canvas_component::ImageCanvasComponent,
+        tile_creation_component::{TileCreationComponent, TilesetHandle},
+    },
+    config::Config,
+    recording::BoardRenderer,
+    reproduce,
+    wfc::GenerationSettings,
+    HexSolver, HexTileset, Outcome, Tileset,
 };
-use gamezap::{ecs::scene::Scene, GameZap};
 
-pub mod components {
-    pub mod image_canvas_component;
-    pub mod tile_creation_component;
+// Reads a setting from an environment variable, ignoring it if it is unset or invalid
+fn env_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok()?.trim().parse().ok()
+}
+
+// Environment variables take precedence over the config file, for quick experiments and scripts
+fn apply_env_overrides(settings: &mut GenerationSettings) {
+    // Sizes are kept in the same range as the canvas settings allow
+    if let Some(width) = env_var::<usize>("WFC_WIDTH") {
+        settings.width = width.clamp(1, 256);
+    }
+    if let Some(height) = env_var::<usize>("WFC_HEIGHT") {
+        settings.height = height.clamp(1, 256);
+    }
+    if let Some(seed) = env_var("WFC_SEED") {
+        settings.seed = seed;
+    }
+    if let Some(step_interval) = env_var("WFC_STEP_MS") {
+        settings.step_interval = step_interval;
+    }
+}
+
+// The overridden settings only last for the session, the config file keeps its own values for them
+fn without_env_overrides(
+    mut settings: GenerationSettings,
+    file: &GenerationSettings,
+) -> GenerationSettings {
+    if env_var::<usize>("WFC_WIDTH").is_some() {
+        settings.width = file.width;
+    }
+    if env_var::<usize>("WFC_HEIGHT").is_some() {
+        settings.height = file.height;
+    }
+    if env_var::<u64>("WFC_SEED").is_some() {
+        settings.seed = file.seed;
+    }
+    if env_var::<u64>("WFC_STEP_MS").is_some() {
+        settings.step_interval = file.step_interval;
+    }
+    settings
+}
+
+// Antialiasing is turned off by the --no-antialiasing flag or the WFC_ANTIALIASING variable
+// Neither is written back to the config file
+fn antialiasing(config: &Config) -> bool {
+    if std::env::args().any(|arg| arg == "--no-antialiasing") {
+        return false;
+    }
+    env_var("WFC_ANTIALIASING").unwrap_or(config.antialiasing)
+}
+
+// Runs a tileset without opening the window and prints the result, to attach to bug reports
+// Usage: --reproduce <tileset file> <seed>, the rest of the settings come from the config
+fn run_reproduction(
+    path: &str,
+    seed: &str,
+    settings: GenerationSettings,
+) -> Result<String, String> {
+    let tileset = Tileset::load(path).map_err(|err| format!("Could not load {path}: {err}"))?;
+    let seed = seed
+        .parse()
+        .map_err(|_| format!("Seed {seed} is not a number"))?;
+    let settings = GenerationSettings { seed, ..settings };
+    match reproduce(&tileset, settings).map_err(|err| err.to_string())? {
+        Outcome::Complete(cells) => Ok(cells
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(usize::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")),
+        Outcome::Contradiction(contradiction) => Err(contradiction.to_string()),
+    }
+}
+
+// Generates a hexagonal map without opening the window and saves it as an image
+// Usage: --hex <hex tileset file> <output image>, the settings come from the config
+fn run_hex(path: &str, output: &str, settings: GenerationSettings) -> Result<(), String> {
+    let tileset = HexTileset::load(path).map_err(|err| format!("Could not load {path}: {err}"))?;
+    let renderer = BoardRenderer::from_paths(tileset.images.iter().map(String::as_str), 1)
+        .map_err(|err| format!("Could not load the images of {path}: {err}"))?;
+    let mut solver = HexSolver::new(tileset.tiles, settings);
+    solver.solve_all().map_err(|err| err.to_string())?;
+    if solver.contradictions() > 0 {
+        eprintln!(
+            "The map has {} contradictions with seed {}",
+            solver.contradictions(),
+            solver.seed()
+        );
+    }
+    renderer
+        .render_hex(solver.cells())
+        .save(output)
+        .map_err(|err| format!("Could not save {output}: {err}"))
 }
 
 #[tokio::main]
 async fn main() {
+    let mut config = Config::load();
+    let mut generation = config.generation;
+    apply_env_overrides(&mut generation);
+
+    let args = std::env::args().collect::<Vec<_>>();
+    if let Some(position) = args.iter().position(|arg| arg == "--reproduce") {
+        let (Some(path), Some(seed)) = (args.get(position + 1), args.get(position + 2)) else {
+            eprintln!("Usage: --reproduce <tileset file> <seed>");
+            std::process::exit(2);
+        };
+        match run_reproduction(path, seed, generation) {
+            Ok(board) => println!("{board}"),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(position) = args.iter().position(|arg| arg == "--hex") {
+        let (Some(path), Some(output)) = (args.get(position + 1), args.get(position + 2)) else {
+            eprintln!("Usage: --hex <hex tileset file> <output image>");
+            std::process::exit(2);
+        };
+        if let Err(err) = run_hex(path, output, generation) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     let event_pump = sdl_context.event_pump().unwrap();
@@ -21,39 +159,78 @@ async fn main() {
         .build()
         .unwrap();
 
-    let mut engine = GameZap::builder()
-        .window_and_renderer(
-            sdl_context,
-            video_subsystem,
-            event_pump,
-            window,
-            wgpu::Color {
-                r: 0.9,
-                g: 0.9,
-                b: 0.9,
-                a: 1.0,
-            },
-        )
-        .antialiasing()
-        .build()
-        .await;
+    let mut builder = GameZap::builder().window_and_renderer(
+        sdl_context,
+        video_subsystem,
+        event_pump,
+        window,
+        wgpu::Color {
+            r: 0.9,
+            g: 0.9,
+            b: 0.9,
+            a: 1.0,
+        },
+    );
+    if antialiasing(&config) {
+        builder = builder.antialiasing();
+    }
+    let mut engine = builder.build().await;
 
     // Setting up the scene
     let mut scene = Scene::default();
+    let concept_manager = scene.get_concept_manager();
 
     // Creating user input component
-    let tile_creation_component = TileCreationComponent::new(scene.get_concept_manager());
+    let tile_creation_component = TileCreationComponent::new(concept_manager.clone(), &config);
 
-    let _tile_creation_entity =
+    let tile_creation_entity =
         scene.create_entity(0, true, vec![Box::new(tile_creation_component)], None);
 
-    // Creating canvas component
-    // This is responsible for running the algorithm
-    let canvas_component = ImageCanvasComponent::default();
+    // Creating canvas components
+    // These are responsible for running the algorithm
+    // Each canvas gets its own seed and window, laid out side by side
+    let tileset = TilesetHandle::new(tile_creation_entity);
+    let mut window_x = 500.0;
+    let mut first_canvas_entity = None;
+    for canvas_index in 0..config.canvas_count.max(1) {
+        let settings = GenerationSettings {
+            seed: generation.seed.wrapping_add(canvas_index as u64),
+            ..generation
+        };
+        let canvas_component =
+            ImageCanvasComponent::new(concept_manager.clone(), settings, tileset, [window_x, 20.0]);
+        window_x += settings.width as f32 * 50.0 + 20.0;
 
-    let _canvas_entity = scene.create_entity(1, false, vec![Box::new(canvas_component)], None);
+        let canvas_entity = scene.create_entity(
+            canvas_index as EntityId + 1,
+            false,
+            vec![Box::new(canvas_component)],
+            None,
+        );
+        first_canvas_entity.get_or_insert(canvas_entity);
+    }
 
     engine.create_scene(scene);
 
     engine.main_loop();
+
+    // Remembers the session for the next launch
+    let concept_manager = concept_manager.lock().unwrap();
+    let canvas_id = (
+        first_canvas_entity.unwrap(),
+        TypeId::of::<ImageCanvasComponent>(),
+        0,
+    );
+    config.tileset_path = tileset.tileset_path(&concept_manager).clone();
+    config.run_algorithm = tileset.run_algorithm(&concept_manager);
+    config.model = tileset.model(&concept_manager);
+    config.generation = without_env_overrides(
+        *concept_manager
+            .get_concept::<GenerationSettings>(canvas_id, "generation_settings".to_string())
+            .unwrap(),
+        &config.generation,
+    );
+    if let Err(err) = config.save() {
+        eprintln!("Could not save the config: {err}");
+    }
 }
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+// Version of the map format written by this build
+pub const MAP_VERSION: u32 = 1;
+
+// What a tile index of a map stands for, so the map can be used without reading the tileset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapTile {
+    pub name: String,
+    pub image: String,
+    // Quarter turns clockwise the image is drawn with
+    #[serde(default)]
+    pub rotation: u8,
+}
+
+// Board of a layer generated on top of the map
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapLayer {
+    pub name: String,
+    pub tileset: Option<String>,
+    pub tiles: Vec<MapTile>,
+    pub cells: Vec<Vec<Option<usize>>>,
+}
+
+// Generated board in a form other engines can load
+// Cells hold the tile index of every cell row by row, open cells are null
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Map {
+    pub version: u32,
+    pub width: usize,
+    pub height: usize,
+    // Tileset file the tile indices refer to, None if it was never saved
+    pub tileset: Option<String>,
+    pub tiles: Vec<MapTile>,
+    pub cells: Vec<Vec<Option<usize>>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layers: Vec<MapLayer>,
+}
+
+impl Map {
+    pub fn new(
+        tileset: Option<String>,
+        tiles: Vec<MapTile>,
+        cells: Vec<Vec<Option<usize>>>,
+    ) -> Self {
+        Self {
+            version: MAP_VERSION,
+            width: cells.first().map_or(0, Vec::len),
+            height: cells.len(),
+            tileset,
+            tiles,
+            cells,
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    // Maps from a newer version are rejected, since their fields can't be understood
+    pub fn parse(text: &str) -> std::io::Result<Self> {
+        let map: Self = serde_json::from_str(text)?;
+        if map.version > MAP_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Map version {} is newer than the supported version {MAP_VERSION}",
+                    map.version
+                ),
+            ));
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Map {
+        Map::new(
+            Some("tiles.json".to_owned()),
+            vec![MapTile {
+                name: "grass".to_owned(),
+                image: "grass.png".to_owned(),
+                rotation: 1,
+            }],
+            vec![vec![Some(0), None], vec![None, Some(0)]],
+        )
+    }
+
+    #[test]
+    fn parses_what_it_writes() {
+        let map = example();
+        assert_eq!((map.width, map.height), (2, 2));
+        let parsed = Map::parse(&serde_json::to_string(&map).unwrap()).unwrap();
+        assert_eq!(parsed, map);
+    }
+
+    #[test]
+    fn missing_rotations_and_layers_default() {
+        let text = r#"{
+            "version": 1, "width": 1, "height": 1, "tileset": null,
+            "tiles": [{ "name": "a", "image": "a.png" }],
+            "cells": [[0]]
+        }"#;
+        let map = Map::parse(text).unwrap();
+        assert_eq!(map.tiles[0].rotation, 0);
+        assert!(map.layers.is_empty());
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let mut map = example();
+        map.version = MAP_VERSION + 1;
+        let err = Map::parse(&serde_json::to_string(&map).unwrap()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_malformed_maps() {
+        assert!(Map::parse("{}").is_err());
+        assert!(Map::parse("not json").is_err());
+    }
+}
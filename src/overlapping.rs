@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use image::{imageops::FilterType, ImageResult, RgbaImage};
+
+use crate::{
+    tile::{Direction, TileData},
+    tileset::{TileImage, Tileset},
+};
+
+// How much larger the pattern images are saved, so the canvas can show a single pixel without blurring
+const PATTERN_SCALE: u32 = 8;
+
+// Offset of the neighboring pattern in each direction
+const OFFSETS: [(Direction, (i32, i32)); 4] = [
+    (Direction::North, (0, -1)),
+    (Direction::South, (0, 1)),
+    (Direction::East, (1, 0)),
+    (Direction::West, (-1, 0)),
+];
+
+// Every distinct NxN block of the sample, with how often it appears
+// The sample is treated as repeating, so blocks wrap around its edges
+fn extract_patterns(sample: &RgbaImage, size: u32) -> Vec<(RgbaImage, u32)> {
+    let mut patterns: Vec<(RgbaImage, u32)> = Vec::new();
+    for y in 0..sample.height() {
+        for x in 0..sample.width() {
+            let pattern = RgbaImage::from_fn(size, size, |dx, dy| {
+                *sample.get_pixel((x + dx) % sample.width(), (y + dy) % sample.height())
+            });
+            match patterns.iter_mut().find(|(other, _)| *other == pattern) {
+                Some((_, count)) => *count += 1,
+                None => patterns.push((pattern, 1)),
+            }
+        }
+    }
+    patterns
+}
+
+// Checks if two patterns agree where they overlap, with `b` shifted by the offset
+fn patterns_agree(a: &RgbaImage, b: &RgbaImage, (dx, dy): (i32, i32)) -> bool {
+    let size = a.width() as i32;
+    for y in dy.max(0)..(size + dy).min(size) {
+        for x in dx.max(0)..(size + dx).min(size) {
+            if a.get_pixel(x as u32, y as u32) != b.get_pixel((x - dx) as u32, (y - dy) as u32) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// Builds a tileset out of the patterns in an example image (the overlapping model)
+// Every pattern becomes a tile, weighted by its frequency, and two patterns may sit
+// next to each other if they agree on the pixels they share
+// The canvas only shows the top left pixel of each pattern
+pub fn overlapping_tileset(sample_path: &str, size: u32) -> ImageResult<Tileset> {
+    let sample = image::open(sample_path)?.to_rgba8();
+    let patterns = extract_patterns(&sample, size);
+    let total = patterns.iter().map(|(_, count)| count).sum::<u32>() as f32;
+
+    // The images have to be on disk to be uploaded as textures
+    let directory = std::env::temp_dir().join("wfc_patterns");
+    std::fs::create_dir_all(&directory)?;
+
+    let mut tileset = Tileset::default();
+    for (index, (pattern, count)) in patterns.iter().enumerate() {
+        let path: PathBuf = directory.join(format!("pattern_{index}.png"));
+        image::imageops::resize(
+            pattern,
+            size * PATTERN_SCALE,
+            size * PATTERN_SCALE,
+            FilterType::Nearest,
+        )
+        .save(&path)?;
+        tileset.images.push(TileImage::new(
+            path.to_string_lossy().into_owned(),
+            format!("Pattern {index}"),
+        ));
+
+        let mut tile = TileData::new(index);
+        tile.weight = *count as f32 / total;
+        tileset.tiles.push(tile);
+    }
+
+    // Every tile has its id once they all exist
+    let ids = tileset.tiles.iter().map(|tile| tile.id).collect::<Vec<_>>();
+    for ((pattern, _), tile) in patterns.iter().zip(&mut tileset.tiles) {
+        for (direction, offset) in OFFSETS {
+            for ((other, _), id) in patterns.iter().zip(&ids) {
+                if patterns_agree(pattern, other, offset) {
+                    tile.valid_tiles_mut(direction)
+                        .push((*id, direction.opposite()));
+                }
+            }
+        }
+    }
+
+    Ok(tileset)
+}
@@ -0,0 +1,178 @@
+use std::path::PathBuf;
+
+use image::{imageops::FilterType, ImageResult, RgbaImage};
+
+use crate::{components::tile_creation_component::ImageData, tile::TileData};
+
+// Draws boards into images, the same way for every export
+#[derive(Debug, Clone)]
+pub struct BoardRenderer {
+    // Tile images at the size of a cell, in the order of the tileset
+    tiles: Vec<RgbaImage>,
+    cell_size: (u32, u32),
+}
+
+impl BoardRenderer {
+    // Loads the tile images up front so drawing a board doesn't touch them again
+    // Patterns of the overlapping model are cut down to their top left pixel, like on the canvas
+    pub fn new(images: &[ImageData], pattern_size: u32) -> ImageResult<Self> {
+        Self::from_paths(images.iter().map(ImageData::path), pattern_size)
+    }
+
+    // Same as new, for boards drawn without the editor
+    pub fn from_paths<'a>(
+        paths: impl IntoIterator<Item = &'a str>,
+        pattern_size: u32,
+    ) -> ImageResult<Self> {
+        let mut tiles = Vec::new();
+        let mut cell_size = (1, 1);
+        for (index, path) in paths.into_iter().enumerate() {
+            let mut tile = image::open(path)?.to_rgba8();
+            let (width, height) = tile.dimensions();
+            let (width, height) = (
+                (width / pattern_size.max(1)).max(1),
+                (height / pattern_size.max(1)).max(1),
+            );
+            tile = image::imageops::crop_imm(&tile, 0, 0, width, height).to_image();
+            if index == 0 {
+                cell_size = (width, height);
+            } else if (width, height) != cell_size {
+                tile =
+                    image::imageops::resize(&tile, cell_size.0, cell_size.1, FilterType::Nearest);
+            }
+            tiles.push(tile);
+        }
+
+        Ok(Self { tiles, cell_size })
+    }
+
+    // Cells that aren't collapsed yet stay transparent
+    pub fn render(&self, board: &[Vec<Option<TileData>>]) -> RgbaImage {
+        let (cell_width, cell_height) = self.cell_size;
+        let rows = board.len() as u32;
+        let columns = board.first().map_or(0, |row| row.len()) as u32;
+        let mut frame = RgbaImage::new(columns * cell_width, rows * cell_height);
+
+        for (row_index, row) in board.iter().enumerate() {
+            for (col_index, tile) in row.iter().enumerate() {
+                let Some(tile) = tile else {
+                    continue;
+                };
+                let Some(image) = self.tiles.get(tile.image_index) else {
+                    continue;
+                };
+                let image = match tile.rotation % 4 {
+                    1 => image::imageops::rotate90(image),
+                    2 => image::imageops::rotate180(image),
+                    3 => image::imageops::rotate270(image),
+                    _ => image.clone(),
+                };
+                image::imageops::overlay(
+                    &mut frame,
+                    &image,
+                    (col_index as u32 * cell_width) as i64,
+                    (row_index as u32 * cell_height) as i64,
+                );
+            }
+        }
+        frame
+    }
+
+    // Hexagonal boards, the images are pointy topped hexagons with transparent corners
+    // Odd rows are shifted right by half a cell and rows overlap by a quarter of one, like hex_center
+    pub fn render_hex(&self, cells: &[Vec<Option<usize>>]) -> RgbaImage {
+        let (cell_width, cell_height) = self.cell_size;
+        let row_step = cell_height * 3 / 4;
+        let rows = cells.len() as u32;
+        let columns = cells.first().map_or(0, |row| row.len()) as u32;
+        let shift = if rows > 1 { cell_width / 2 } else { 0 };
+        let height = rows
+            .checked_sub(1)
+            .map_or(0, |last| last * row_step + cell_height);
+        let mut frame = RgbaImage::new(columns * cell_width + shift, height);
+
+        for (row_index, row) in cells.iter().enumerate() {
+            let offset = if row_index % 2 == 1 {
+                cell_width / 2
+            } else {
+                0
+            };
+            for (col_index, tile) in row.iter().enumerate() {
+                let Some(image) = tile.and_then(|tile| self.tiles.get(tile)) else {
+                    continue;
+                };
+                image::imageops::overlay(
+                    &mut frame,
+                    image,
+                    (col_index as u32 * cell_width + offset) as i64,
+                    (row_index as u32 * row_step) as i64,
+                );
+            }
+        }
+        frame
+    }
+}
+
+// Writes a numbered PNG of the board after every step, to be turned into a GIF or video afterwards
+#[derive(Debug, Clone)]
+pub struct FrameRecorder {
+    directory: PathBuf,
+    renderer: BoardRenderer,
+    next_frame: usize,
+}
+
+impl FrameRecorder {
+    pub fn new(directory: PathBuf, images: &[ImageData], pattern_size: u32) -> ImageResult<Self> {
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self {
+            directory,
+            renderer: BoardRenderer::new(images, pattern_size)?,
+            next_frame: 0,
+        })
+    }
+
+    // Writes the next frame
+    pub fn record(&mut self, board: &[Vec<Option<TileData>>]) -> ImageResult<()> {
+        self.renderer.render(board).save(
+            self.directory
+                .join(format!("frame_{:04}.png", self.next_frame)),
+        )?;
+        self.next_frame += 1;
+        Ok(())
+    }
+
+    pub fn frames_written(&self) -> usize {
+        self.next_frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_without_an_image_are_left_out() {
+        let renderer = BoardRenderer::new(&[], 1).unwrap();
+        let board = vec![vec![Some(TileData::new(3)), None]; 2];
+        let frame = renderer.render(&board);
+        assert_eq!(frame.dimensions(), (2, 2));
+        assert!(frame.pixels().all(|pixel| pixel.0[3] == 0));
+    }
+
+    #[test]
+    fn odd_hex_rows_are_shifted_and_overlap() {
+        let red = image::Rgba([255, 0, 0, 255]);
+        let renderer = BoardRenderer {
+            tiles: vec![RgbaImage::from_pixel(4, 4, red)],
+            cell_size: (4, 4),
+        };
+        let frame = renderer.render_hex(&[vec![Some(0), None], vec![Some(0), None]]);
+        assert_eq!(frame.dimensions(), (10, 7));
+        assert_eq!(*frame.get_pixel(0, 0), red);
+        // The second row starts half a cell to the right and three quarters of a cell down
+        assert_eq!(frame.get_pixel(1, 6).0[3], 0);
+        assert_eq!(*frame.get_pixel(2, 6), red);
+        assert_eq!(*frame.get_pixel(2, 3), red);
+        assert_eq!(frame.get_pixel(6, 6).0[3], 0);
+    }
+}
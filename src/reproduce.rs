@@ -0,0 +1,151 @@
+use crate::{
+    tileset::{connect_corners, Adjacency, Tileset},
+    wfc::{GenerationSettings, Solver, SolverError},
+};
+
+// First cell where a generation ran out of valid tiles (x is the column, y the row)
+// The seed is the one the failing attempt started from, so the same board comes back with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Contradiction {
+    pub x: usize,
+    pub y: usize,
+    pub seed: u64,
+}
+
+impl std::fmt::Display for Contradiction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Contradiction at x {}, y {} with seed {}",
+            self.x, self.y, self.seed
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    // Image index of every cell, row by row
+    Complete(Vec<Vec<usize>>),
+    Contradiction(Contradiction),
+}
+
+// Runs a tileset headless from the seed of the settings, for attaching a failing case to a bug report
+// Stops at the first contradiction, the cell reported is the one collapsed in the step it was found in
+// Contradictions found by the last step, which collapses nothing, are reported at the last collapsed cell
+pub fn reproduce(tileset: &Tileset, settings: GenerationSettings) -> Result<Outcome, SolverError> {
+    let mut tiles = tileset.tiles.clone();
+    if tileset.adjacency == Adjacency::Corner {
+        connect_corners(&mut tiles);
+    }
+
+    let mut solver = Solver::new(tiles, settings);
+    let mut last_collapsed = (0, 0);
+    loop {
+        let seed = solver.seed();
+        let attempt = solver.attempt();
+        let contradictions = solver.contradictions();
+        let collapsed = solver.step()?;
+        // A contradicted attempt is thrown away right away if there are attempts left
+        if solver.contradictions() > contradictions || solver.attempt() != attempt {
+            let (row, col) = collapsed.unwrap_or(last_collapsed);
+            return Ok(Outcome::Contradiction(Contradiction {
+                x: col,
+                y: row,
+                seed,
+            }));
+        }
+        match collapsed {
+            Some(pos) => last_collapsed = pos,
+            None => break,
+        }
+    }
+
+    Ok(Outcome::Complete(
+        solver
+            .cells()
+            .into_iter()
+            .map(|row| row.into_iter().flatten().collect())
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tile::{Direction, TileData};
+
+    fn settings(seed: u64) -> GenerationSettings {
+        GenerationSettings {
+            width: 4,
+            height: 3,
+            seed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn solvable_tilesets_complete() {
+        let mut tileset = Tileset::default();
+        let mut tile = TileData::new(0);
+        for direction in crate::wfc::DIRECTIONS {
+            let id = tile.id;
+            tile.valid_tiles_mut(direction)
+                .push((id, direction.opposite()));
+        }
+        tileset.tiles.push(tile);
+
+        assert_eq!(
+            reproduce(&tileset, settings(3)),
+            Ok(Outcome::Complete(vec![vec![0; 4]; 3]))
+        );
+    }
+
+    #[test]
+    fn contradictions_report_the_cell_and_seed() {
+        // Neither tile fits next to anything
+        let mut tileset = Tileset {
+            tiles: vec![TileData::new(0), TileData::new(1)],
+            ..Default::default()
+        };
+        let id = tileset.tiles[1].id;
+        tileset.tiles[0]
+            .east_valid_tiles
+            .push((id, Direction::North));
+
+        let Ok(Outcome::Contradiction(contradiction)) = reproduce(&tileset, settings(5)) else {
+            panic!("the tileset can't fill a board");
+        };
+        assert_eq!(contradiction.seed, 5);
+        assert!(contradiction.x < 4 && contradiction.y < 3);
+    }
+
+    #[test]
+    fn empty_tilesets_are_an_error() {
+        assert_eq!(
+            reproduce(&Tileset::default(), settings(0)),
+            Err(SolverError::EmptyTileset)
+        );
+    }
+
+    #[test]
+    fn contradictions_on_the_last_cell_are_reported() {
+        // Two cells side by side, the tile doesn't fit next to itself
+        let tileset = Tileset {
+            tiles: vec![TileData::new(0)],
+            ..Default::default()
+        };
+        let settings = GenerationSettings {
+            width: 2,
+            height: 1,
+            max_attempts: 1,
+            ..settings(9)
+        };
+
+        let Ok(Outcome::Contradiction(contradiction)) = reproduce(&tileset, settings) else {
+            panic!("the second cell can't be filled");
+        };
+        assert_eq!(contradiction.seed, 9);
+        assert_eq!(contradiction.y, 0);
+        assert!(contradiction.x < 2);
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use image::{imageops::FilterType, ImageResult, RgbaImage};
+
+use crate::{
+    components::tile_creation_component::ImageData,
+    tile::{Direction, TileData},
+    wfc::DIRECTIONS,
+};
+
+// Sum of the per channel differences between two equally sized images
+fn image_distance(a: &RgbaImage, b: &RgbaImage) -> u64 {
+    a.as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(a, b)| a.abs_diff(*b) as u64)
+        .sum()
+}
+
+// Derives tile weights from how often each tile appears in an example layout
+// The sample is cut into cells the size of the first tile, and every cell counts
+// towards the tile image it looks the most like
+pub fn infer_weights_from_sample(sample_path: &str, images: &[ImageData]) -> ImageResult<Vec<f32>> {
+    if images.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let sample = image::open(sample_path)?.to_rgba8();
+    let (cell_width, cell_height) = image::image_dimensions(images[0].path())?;
+
+    // Every tile is compared at the cell size, even if its own image differs
+    let tile_images = images
+        .iter()
+        .map(|image| {
+            let tile = image::open(image.path())?.to_rgba8();
+            Ok(if tile.dimensions() == (cell_width, cell_height) {
+                tile
+            } else {
+                image::imageops::resize(&tile, cell_width, cell_height, FilterType::Nearest)
+            })
+        })
+        .collect::<ImageResult<Vec<_>>>()?;
+
+    let mut counts = vec![0; images.len()];
+    for y in (0..sample.height() / cell_height).map(|row| row * cell_height) {
+        for x in (0..sample.width() / cell_width).map(|col| col * cell_width) {
+            let cell = image::imageops::crop_imm(&sample, x, y, cell_width, cell_height).to_image();
+            let closest = tile_images
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, tile)| image_distance(&cell, tile))
+                .map(|(index, _)| index)
+                .unwrap();
+            counts[closest] += 1;
+        }
+    }
+
+    let total = counts.iter().sum::<u32>().max(1) as f32;
+    Ok(counts
+        .into_iter()
+        .map(|count| count as f32 / total)
+        .collect())
+}
+
+// Learns connections and weights from a map drawn with characters, like `#` for walls and `.` for floors
+// Characters that aren't mapped are left out
+pub fn learn_from_ascii(text: &str, char_to_tile: &HashMap<char, usize>, tiles: &mut [TileData]) {
+    let grid = text
+        .lines()
+        .map(|line| {
+            line.chars()
+                .map(|character| {
+                    char_to_tile
+                        .get(&character)
+                        .copied()
+                        .filter(|&index| index < tiles.len())
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    learn_from_board(&grid, tiles);
+}
+
+// Learns connections and weights from a board of tile indices, like a generated canvas
+// Every pair of tiles next to each other becomes a connection, in both directions
+// Empty cells are left out, and existing connections are kept
+// Every index has to be one of the tiles
+pub fn learn_from_board(grid: &[Vec<Option<usize>>], tiles: &mut [TileData]) {
+    let mut counts = vec![0; tiles.len()];
+    for (row_index, row) in grid.iter().enumerate() {
+        for (col_index, &tile) in row.iter().enumerate() {
+            let Some(tile) = tile else {
+                continue;
+            };
+            counts[tile] += 1;
+
+            for direction in DIRECTIONS {
+                let neighbor = match direction {
+                    Direction::North => row_index
+                        .checked_sub(1)
+                        .and_then(|row| grid[row].get(col_index)),
+                    Direction::South => grid.get(row_index + 1).and_then(|row| row.get(col_index)),
+                    Direction::East => row.get(col_index + 1),
+                    Direction::West => col_index.checked_sub(1).and_then(|col| row.get(col)),
+                };
+                let Some(&Some(other)) = neighbor else {
+                    continue;
+                };
+                let connection = (
+                    tiles[other].id,
+                    tiles[other].side_facing(direction.opposite()),
+                );
+                let valid_tiles = tiles[tile].valid_tiles_mut(direction);
+                if !valid_tiles.contains(&connection) {
+                    valid_tiles.push(connection);
+                }
+            }
+        }
+    }
+
+    // Only tiles that show up in the map get a new weight
+    let total = counts.iter().sum::<u32>().max(1) as f32;
+    for (tile, count) in tiles.iter_mut().zip(counts) {
+        if count > 0 {
+            tile.weight = count as f32 / total;
+        }
+    }
+}
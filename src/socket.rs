@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    tile::{Direction, TileConnection, TileData, TileId},
+    wfc::DIRECTIONS,
+};
+
+// Label of one side of a tile, read clockwise around the tile
+// Symmetric sockets fit the same socket, asymmetric ones only fit their flipped counterpart,
+// like a path that runs off center and has to line up with the tile next to it
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Socket {
+    pub id: String,
+    #[serde(default = "default_symmetric")]
+    pub symmetric: bool,
+    #[serde(default)]
+    pub flipped: bool,
+}
+
+fn default_symmetric() -> bool {
+    true
+}
+
+impl Default for Socket {
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+impl Socket {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            symmetric: true,
+            flipped: false,
+        }
+    }
+
+    pub fn asymmetric(id: impl Into<String>, flipped: bool) -> Self {
+        Self {
+            id: id.into(),
+            symmetric: false,
+            flipped,
+        }
+    }
+
+    // Socket of the same side after the image is flipped, which reverses the way it is read
+    pub fn mirrored(&self) -> Self {
+        Self {
+            flipped: self.flipped ^ !self.symmetric,
+            ..self.clone()
+        }
+    }
+
+    // Whether this socket can touch another one
+    pub fn fits(&self, other: &Socket) -> bool {
+        self.id == other.id
+            && self.symmetric == other.symmetric
+            && (self.symmetric || self.flipped != other.flipped)
+    }
+}
+
+// Tile authored with a socket on every side instead of lists of connections
+// Two tiles connect when the sockets of their touching sides fit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SocketTile {
+    // Sockets of the north, south, east and west sides of the unrotated image
+    pub sockets: [Socket; 4],
+    // Quarter turns clockwise the image is drawn with, the sockets turn along with it
+    #[serde(default)]
+    pub rotation: u8,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+impl SocketTile {
+    pub fn new(sockets: [Socket; 4]) -> Self {
+        Self {
+            sockets,
+            rotation: 0,
+            weight: default_weight(),
+        }
+    }
+
+    // Same tile turned clockwise by a number of quarter turns
+    pub fn rotated(&self, quarter_turns: u8) -> Self {
+        Self {
+            rotation: (self.rotation + quarter_turns) % 4,
+            ..self.clone()
+        }
+    }
+
+    pub fn from_tile(tile: &TileData) -> Self {
+        Self {
+            sockets: tile.sockets.clone(),
+            rotation: tile.rotation,
+            weight: tile.weight,
+        }
+    }
+
+    pub fn socket(&self, side: Direction) -> &Socket {
+        &self.sockets[side as usize]
+    }
+
+    // Socket that ends up facing a direction once the tile is rotated
+    pub fn socket_facing(&self, direction: Direction) -> &Socket {
+        self.socket(direction.rotated(4 - self.rotation % 4))
+    }
+}
+
+// Every tile whose socket fits the one of a tile facing a direction, by the id of the tile at the same position
+// The side listed is the one of the other unrotated image that faces back
+fn socket_connections(
+    socket_tile: &SocketTile,
+    direction: Direction,
+    socket_tiles: &[SocketTile],
+    ids: &[TileId],
+) -> Vec<TileConnection> {
+    let own = socket_tile.socket_facing(direction);
+    socket_tiles
+        .iter()
+        .zip(ids)
+        .filter(|(other, _)| own.fits(other.socket_facing(direction.opposite())))
+        .map(|(other, id)| (*id, direction.opposite().rotated(4 - other.rotation % 4)))
+        .collect()
+}
+
+// Turns socket tiles into tiles with connections the solver understands
+// Tiles keep their position as image index, so they line up with the images they were authored for
+pub fn tiles_from_sockets(socket_tiles: &[SocketTile]) -> Vec<TileData> {
+    let mut tiles = socket_tiles
+        .iter()
+        .enumerate()
+        .map(|(index, socket_tile)| {
+            let mut tile = TileData::new(index);
+            tile.sockets = socket_tile.sockets.clone();
+            tile.rotation = socket_tile.rotation % 4;
+            tile.weight = socket_tile.weight;
+            tile
+        })
+        .collect::<Vec<_>>();
+    connect_sockets(&mut tiles);
+    tiles
+}
+
+// Replaces the connections of every tile with the ones its sockets allow
+pub fn connect_sockets(tiles: &mut [TileData]) {
+    let socket_tiles = tiles.iter().map(SocketTile::from_tile).collect::<Vec<_>>();
+    let ids = tiles.iter().map(|tile| tile.id).collect::<Vec<_>>();
+    for (tile, socket_tile) in tiles.iter_mut().zip(&socket_tiles) {
+        for direction in DIRECTIONS {
+            *tile.valid_tiles_mut(direction) =
+                socket_connections(socket_tile, direction, &socket_tiles, &ids);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Direction::{East, North, South, West};
+
+    fn tile(sockets: [Socket; 4]) -> TileData {
+        let mut tile = TileData::new(0);
+        tile.sockets = sockets;
+        tile
+    }
+
+    #[test]
+    fn asymmetric_sockets_only_fit_their_flipped_counterpart() {
+        let path = Socket::asymmetric("path", false);
+        assert!(path.fits(&Socket::asymmetric("path", true)));
+        assert!(!path.fits(&path));
+        assert!(!path.fits(&Socket::new("path")));
+        assert!(Socket::new("grass").fits(&Socket::new("grass")));
+        assert!(!Socket::new("grass").fits(&Socket::new("road")));
+    }
+
+    #[test]
+    fn connects_tiles_whose_sockets_fit() {
+        let grass = || Socket::new("grass");
+        let mut tiles = vec![
+            tile([grass(), grass(), grass(), grass()]),
+            // A road leaving to the north, which nothing continues
+            tile([Socket::new("road"), grass(), grass(), grass()]),
+            // An off center path running east to west
+            tile([
+                grass(),
+                grass(),
+                Socket::asymmetric("path", false),
+                Socket::asymmetric("path", true),
+            ]),
+        ];
+        connect_sockets(&mut tiles);
+        let [a, b, c] = [tiles[0].id, tiles[1].id, tiles[2].id];
+
+        assert_eq!(
+            tiles[0].north_valid_tiles,
+            vec![(a, South), (b, South), (c, South)]
+        );
+        assert_eq!(tiles[0].east_valid_tiles, vec![(a, West), (b, West)]);
+        assert!(tiles[1].north_valid_tiles.is_empty());
+        assert_eq!(tiles[1].south_valid_tiles, vec![(a, North), (c, North)]);
+        assert_eq!(tiles[2].east_valid_tiles, vec![(c, West)]);
+        assert_eq!(tiles[2].west_valid_tiles, vec![(c, East)]);
+    }
+
+    #[test]
+    fn rotated_tiles_connect_with_the_side_facing_back() {
+        // The road of the second tile faces east once it is turned a quarter
+        let road_north = SocketTile::new([
+            Socket::new("road"),
+            Socket::new("grass"),
+            Socket::new("grass"),
+            Socket::new("grass"),
+        ]);
+        let road_west = SocketTile::new([
+            Socket::new("grass"),
+            Socket::new("grass"),
+            Socket::new("grass"),
+            Socket::new("road"),
+        ]);
+        let tiles = tiles_from_sockets(&[road_north.rotated(1), road_west]);
+        assert_eq!(tiles[0].east_valid_tiles, vec![(tiles[1].id, West)]);
+        assert!(tiles[0].allows(East, &tiles[1]));
+        assert!(tiles[1].allows(West, &tiles[0]));
+    }
+}
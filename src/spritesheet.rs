@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use image::ImageResult;
+
+// Layout of the tiles in a sheet, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SheetGrid {
+    pub tile_width: u32,
+    pub tile_height: u32,
+    // Border around the whole sheet
+    pub margin: u32,
+    // Gap between neighboring tiles
+    pub spacing: u32,
+}
+
+impl Default for SheetGrid {
+    fn default() -> Self {
+        Self {
+            tile_width: 16,
+            tile_height: 16,
+            margin: 0,
+            spacing: 0,
+        }
+    }
+}
+
+// Cuts a sheet into its tiles, row by row, and writes every tile to its own image
+// Fully transparent cells are skipped, since sheets often leave part of their last row empty
+pub fn split_tile_sheet(path: &str, grid: SheetGrid) -> ImageResult<Vec<String>> {
+    let sheet = image::open(path)?.to_rgba8();
+    let stem = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let count = |size: u32, tile_size: u32| {
+        (size.saturating_sub(grid.margin * 2) + grid.spacing) / (tile_size + grid.spacing).max(1)
+    };
+    let columns = count(sheet.width(), grid.tile_width);
+    let rows = count(sheet.height(), grid.tile_height);
+
+    let directory = std::env::temp_dir().join("wfc_tiles");
+    std::fs::create_dir_all(&directory)?;
+
+    let mut tiles = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let x = grid.margin + column * (grid.tile_width + grid.spacing);
+            let y = grid.margin + row * (grid.tile_height + grid.spacing);
+            let tile = image::imageops::crop_imm(&sheet, x, y, grid.tile_width, grid.tile_height)
+                .to_image();
+            if tile.pixels().all(|pixel| pixel[3] == 0) {
+                continue;
+            }
+
+            let tile_path = directory.join(format!("{stem}_{row}_{column}.png"));
+            tile.save(&tile_path)?;
+            tiles.push(tile_path.to_string_lossy().into_owned());
+        }
+    }
+    Ok(tiles)
+}
@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    tile::{tile_positions, Direction, TileData},
+    wfc::DIRECTIONS,
+};
+
+// Connections of a tile with the other tiles given as offsets from its position, so they can be applied to any tile
+// The side of every connection is kept as it is, like in the connection lists of a tile
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionTemplate {
+    pub north: Vec<(isize, Direction)>,
+    pub south: Vec<(isize, Direction)>,
+    pub east: Vec<(isize, Direction)>,
+    pub west: Vec<(isize, Direction)>,
+}
+
+impl ConnectionTemplate {
+    // Connections of the tile at a position of a tileset
+    pub fn from_tile(tiles: &[TileData], index: usize) -> Self {
+        let positions = tile_positions(tiles);
+        let mut template = Self::default();
+        for direction in DIRECTIONS {
+            *template.offsets_mut(direction) = tiles[index]
+                .valid_tiles(direction)
+                .iter()
+                .filter_map(|(id, side)| {
+                    Some((*positions.get(id)? as isize - index as isize, *side))
+                })
+                .collect();
+        }
+        template
+    }
+
+    fn offsets(&self, direction: Direction) -> &Vec<(isize, Direction)> {
+        match direction {
+            Direction::North => &self.north,
+            Direction::South => &self.south,
+            Direction::East => &self.east,
+            Direction::West => &self.west,
+        }
+    }
+
+    fn offsets_mut(&mut self, direction: Direction) -> &mut Vec<(isize, Direction)> {
+        match direction {
+            Direction::North => &mut self.north,
+            Direction::South => &mut self.south,
+            Direction::East => &mut self.east,
+            Direction::West => &mut self.west,
+        }
+    }
+
+    // Replaces the connections of the tile at a position with the template, relative to that position
+    // Offsets that land outside of the tileset are left out
+    pub fn apply(&self, tiles: &mut [TileData], index: usize) {
+        let ids = tiles.iter().map(|tile| tile.id).collect::<Vec<_>>();
+        for direction in DIRECTIONS {
+            *tiles[index].valid_tiles_mut(direction) = self
+                .offsets(direction)
+                .iter()
+                .filter_map(|&(offset, side)| {
+                    let other = usize::try_from(index as isize + offset).ok()?;
+                    ids.get(other).map(|id| (*id, side))
+                })
+                .collect();
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
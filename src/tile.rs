@@ -0,0 +1,418 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{socket::Socket, wfc::DIRECTIONS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Hash, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Direction {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl From<usize> for Direction {
+    fn from(value: usize) -> Self {
+        match value {
+            0 => Direction::North,
+            1 => Direction::South,
+            2 => Direction::East,
+            3 => Direction::West,
+            _ => Direction::North,
+        }
+    }
+}
+
+impl Direction {
+    pub fn opposite(&self) -> Self {
+        match self {
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+        }
+    }
+
+    // Direction after flipping left to right (horizontally) or top to bottom
+    pub fn mirrored(&self, horizontal: bool) -> Self {
+        match (self, horizontal) {
+            (Direction::East, true) | (Direction::West, true) => self.opposite(),
+            (Direction::North, false) | (Direction::South, false) => self.opposite(),
+            _ => *self,
+        }
+    }
+
+    // Direction after turning clockwise by a number of quarter turns
+    pub fn rotated(&self, quarter_turns: u8) -> Self {
+        let clockwise = [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ];
+        let index = clockwise
+            .iter()
+            .position(|direction| direction == self)
+            .unwrap();
+        clockwise[(index + quarter_turns as usize) % 4]
+    }
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::North => write!(f, "North"),
+            Direction::South => write!(f, "South"),
+            Direction::East => write!(f, "East"),
+            Direction::West => write!(f, "West"),
+        }
+    }
+}
+
+// Identifier of a tile that stays the same when tiles are added, removed or reordered
+// Connections refer to other tiles by it, the solver turns them into positions when it starts
+pub type TileId = u64;
+
+// Another tile by its id, and the side of its unrotated image that has to face back towards this tile
+// For tiles without rotation that side is always opposite of the direction the connection is listed in,
+// a tile rotated by a quarter turn clockwise instead faces back with its original south side when placed to the east
+pub type TileConnection = (TileId, Direction);
+
+// Random, so tiles made in different sessions don't share an id
+pub fn new_tile_id() -> TileId {
+    rand::random()
+}
+
+// Position of every tile in a list by its id
+pub fn tile_positions(tiles: &[TileData]) -> HashMap<TileId, usize> {
+    tiles
+        .iter()
+        .enumerate()
+        .map(|(index, tile)| (tile.id, index))
+        .collect()
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+// JSON only allows text keys, so the contextual weights are stored as a sorted list of pairs
+mod contextual_weight_list {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{Direction, TileId};
+
+    pub fn serialize<S: Serializer>(
+        weights: &HashMap<(TileId, Direction), f32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut list = weights.iter().collect::<Vec<_>>();
+        list.sort_by_key(|(context, _)| **context);
+        serializer.collect_seq(list)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<(TileId, Direction), f32>, D::Error> {
+        let list = Vec::<((TileId, Direction), f32)>::deserialize(deserializer)?;
+        Ok(list.into_iter().collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TileData {
+    pub image_index: usize,
+    #[serde(default = "new_tile_id")]
+    pub id: TileId,
+    // Relative likelihood of the tile being picked when several fit
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    // Weight used instead when a specific tile sits in a direction next to this one
+    // If several of them apply the average is used
+    #[serde(default, with = "contextual_weight_list")]
+    pub contextual_weight: HashMap<(TileId, Direction), f32>,
+    // Colors of the north west, north east, south east and south west corners
+    // Only used by tilesets with corner adjacency
+    #[serde(default)]
+    pub corners: [u32; 4],
+    // Regions of a canvas the tile may be placed in
+    #[serde(default)]
+    pub categories: HashSet<String>,
+    // Quarter turns clockwise the image is drawn with
+    #[serde(default)]
+    pub rotation: u8,
+    // Keeps copies of the tile apart, whatever its connections say
+    #[serde(default)]
+    pub forbid_self_adjacency: bool,
+    // Copies of the tile a board needs at least, placed before the rest of the board fills up
+    #[serde(default)]
+    pub min_count: u32,
+    // Copies of the tile a board may have at most, None doesn't limit them
+    #[serde(default)]
+    pub max_count: Option<u32>,
+    // Sockets of the north, south, east and west sides of the unrotated image
+    // Only used to work out the connections, see the socket module
+    #[serde(default)]
+    pub sockets: [Socket; 4],
+    pub north_valid_tiles: Vec<TileConnection>,
+    pub south_valid_tiles: Vec<TileConnection>,
+    pub east_valid_tiles: Vec<TileConnection>,
+    pub west_valid_tiles: Vec<TileConnection>,
+}
+
+impl TileData {
+    pub fn new(image_index: usize) -> Self {
+        Self {
+            image_index,
+            id: new_tile_id(),
+            weight: default_weight(),
+            contextual_weight: HashMap::new(),
+            corners: [0; 4],
+            categories: HashSet::new(),
+            rotation: 0,
+            forbid_self_adjacency: false,
+            min_count: 0,
+            max_count: None,
+            sockets: Default::default(),
+            north_valid_tiles: Vec::new(),
+            south_valid_tiles: Vec::new(),
+            east_valid_tiles: Vec::new(),
+            west_valid_tiles: Vec::new(),
+        }
+    }
+
+    // Tiles allowed next to this one in a direction
+    pub fn valid_tiles(&self, direction: Direction) -> &Vec<TileConnection> {
+        match direction {
+            Direction::North => &self.north_valid_tiles,
+            Direction::South => &self.south_valid_tiles,
+            Direction::East => &self.east_valid_tiles,
+            Direction::West => &self.west_valid_tiles,
+        }
+    }
+
+    pub fn valid_tiles_mut(&mut self, direction: Direction) -> &mut Vec<TileConnection> {
+        match direction {
+            Direction::North => &mut self.north_valid_tiles,
+            Direction::South => &mut self.south_valid_tiles,
+            Direction::East => &mut self.east_valid_tiles,
+            Direction::West => &mut self.west_valid_tiles,
+        }
+    }
+
+    // Direction one of the sides of the unrotated image ends up facing
+    pub fn facing(&self, side: Direction) -> Direction {
+        side.rotated(self.rotation)
+    }
+
+    // Side of the unrotated image that ends up facing a direction
+    pub fn side_facing(&self, direction: Direction) -> Direction {
+        direction.rotated(4 - self.rotation % 4)
+    }
+
+    // Whether another tile is allowed next to this one in a direction
+    // The connection only counts if the listed side of the other tile actually faces back
+    pub fn allows(&self, direction: Direction, other: &TileData) -> bool {
+        if self.forbid_self_adjacency && other.id == self.id {
+            return false;
+        }
+        self.valid_tiles(direction)
+            .iter()
+            .any(|(id, side)| *id == other.id && other.facing(*side) == direction.opposite())
+    }
+
+    // Flips the connections left to right (horizontally) or top to bottom, along with the corners and rotation
+    // Like with rotating, the sides the other tiles connect with are flipped too
+    pub fn mirror(&mut self, horizontal: bool) {
+        let mut mirrored = DIRECTIONS.map(|direction| {
+            let connections = self
+                .valid_tiles(direction)
+                .iter()
+                .map(|(other, side)| (*other, side.mirrored(horizontal)))
+                .collect::<Vec<_>>();
+            (direction.mirrored(horizontal), connections)
+        });
+        for (direction, connections) in &mut mirrored {
+            *self.valid_tiles_mut(*direction) = std::mem::take(connections);
+        }
+
+        let [north_west, north_east, south_east, south_west] = self.corners;
+        self.corners = if horizontal {
+            [north_east, north_west, south_west, south_east]
+        } else {
+            [south_west, south_east, north_east, north_west]
+        };
+        // A mirrored quarter turn clockwise is a quarter turn counterclockwise of the mirrored image
+        self.rotation = (4 - self.rotation % 4) % 4;
+        // The image itself is flipped, so its sockets trade sides in its own frame
+        let sockets = self.sockets.clone();
+        for direction in DIRECTIONS {
+            self.sockets[direction as usize] =
+                sockets[direction.mirrored(horizontal) as usize].mirrored();
+        }
+    }
+
+    // Turns the connections clockwise by a number of quarter turns, along with the corners
+    // The sides the other tiles connect with turn as well, so they still face back
+    pub fn rotate_connections(&mut self, quarter_turns: u8) {
+        let mut rotated = DIRECTIONS.map(|direction| {
+            let connections = self
+                .valid_tiles(direction)
+                .iter()
+                .map(|(other, side)| (*other, side.rotated(quarter_turns)))
+                .collect::<Vec<_>>();
+            (direction.rotated(quarter_turns), connections)
+        });
+        for (direction, connections) in &mut rotated {
+            *self.valid_tiles_mut(*direction) = std::mem::take(connections);
+        }
+        self.corners.rotate_right(quarter_turns as usize % 4);
+    }
+
+    // Drops every reference to a removed tile
+    // The other tiles keep their ids, so their connections stay as they are
+    pub fn forget_tile(&mut self, removed: TileId) {
+        for direction in DIRECTIONS {
+            self.valid_tiles_mut(direction)
+                .retain(|(other, _)| *other != removed);
+        }
+        self.contextual_weight
+            .retain(|(other, _), _| *other != removed);
+    }
+
+    pub fn total_connections(&self) -> usize {
+        self.north_valid_tiles.len()
+            + self.south_valid_tiles.len()
+            + self.east_valid_tiles.len()
+            + self.west_valid_tiles.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Direction::{East, North, South, West};
+
+    #[test]
+    fn opposite_directions() {
+        assert_eq!(North.opposite(), South);
+        assert_eq!(South.opposite(), North);
+        assert_eq!(East.opposite(), West);
+        assert_eq!(West.opposite(), East);
+    }
+
+    #[test]
+    fn rotating_turns_clockwise() {
+        assert_eq!(North.rotated(1), East);
+        assert_eq!(East.rotated(1), South);
+        assert_eq!(West.rotated(2), East);
+        assert_eq!(South.rotated(3), East);
+        for direction in DIRECTIONS {
+            assert_eq!(direction.rotated(0), direction);
+            assert_eq!(direction.rotated(4), direction);
+            assert_eq!(direction.rotated(2), direction.opposite());
+        }
+    }
+
+    #[test]
+    fn mirroring_only_flips_its_own_axis() {
+        assert_eq!(East.mirrored(true), West);
+        assert_eq!(West.mirrored(true), East);
+        assert_eq!(North.mirrored(true), North);
+        assert_eq!(North.mirrored(false), South);
+        assert_eq!(South.mirrored(false), North);
+        assert_eq!(East.mirrored(false), East);
+    }
+
+    #[test]
+    fn allows_needs_the_listed_side_to_face_back() {
+        let mut tile = TileData::new(0);
+        let mut other = TileData::new(1);
+        tile.east_valid_tiles.push((other.id, West));
+        assert!(tile.allows(East, &other));
+        assert!(!tile.allows(West, &other));
+
+        // Turned a quarter, the west side of the other tile faces north instead
+        other.rotation = 1;
+        assert!(!tile.allows(East, &other));
+        tile.east_valid_tiles.push((other.id, South));
+        assert!(tile.allows(East, &other));
+    }
+
+    #[test]
+    fn allows_goes_by_id_not_position() {
+        let mut tile = TileData::new(0);
+        let other = TileData::new(1);
+        tile.east_valid_tiles.push((other.id, West));
+
+        // Moved to another position the tile is still the same one
+        let mut moved = other.clone();
+        moved.image_index = 5;
+        assert!(tile.allows(East, &moved));
+        assert!(!tile.allows(East, &TileData::new(1)));
+    }
+
+    #[test]
+    fn allows_respects_self_adjacency() {
+        let mut tile = TileData::new(0);
+        tile.north_valid_tiles.push((tile.id, South));
+        assert!(tile.allows(North, &tile.clone()));
+        tile.forbid_self_adjacency = true;
+        assert!(!tile.allows(North, &tile.clone()));
+    }
+
+    #[test]
+    fn mirroring_moves_connections_corners_and_rotation() {
+        let mut tile = TileData::new(0);
+        tile.east_valid_tiles.push((1, West));
+        tile.north_valid_tiles.push((2, South));
+        tile.corners = [1, 2, 3, 4];
+        tile.rotation = 1;
+        tile.mirror(true);
+
+        assert_eq!(tile.west_valid_tiles, vec![(1, East)]);
+        assert!(tile.east_valid_tiles.is_empty());
+        assert_eq!(tile.north_valid_tiles, vec![(2, South)]);
+        assert_eq!(tile.corners, [2, 1, 4, 3]);
+        assert_eq!(tile.rotation, 3);
+
+        tile.mirror(false);
+        assert_eq!(tile.south_valid_tiles, vec![(2, North)]);
+        assert_eq!(tile.corners, [3, 4, 1, 2]);
+    }
+
+    #[test]
+    fn rotating_connections_turns_sides_and_corners() {
+        let mut tile = TileData::new(0);
+        tile.north_valid_tiles.push((1, South));
+        tile.corners = [1, 2, 3, 4];
+        tile.rotate_connections(1);
+
+        assert!(tile.north_valid_tiles.is_empty());
+        assert_eq!(tile.east_valid_tiles, vec![(1, West)]);
+        assert_eq!(tile.corners, [4, 1, 2, 3]);
+
+        let unchanged = tile.clone();
+        tile.rotate_connections(4);
+        assert_eq!(tile, unchanged);
+    }
+
+    #[test]
+    fn forgetting_a_tile_keeps_the_other_references() {
+        let mut tile = TileData::new(3);
+        tile.north_valid_tiles = vec![(10, South), (11, South), (12, South), (13, South)];
+        tile.contextual_weight.insert((11, East), 2.0);
+        tile.contextual_weight.insert((12, East), 3.0);
+        tile.forget_tile(11);
+
+        assert_eq!(tile.image_index, 3);
+        assert_eq!(
+            tile.north_valid_tiles,
+            vec![(10, South), (12, South), (13, South)]
+        );
+        assert_eq!(tile.contextual_weight, HashMap::from([((12, East), 3.0)]));
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+use quick_xml::{events::BytesStart, events::Event, Reader};
+
+use crate::{
+    tile::{Direction, TileData},
+    tileset::{TileImage, Tileset},
+};
+
+// Positions in a Wang id that make up each side of a tile
+// Tiled lists the colors clockwise starting from the top edge:
+// top, top right, right, bottom right, bottom, bottom left, left, top left
+// The edge comes first, followed by the corners in the order they meet the opposite side
+const WANG_SIDES: [(Direction, [usize; 3]); 4] = [
+    (Direction::North, [0, 7, 1]),
+    (Direction::South, [4, 5, 3]),
+    (Direction::East, [2, 1, 3]),
+    (Direction::West, [6, 7, 5]),
+];
+
+fn invalid_data(err: impl ToString) -> Error {
+    Error::new(ErrorKind::InvalidData, err.to_string())
+}
+
+fn attribute(element: &BytesStart, name: &str) -> std::io::Result<Option<String>> {
+    match element.try_get_attribute(name).map_err(invalid_data)? {
+        Some(attribute) => Ok(Some(
+            attribute
+                .unescape_value()
+                .map_err(invalid_data)?
+                .into_owned(),
+        )),
+        None => Ok(None),
+    }
+}
+
+fn wang_side(wang_id: &[u32; 8], direction: Direction) -> [u32; 3] {
+    let (_, indices) = WANG_SIDES
+        .iter()
+        .find(|(side, _)| *side == direction)
+        .unwrap();
+    indices.map(|index| wang_id[index])
+}
+
+// Reads an image collection tileset made in the Tiled editor (.tsx)
+// Maps also work (.tmx), in which case the first tileset they use is read
+// Wang sets are turned into connections between tiles whose touching sides have the same colors
+pub fn import_tiled(path: impl AsRef<Path>) -> std::io::Result<Tileset> {
+    let path = path.as_ref();
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let contents = std::fs::read_to_string(path)?;
+    let mut reader = Reader::from_str(&contents);
+    reader.trim_text(true);
+
+    let mut images = Vec::new();
+    let mut tile_ids = Vec::new();
+    let mut wang_ids: HashMap<u32, [u32; 8]> = HashMap::new();
+    let mut current_tile = None;
+    let mut in_tileset = false;
+
+    loop {
+        match reader.read_event().map_err(invalid_data)? {
+            Event::Start(element) | Event::Empty(element) => match element.name().as_ref() {
+                b"tileset" => {
+                    if in_tileset {
+                        break;
+                    }
+                    // Maps usually refer to a separate tileset file
+                    if let Some(source) = attribute(&element, "source")? {
+                        return import_tiled(directory.join(source));
+                    }
+                    in_tileset = true;
+                }
+                b"tile" => {
+                    let id = attribute(&element, "id")?
+                        .ok_or_else(|| invalid_data("Tile without an id"))?;
+                    current_tile = Some(id.parse::<u32>().map_err(invalid_data)?);
+                }
+                b"image" => {
+                    let Some(id) = current_tile else {
+                        return Err(invalid_data("Only image collection tilesets are supported"));
+                    };
+                    let source = attribute(&element, "source")?
+                        .ok_or_else(|| invalid_data("Image without a source"))?;
+                    let image_path = directory.join(&source);
+                    images.push(TileImage::new(
+                        image_path.to_string_lossy().into_owned(),
+                        Path::new(&source)
+                            .file_stem()
+                            .map(|stem| stem.to_string_lossy().into_owned())
+                            .unwrap_or_default(),
+                    ));
+                    tile_ids.push(id);
+                }
+                b"wangtile" => {
+                    let tile_id = attribute(&element, "tileid")?
+                        .ok_or_else(|| invalid_data("Wang tile without a tile id"))?
+                        .parse::<u32>()
+                        .map_err(invalid_data)?;
+                    let colors = attribute(&element, "wangid")?
+                        .ok_or_else(|| invalid_data("Wang tile without a wang id"))?
+                        .split(',')
+                        .map(|color| color.trim().parse::<u32>().map_err(invalid_data))
+                        .collect::<std::io::Result<Vec<_>>>()?;
+                    let wang_id = colors
+                        .try_into()
+                        .map_err(|_| invalid_data("Wang ids need 8 colors"))?;
+                    wang_ids.insert(tile_id, wang_id);
+                }
+                _ => {}
+            },
+            Event::End(element) => match element.name().as_ref() {
+                b"tile" => current_tile = None,
+                b"tileset" => break,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let mut tiles = (0..images.len()).map(TileData::new).collect::<Vec<_>>();
+    let ids = tiles.iter().map(|tile| tile.id).collect::<Vec<_>>();
+    for (index, tile) in tiles.iter_mut().enumerate() {
+        let Some(wang_id) = wang_ids.get(&tile_ids[index]) else {
+            continue;
+        };
+        for (direction, _) in WANG_SIDES {
+            let side = wang_side(wang_id, direction);
+            for (other_index, other_id) in tile_ids.iter().enumerate() {
+                let Some(other_wang_id) = wang_ids.get(other_id) else {
+                    continue;
+                };
+                if wang_side(other_wang_id, direction.opposite()) == side {
+                    tile.valid_tiles_mut(direction)
+                        .push((ids[other_index], direction.opposite()));
+                }
+            }
+        }
+    }
+
+    Ok(Tileset {
+        images,
+        tiles,
+        ..Default::default()
+    })
+}
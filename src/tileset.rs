@@ -0,0 +1,486 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    tile::{tile_positions, Direction, TileData},
+    wfc::DIRECTIONS,
+};
+
+// How tiles decide which other tiles may sit next to them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Adjacency {
+    // Connections are listed by hand for every side
+    #[default]
+    Edge,
+    // Tiles fit together when the colors of their touching corners match
+    Corner,
+    // Connections are worked out from the sockets of every side whenever they change
+    Socket,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TileImage {
+    pub path: String,
+    pub name: String,
+    // Images shown after the main one for animated tiles
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frames: Vec<String>,
+    // Time every frame stays on screen in milliseconds
+    #[serde(default = "default_frame_duration")]
+    pub frame_duration: u64,
+}
+
+pub fn default_frame_duration() -> u64 {
+    100
+}
+
+impl TileImage {
+    pub fn new(path: String, name: String) -> Self {
+        Self {
+            path,
+            name,
+            frames: Vec::new(),
+            frame_duration: default_frame_duration(),
+        }
+    }
+}
+
+// Version of the tileset format written by this build
+// 1: no version field, tiles may be missing their weight
+// 2: the version is stored and every tile has a weight
+// 3: every tile has an id, connections and contextual weights refer to other tiles by it
+pub const TILESET_VERSION: u32 = 3;
+
+// On-disk representation of the loaded images and their connection rules
+// Images and tiles are stored in lockstep, like the loaded concepts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tileset {
+    pub version: u32,
+    pub images: Vec<TileImage>,
+    pub tiles: Vec<TileData>,
+    #[serde(default)]
+    pub adjacency: Adjacency,
+}
+
+impl Default for Tileset {
+    fn default() -> Self {
+        Self {
+            version: TILESET_VERSION,
+            images: Vec::new(),
+            tiles: Vec::new(),
+            adjacency: Adjacency::default(),
+        }
+    }
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+// Brings a tileset written by an older version up to date
+// Versions newer than this build are rejected, since their fields can't be understood
+fn migrate(mut tileset: Value) -> std::io::Result<Value> {
+    let version = tileset.get("version").and_then(Value::as_u64).unwrap_or(1);
+    if version > TILESET_VERSION as u64 {
+        return Err(invalid_data(format!(
+            "Tileset version {version} is newer than the supported version {TILESET_VERSION}"
+        )));
+    }
+
+    if version < 2 {
+        // Tiles without a weight were all equally likely
+        let tiles = tileset.get_mut("tiles").and_then(Value::as_array_mut);
+        for tile in tiles.into_iter().flatten().filter_map(Value::as_object_mut) {
+            tile.entry("weight").or_insert(json!(1.0));
+        }
+    }
+
+    if version < 3 {
+        // Connections listed positions, which become the ids so they keep pointing at the same tiles
+        let tiles = tileset.get_mut("tiles").and_then(Value::as_array_mut);
+        for (index, tile) in tiles.into_iter().flatten().enumerate() {
+            if let Some(tile) = tile.as_object_mut() {
+                tile.insert("id".to_owned(), json!(index));
+            }
+        }
+    }
+
+    if let Some(fields) = tileset.as_object_mut() {
+        fields.insert("version".to_owned(), json!(TILESET_VERSION));
+    }
+    Ok(tileset)
+}
+
+// Every tile needs an id of its own, and connections may only refer to tiles of the same tileset
+fn check_tile_ids(tileset: &Value) -> std::io::Result<()> {
+    let Some(tiles) = tileset.get("tiles").and_then(Value::as_array) else {
+        return Ok(());
+    };
+    let mut positions = HashMap::new();
+    for (index, tile) in tiles.iter().enumerate() {
+        let id = tile
+            .get("id")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| invalid_data(format!("Tile {index} has no id")))?;
+        if let Some(first) = positions.insert(id, index) {
+            return Err(invalid_data(format!(
+                "Tiles {first} and {index} share the id {id}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Fails on the first connection or contextual weight that refers to a tile that isn't in the tileset
+fn check_tile_references(tiles: &[TileData]) -> std::io::Result<()> {
+    let positions = tile_positions(tiles);
+    for (index, tile) in tiles.iter().enumerate() {
+        let missing = DIRECTIONS
+            .iter()
+            .flat_map(|&direction| tile.valid_tiles(direction).iter().map(|(id, _)| id))
+            .chain(tile.contextual_weight.keys().map(|(id, _)| id))
+            .find(|id| !positions.contains_key(id));
+        if let Some(missing) = missing {
+            return Err(invalid_data(format!(
+                "Tile {index} connects to missing tile {missing}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Path of a file relative to a folder, going up with .. where needed
+// Separated by slashes so the file reads the same on every system
+// None if the two have no common root, like files on different drives
+fn relative_path(path: &Path, base: &Path) -> Option<String> {
+    let path = std::path::absolute(path).ok()?;
+    let base = std::path::absolute(base).ok()?;
+    let mut path_components = path.components().peekable();
+    let mut base_components = base.components().peekable();
+    if path_components.peek() != base_components.peek() {
+        return None;
+    }
+    while path_components.peek().is_some() && path_components.peek() == base_components.peek() {
+        path_components.next();
+        base_components.next();
+    }
+
+    let relative = base_components
+        .map(|_| "..".to_owned())
+        .chain(
+            path_components.map(|component| component.as_os_str().to_string_lossy().into_owned()),
+        )
+        .collect::<Vec<_>>();
+    Some(relative.join("/"))
+}
+
+// Corners of a side, paired with the corners of the tile on the other side they touch
+const CORNER_SIDES: [(Direction, [(usize, usize); 2]); 4] = [
+    (Direction::North, [(0, 3), (1, 2)]),
+    (Direction::South, [(3, 0), (2, 1)]),
+    (Direction::East, [(1, 0), (2, 3)]),
+    (Direction::West, [(0, 1), (3, 2)]),
+];
+
+// Replaces the connections of every tile with the ones its corner colors allow
+pub fn connect_corners(tiles: &mut [TileData]) {
+    let corners = tiles
+        .iter()
+        .map(|tile| (tile.id, tile.corners))
+        .collect::<Vec<_>>();
+    for tile in tiles.iter_mut() {
+        for (direction, pairs) in CORNER_SIDES {
+            *tile.valid_tiles_mut(direction) = corners
+                .iter()
+                .filter(|(_, other)| {
+                    pairs
+                        .iter()
+                        .all(|&(own, theirs)| tile.corners[own] == other[theirs])
+                })
+                .map(|(id, _)| (*id, direction.opposite()))
+                .collect();
+        }
+    }
+}
+
+impl Tileset {
+    // Images are stored relative to the file, so the tileset can be moved along with its images
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let directory = Self::directory_of(path);
+        let mut tileset = self.clone();
+        tileset.map_image_paths(|image| {
+            relative_path(Path::new(image), &directory).unwrap_or_else(|| image.to_owned())
+        });
+        std::fs::write(path, tileset.to_text()?)
+    }
+
+    // Text of a tileset file
+    // Connections list other tiles by id, so the file still reads right after reordering it
+    pub fn to_text(&self) -> std::io::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    // Relative image paths are read from the folder of the file
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let mut tileset = Self::parse(&std::fs::read_to_string(path)?)?;
+        let directory = Self::directory_of(path);
+        tileset.map_image_paths(|image| {
+            if Path::new(image).is_absolute() {
+                image.to_owned()
+            } else {
+                // Images that exist get a path without any .. in it
+                let joined = directory.join(image);
+                std::fs::canonicalize(&joined)
+                    .unwrap_or(joined)
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        });
+        Ok(tileset)
+    }
+
+    fn directory_of(path: &Path) -> PathBuf {
+        match path.parent() {
+            Some(directory) if !directory.as_os_str().is_empty() => directory.to_owned(),
+            _ => PathBuf::from("."),
+        }
+    }
+
+    // Changes the path of every image and animation frame
+    fn map_image_paths(&mut self, map: impl Fn(&str) -> String) {
+        for image in &mut self.images {
+            image.path = map(&image.path);
+            for frame in &mut image.frames {
+                *frame = map(frame);
+            }
+        }
+    }
+
+    // Fails with every image that can't be found, so they can all be fixed at once
+    pub fn check_images(&self) -> std::io::Result<()> {
+        let missing = self
+            .images
+            .iter()
+            .flat_map(|image| std::iter::once(&image.path).chain(&image.frames))
+            .filter(|path| !Path::new(path).exists())
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Missing images: {}", missing.join(", ")),
+        ))
+    }
+
+    // Reads a tileset from the text of a tileset file
+    pub fn parse(text: &str) -> std::io::Result<Self> {
+        // Connections to missing tiles would break the generation
+        let tileset = migrate(serde_json::from_str(text)?)?;
+        check_tile_ids(&tileset)?;
+        let mut tileset: Self = serde_json::from_value(tileset)?;
+        check_tile_references(&tileset.tiles)?;
+        if tileset.images.len() != tileset.tiles.len() {
+            return Err(invalid_data(
+                "Tileset has a different number of images and tiles".to_owned(),
+            ));
+        }
+        // Tiles that were moved around in the file keep lining up with their images
+        for (index, tile) in tileset.tiles.iter_mut().enumerate() {
+            tile.image_index = index;
+        }
+        Ok(tileset)
+    }
+    // Adjacency graph in the Graphviz DOT format
+    // Every tile is a node, and every allowed neighbor an edge labeled with its direction
+    pub fn export_adjacency_dot(&self) -> String {
+        let mut dot = String::from("digraph adjacency {\n");
+        for (index, image) in self.images.iter().enumerate() {
+            let name = if image.name.is_empty() {
+                index.to_string()
+            } else {
+                image.name.replace('"', "\\\"")
+            };
+            dot.push_str(&format!("    t{index} [label=\"{name}\"];\n"));
+        }
+        let positions = tile_positions(&self.tiles);
+        for (index, tile) in self.tiles.iter().enumerate() {
+            for direction in DIRECTIONS {
+                for (id, _) in tile.valid_tiles(direction) {
+                    let Some(other) = positions.get(id) else {
+                        continue;
+                    };
+                    dot.push_str(&format!(
+                        "    t{index} -> t{other} [label=\"{direction}\"];\n"
+                    ));
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrating_version_one_adds_weights_and_ids() {
+        let tileset = json!({
+            "images": [],
+            "tiles": [{ "image_index": 0 }, { "image_index": 1, "weight": 2.0 }],
+        });
+        let tileset = migrate(tileset).unwrap();
+        assert_eq!(tileset["version"], json!(TILESET_VERSION));
+        assert_eq!(tileset["tiles"][0]["weight"], json!(1.0));
+        assert_eq!(tileset["tiles"][1]["weight"], json!(2.0));
+        assert_eq!(tileset["tiles"][0]["id"], json!(0));
+        assert_eq!(tileset["tiles"][1]["id"], json!(1));
+    }
+
+    #[test]
+    fn migrating_keeps_ids_of_the_current_version() {
+        let tileset = json!({
+            "version": TILESET_VERSION,
+            "tiles": [{ "id": 42, "weight": 3.0 }],
+        });
+        assert_eq!(migrate(tileset.clone()).unwrap(), tileset);
+    }
+
+    #[test]
+    fn migrating_rejects_newer_versions() {
+        let tileset = json!({ "version": TILESET_VERSION + 1, "tiles": [] });
+        let err = migrate(tileset).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn tile_ids_have_to_be_unique_and_exist() {
+        let shared = json!({ "tiles": [{ "id": 1 }, { "id": 1 }] });
+        assert!(check_tile_ids(&shared).is_err());
+
+        let without_id = json!({ "tiles": [{}] });
+        assert!(check_tile_ids(&without_id).is_err());
+
+        let mut tile = TileData::new(0);
+        tile.east_valid_tiles.push((5, Direction::West));
+        let err = check_tile_references(&[tile.clone()]).unwrap_err();
+        assert!(err.to_string().contains("missing tile 5"), "{err}");
+
+        tile.east_valid_tiles.clear();
+        tile.contextual_weight.insert((6, Direction::East), 2.0);
+        assert!(check_tile_references(&[tile]).is_err());
+    }
+
+    #[test]
+    fn reordered_tiles_keep_their_connections() {
+        let text = r#"{
+            "version": 3,
+            "images": [{ "path": "a.png", "name": "a" }, { "path": "b.png", "name": "b" }],
+            "tiles": [
+                { "image_index": 1, "id": 20, "north_valid_tiles": [[10, "South"]], "south_valid_tiles": [],
+                  "east_valid_tiles": [], "west_valid_tiles": [], "contextual_weight": [] },
+                { "image_index": 0, "id": 10, "north_valid_tiles": [], "south_valid_tiles": [],
+                  "east_valid_tiles": [], "west_valid_tiles": [], "contextual_weight": [[[20, "East"], 2.0]] }
+            ]
+        }"#;
+        let tileset = Tileset::parse(text).unwrap();
+        assert_eq!(tileset.tiles[0].image_index, 0);
+        assert_eq!(
+            tileset.tiles[0].north_valid_tiles,
+            vec![(10, Direction::South)]
+        );
+        assert!(tileset.tiles[0].allows(Direction::North, &tileset.tiles[1]));
+        assert_eq!(
+            tileset.tiles[1].contextual_weight,
+            HashMap::from([((20, Direction::East), 2.0)])
+        );
+    }
+
+    #[test]
+    fn relative_paths_go_up_where_needed() {
+        let base = std::env::temp_dir().join("tilesets");
+        assert_eq!(
+            relative_path(&base.join("images").join("a.png"), &base).as_deref(),
+            Some("images/a.png")
+        );
+        assert_eq!(
+            relative_path(&base.parent().unwrap().join("b.png"), &base).as_deref(),
+            Some("../b.png")
+        );
+        assert_eq!(
+            relative_path(&base.join("c.png"), &base.join("x").join("y")).as_deref(),
+            Some("../../c.png")
+        );
+    }
+
+    #[test]
+    fn saved_tilesets_read_back_the_same() {
+        let mut tileset = Tileset::default();
+        for index in 0..3 {
+            tileset.images.push(TileImage::new(
+                format!("/tiles/{index}.png"),
+                index.to_string(),
+            ));
+            tileset.tiles.push(TileData::new(index));
+        }
+        let ids = tileset.tiles.iter().map(|tile| tile.id).collect::<Vec<_>>();
+        tileset.tiles[0]
+            .east_valid_tiles
+            .push((ids[2], Direction::West));
+        tileset.tiles[2]
+            .west_valid_tiles
+            .push((ids[0], Direction::East));
+
+        let parsed = Tileset::parse(&tileset.to_text().unwrap()).unwrap();
+        assert_eq!(parsed.tiles, tileset.tiles);
+    }
+
+    #[test]
+    fn every_version_reads_back_the_same() {
+        // The same two tiles in each version of the format, connected to each other east to west
+        let v1 = r#"{
+            "images": [{ "path": "a.png", "name": "a" }, { "path": "b.png", "name": "b" }],
+            "tiles": [
+                { "image_index": 0, "north_valid_tiles": [], "south_valid_tiles": [],
+                  "east_valid_tiles": [[1, "West"]], "west_valid_tiles": [] },
+                { "image_index": 1, "north_valid_tiles": [], "south_valid_tiles": [],
+                  "east_valid_tiles": [], "west_valid_tiles": [[0, "East"]] }
+            ]
+        }"#;
+        let v2 = v1.replacen("{\n", "{ \"version\": 2,\n", 1).replace(
+            "\"image_index\": 1,",
+            "\"image_index\": 1, \"weight\": 3.0,",
+        );
+        let v3 = v1
+            .replacen("{\n", "{ \"version\": 3,\n", 1)
+            .replace("\"image_index\": 0,", "\"image_index\": 0, \"id\": 70,")
+            .replace("\"image_index\": 1,", "\"image_index\": 1, \"id\": 80,")
+            .replace("[[1, \"West\"]]", "[[80, \"West\"]]")
+            .replace("[[0, \"East\"]]", "[[70, \"East\"]]");
+
+        for (version, text) in [(1, v1.to_owned()), (2, v2), (3, v3)] {
+            let tileset = Tileset::parse(&text).unwrap();
+            assert_eq!(tileset.version, TILESET_VERSION, "version {version}");
+            let [first, second] = [&tileset.tiles[0], &tileset.tiles[1]];
+            assert_eq!(first.east_valid_tiles, vec![(second.id, Direction::West)]);
+            assert_eq!(second.west_valid_tiles, vec![(first.id, Direction::East)]);
+            assert!(first.allows(Direction::East, second));
+            let weight = if version == 2 { 3.0 } else { 1.0 };
+            assert_eq!(tileset.tiles[1].weight, weight, "version {version}");
+
+            let reread = Tileset::parse(&tileset.to_text().unwrap()).unwrap();
+            assert_eq!(reread.tiles, tileset.tiles, "version {version}");
+            assert_eq!(reread.images.len(), 2);
+        }
+    }
+}
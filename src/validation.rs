@@ -0,0 +1,247 @@
+use crate::{
+    components::tile_creation_component::ImageData,
+    tile::{tile_positions, Direction, TileData},
+    wfc::DIRECTIONS,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    // The tileset works, but probably not the way it was meant to
+    Warning,
+    // The tileset can't be generated from
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    // Tile the issue was found on
+    pub tile: usize,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn new(severity: Severity, tile: usize, message: String) -> Self {
+        Self {
+            severity,
+            tile,
+            message,
+        }
+    }
+}
+
+// Lists every problem with a tileset, errors first
+pub fn validate(images: &[ImageData], tiles: &[TileData]) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    let label = |index: usize| {
+        images
+            .get(index)
+            .map(|image| image.label(index))
+            .unwrap_or_else(|| format!("{index}"))
+    };
+
+    for (index, image) in images.iter().enumerate() {
+        if image.size[0] != image.size[1] {
+            issues.push(ValidationIssue::new(
+                Severity::Error,
+                index,
+                format!("Tile {} is not square", label(index)),
+            ));
+        }
+    }
+
+    let positions = tile_positions(tiles);
+    for (index, tile) in tiles.iter().enumerate() {
+        if tile.image_index >= images.len() {
+            issues.push(ValidationIssue::new(
+                Severity::Error,
+                index,
+                format!(
+                    "Tile {} shows image {}, which doesn't exist",
+                    label(index),
+                    tile.image_index
+                ),
+            ));
+        }
+
+        for direction in DIRECTIONS {
+            let connections = tile.valid_tiles(direction);
+            if connections.is_empty() {
+                issues.push(ValidationIssue::new(
+                    Severity::Warning,
+                    index,
+                    format!(
+                        "Tile {} has no {direction} connections, so it only fits on that edge",
+                        label(index)
+                    ),
+                ));
+            }
+
+            for (id, side) in connections {
+                let other = positions.get(id).copied();
+                match other.map(|other| (other, &tiles[other])) {
+                    None => issues.push(ValidationIssue::new(
+                        Severity::Error,
+                        index,
+                        format!(
+                            "Tile {} connects to a tile with id {id} to its {direction}, which doesn't exist",
+                            label(index)
+                        ),
+                    )),
+                    Some((other, other_tile)) if other_tile.facing(*side) != direction.opposite() => {
+                        issues.push(ValidationIssue::new(
+                            Severity::Warning,
+                            index,
+                            format!(
+                                "Tile {} connects to the {side} side of {} to its {direction}, which never faces it",
+                                label(index),
+                                label(other)
+                            ),
+                        ))
+                    }
+                    Some((other, other_tile)) if !other_tile.allows(direction.opposite(), tile) => {
+                        issues.push(ValidationIssue::new(
+                            Severity::Warning,
+                            index,
+                            format!(
+                                "Tile {} allows {} to its {direction}, but not the other way around",
+                                label(index),
+                                label(other)
+                            ),
+                        ))
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.severity != Severity::Error);
+    issues
+}
+
+// Looks for tilesets that are likely to contradict, before any time is spent generating
+// Only the middle of the board is considered, cells on the edges don't need a neighbor on every side
+pub fn precheck_solvability(tiles: &[TileData]) -> Vec<String> {
+    let mut notes = Vec::new();
+    if tiles.is_empty() {
+        notes.push("There are no tiles to place".to_string());
+        return notes;
+    }
+
+    // Both tiles have to agree for them to be placed next to each other
+    let fits = |tile: usize, direction: Direction, other: usize| {
+        tiles[tile].allows(direction, &tiles[other])
+            && tiles[other].allows(direction.opposite(), &tiles[tile])
+    };
+
+    // A tile missing a neighbor on some side can't be placed in the middle,
+    // which can in turn leave the tiles that relied on it without a neighbor
+    let mut usable = vec![true; tiles.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for tile in 0..tiles.len() {
+            if !usable[tile] {
+                continue;
+            }
+            let missing = DIRECTIONS.into_iter().find(|&direction| {
+                !(0..tiles.len()).any(|other| usable[other] && fits(tile, direction, other))
+            });
+            let Some(direction) = missing else {
+                continue;
+            };
+
+            usable[tile] = false;
+            changed = true;
+            if (0..tiles.len()).any(|other| fits(tile, direction, other)) {
+                notes.push(format!(
+                    "Tile {tile} can only be placed on the edges, every tile that fits to its {direction} can't be placed in the middle either"
+                ));
+            } else {
+                notes.push(format!(
+                    "Tile {tile} can only be placed on the edges, no tile fits to its {direction}"
+                ));
+            }
+        }
+    }
+    if !usable.contains(&true) {
+        notes.push(
+            "No tile can be placed in the middle of the board, so only very small boards can be generated"
+                .to_string(),
+        );
+        return notes;
+    }
+
+    // Tiles that never end up next to each other along an axis can't share a row or column,
+    // so a row that starts with one group has to be finished with it
+    for (direction, axis) in [(Direction::East, "row"), (Direction::South, "column")] {
+        let mut group = vec![None; tiles.len()];
+        let mut groups = Vec::new();
+        for start in (0..tiles.len()).filter(|&tile| usable[tile]) {
+            if group[start].is_some() {
+                continue;
+            }
+            let mut members = Vec::new();
+            let mut stack = vec![start];
+            group[start] = Some(groups.len());
+            while let Some(tile) = stack.pop() {
+                members.push(tile);
+                for other in 0..tiles.len() {
+                    if usable[other]
+                        && group[other].is_none()
+                        && (fits(tile, direction, other) || fits(other, direction, tile))
+                    {
+                        group[other] = Some(groups.len());
+                        stack.push(other);
+                    }
+                }
+            }
+            members.sort_unstable();
+            groups.push(members);
+        }
+
+        if groups.len() > 1 {
+            let groups = groups
+                .iter()
+                .map(|members| format!("{members:?}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            notes.push(format!(
+                "The tiles split into groups that never share a {axis}: {groups}"
+            ));
+        }
+    }
+
+    notes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_without_an_image_are_errors() {
+        let images = vec![ImageData::new(
+            "a.png".to_owned(),
+            imgui::TextureId::new(0),
+            [1.0, 1.0],
+        )];
+        let mut tiles = vec![TileData::new(0), TileData::new(1)];
+        for tile in &mut tiles {
+            for direction in DIRECTIONS {
+                let id = tile.id;
+                tile.valid_tiles_mut(direction)
+                    .push((id, direction.opposite()));
+            }
+        }
+
+        let issues = validate(&images, &tiles);
+        assert!(issues
+            .iter()
+            .any(|issue| issue.severity == Severity::Error && issue.tile == 1));
+        assert!(!issues
+            .iter()
+            .any(|issue| issue.severity == Severity::Error && issue.tile == 0));
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant, SystemTime},
+};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+// Editors often write a file in several steps, so changes are only reported once they settle
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+// Watches a tileset file for changes made outside of the app
+#[derive(Debug, Clone)]
+pub struct TilesetWatcher {
+    path: PathBuf,
+    // Stops watching once the last clone is dropped
+    _watcher: Rc<RecommendedWatcher>,
+    events: Rc<Receiver<notify::Result<Event>>>,
+    // Time of the latest change that hasn't been reported yet
+    changed_at: Option<Instant>,
+    // Modification time of the version in use, so saving from the app doesn't trigger a reload
+    loaded_modified: Option<SystemTime>,
+}
+
+impl TilesetWatcher {
+    // The folder is watched rather than the file, since many editors replace the file when saving
+    pub fn new(path: &str) -> notify::Result<Self> {
+        let path = PathBuf::from(path);
+        let folder = match path.parent() {
+            Some(folder) if !folder.as_os_str().is_empty() => folder.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(&folder, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            loaded_modified: modified(&path),
+            path,
+            _watcher: Rc::new(watcher),
+            events: Rc::new(events),
+            changed_at: None,
+        })
+    }
+
+    // Whether the file changed and has been left alone long enough to be reloaded
+    pub fn poll(&mut self) -> bool {
+        let file_name = self.path.file_name();
+        for event in self.events.try_iter().flatten() {
+            if event.paths.iter().any(|path| path.file_name() == file_name) {
+                self.changed_at = Some(Instant::now());
+            }
+        }
+
+        let Some(changed_at) = self.changed_at else {
+            return false;
+        };
+        if changed_at.elapsed() < DEBOUNCE {
+            return false;
+        }
+        self.changed_at = None;
+
+        let current = modified(&self.path);
+        current.is_some() && current != self.loaded_modified
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -0,0 +1,2010 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    grid::{lowest_entropy, weighted_pick, Grid, SquareGrid},
+    map::Map,
+    tile::{tile_positions, Direction, TileData},
+};
+
+pub const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::South,
+    Direction::East,
+    Direction::West,
+];
+
+// Which open cell is collapsed next
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CollapseBias {
+    // The cell with the fewest possible tiles
+    #[default]
+    LowestEntropy,
+    // Cells closest to the center first, entropy only breaks ties
+    CenterOut,
+    // Cells closest to an edge first, entropy only breaks ties
+    EdgesIn,
+    // Row by row from the top left, without any randomness
+    Scanline,
+    // Cells closest to a corner first, so the four corners collapse before anything else
+    CornersIn,
+}
+
+impl CollapseBias {
+    pub const ALL: [CollapseBias; 5] = [
+        CollapseBias::LowestEntropy,
+        CollapseBias::CenterOut,
+        CollapseBias::EdgesIn,
+        CollapseBias::Scanline,
+        CollapseBias::CornersIn,
+    ];
+}
+
+impl std::fmt::Display for CollapseBias {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CollapseBias::LowestEntropy => write!(f, "Lowest entropy"),
+            CollapseBias::CenterOut => write!(f, "Center out"),
+            CollapseBias::EdgesIn => write!(f, "Edges in"),
+            CollapseBias::Scanline => write!(f, "Scanline"),
+            CollapseBias::CornersIn => write!(f, "Corners in"),
+        }
+    }
+}
+
+// User facing parameters of a generation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationSettings {
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+    // Connects opposite edges of the canvas so the output tiles seamlessly
+    pub wrap: bool,
+    // Delay between collapses in milliseconds
+    pub step_interval: u64,
+    // Collapses run in a single frame when there is no delay, so large boards still animate
+    pub max_collapses_per_frame: u32,
+    // Random amount added to the entropy of every cell so ties are broken fairly
+    // Anything below 1.0 never outweighs an actual difference in entropy
+    pub noise: f32,
+    // Picks evenly among the cells tied for the lowest entropy instead of adding noise,
+    // so the first collapses aren't drawn towards any part of the canvas
+    pub uniform_tie_break: bool,
+    // Generations tried with consecutive seeds until one finishes without contradictions
+    pub max_attempts: u32,
+    // Cells further than this from a collapse aren't updated, None propagates over the whole board
+    // Faster on large tilesets, but contradictions get more likely
+    pub propagation_radius: Option<usize>,
+    // Step intervals without a collapse before an unfinished board is reported as stalled
+    pub stall_limit: u32,
+    pub collapse_order: CollapseBias,
+    // With the corners in order, every corner takes the tile of the first one wherever it fits
+    pub matching_corners: bool,
+}
+
+impl Default for GenerationSettings {
+    fn default() -> Self {
+        Self {
+            width: 10,
+            height: 10,
+            seed: 0,
+            wrap: false,
+            step_interval: 100,
+            max_collapses_per_frame: 16,
+            noise: 0.5,
+            uniform_tie_break: false,
+            max_attempts: 1,
+            propagation_radius: None,
+            stall_limit: 10,
+            collapse_order: CollapseBias::LowestEntropy,
+            matching_corners: false,
+        }
+    }
+}
+
+// Another tile by its position in the tileset, and the side of it that faces back
+// Tiles refer to each other by id, the solver only works with positions
+type Connection = (usize, Direction);
+
+// Connections of every tile, with the other tiles turned from their ids into positions in the tileset
+// Connections to tiles that aren't in the tileset are left out
+fn resolve_connections(tiles: &[TileData]) -> Vec<[Vec<Connection>; 4]> {
+    let positions = tile_positions(tiles);
+    tiles
+        .iter()
+        .map(|tile| {
+            DIRECTIONS.map(|direction| {
+                tile.valid_tiles(direction)
+                    .iter()
+                    .filter_map(|(id, side)| Some((*positions.get(id)?, *side)))
+                    .collect()
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct PossibleConnections {
+    north_connections: Vec<Connection>,
+    south_connections: Vec<Connection>,
+    east_connections: Vec<Connection>,
+    west_connections: Vec<Connection>,
+}
+
+impl PossibleConnections {
+    // Randomly chooses a tile from the possible states of the location
+    // Returns None if there are no tiles to choose from
+    fn random_tile<'a>(
+        &'a self,
+        tiles: &'a [TileData],
+        rng: &mut impl Rng,
+    ) -> Option<&'a TileData> {
+        if self == &Self::default() {
+            return tiles.first();
+        }
+        loop {
+            let dir_f: f32 = rng.gen();
+            let dir = (dir_f * 4.0) as usize;
+            let temp_vec = Vec::new();
+            let arr = match dir {
+                0 => &self.north_connections,
+                1 => &self.south_connections,
+                2 => &self.east_connections,
+                3 => &self.west_connections,
+                _ => &temp_vec,
+            };
+
+            if arr.is_empty() {
+                continue;
+            }
+            let index_f: f32 = rng.gen();
+            let index = (index_f * arr.len() as f32) as usize;
+            return tiles.get(arr[index].0);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolverError {
+    // There are no tiles to collapse the board into
+    EmptyTileset,
+    // A replayed decision refers to a cell or tile that doesn't exist
+    InvalidDecision(usize, usize, usize),
+}
+
+impl std::fmt::Display for SolverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolverError::EmptyTileset => write!(f, "The tileset is empty"),
+            SolverError::InvalidDecision(row, col, tile) => write!(
+                f,
+                "Tile {tile} at row {row}, column {col} doesn't fit the canvas or tileset"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SolverError {}
+
+// Time spent in each phase of the algorithm, added up until it is taken from the solver
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhaseTimings {
+    // Finding the cell to collapse next
+    pub entropy_scan: Duration,
+    // Picking a tile for that cell
+    pub collapse: Duration,
+    // Narrowing down the cells around it
+    pub propagation: Duration,
+}
+
+impl std::ops::AddAssign for PhaseTimings {
+    fn add_assign(&mut self, other: Self) {
+        self.entropy_scan += other.entropy_scan;
+        self.collapse += other.collapse;
+        self.propagation += other.propagation;
+    }
+}
+
+// Summary of many generations of the same tileset and settings
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchStats {
+    pub runs: usize,
+    // Runs where at least one cell had no valid tile left
+    pub contradicted_runs: usize,
+    pub total_contradictions: usize,
+    pub average_steps: f32,
+    // How many cells were observed at each entropy, indexed by the entropy
+    pub entropy_histogram: Vec<usize>,
+}
+
+impl BatchStats {
+    pub fn contradiction_rate(&self) -> f32 {
+        if self.runs == 0 {
+            return 0.0;
+        }
+        self.contradicted_runs as f32 / self.runs as f32
+    }
+
+    pub fn average_entropy(&self) -> f32 {
+        let observed = self.entropy_histogram.iter().sum::<usize>();
+        if observed == 0 {
+            return 0.0;
+        }
+        let total = self
+            .entropy_histogram
+            .iter()
+            .enumerate()
+            .map(|(entropy, count)| entropy * count)
+            .sum::<usize>();
+        total as f32 / observed as f32
+    }
+}
+
+impl std::fmt::Display for BatchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Runs: {}", self.runs)?;
+        writeln!(
+            f,
+            "Contradiction rate: {:.1}% ({} contradictions)",
+            self.contradiction_rate() * 100.0,
+            self.total_contradictions
+        )?;
+        writeln!(f, "Average steps: {:.1}", self.average_steps)?;
+        write!(f, "Average observed entropy: {:.2}", self.average_entropy())
+    }
+}
+
+// Headless state of the wave function collapse
+// Owns the board and runs the algorithm independently of any rendering
+#[derive(Debug, Clone)]
+pub struct Solver {
+    settings: GenerationSettings,
+    rng: StdRng,
+    current_tile_set: Vec<TileData>,
+    // Connections of every tile by position, indexed by tile and then direction
+    connections: Vec<[Vec<Connection>; 4]>,
+    canvas_connections: Vec<Vec<PossibleConnections>>,
+    // Tiles every open cell can still take, kept up to date by the propagation
+    // Its length is the entropy of the cell
+    cell_candidates: Vec<Vec<Vec<usize>>>,
+    canvas_representation: Vec<Vec<Option<TileData>>>,
+    // Category every cell is restricted to, None leaves the cell open to any tile
+    mask: Vec<Vec<Option<String>>>,
+    // Tiles every cell is limited to, like the tiles a layer allows on top of the board below it
+    // None leaves the cell open to any tile
+    restrictions: Vec<Vec<Option<Vec<usize>>>>,
+    // Tiles every cell may not take, on top of the mask and restrictions
+    forbidden: Vec<Vec<HashSet<usize>>>,
+    // Tiles allowed along each edge of the canvas, edges without an entry allow any tile
+    edge_constraints: HashMap<Direction, Vec<usize>>,
+    // Cells collapsed without any valid tile left since the last reset
+    contradictions: usize,
+    // Starts at 1, every retry uses the seed after the previous one
+    attempt: u32,
+    // Seed the random state was last restarted from
+    seed: u64,
+    // Every placed tile as (row, column, tile) since the last restart, kept while recording
+    recording: bool,
+    decisions: Vec<(usize, usize, usize)>,
+    timings: PhaseTimings,
+    // Copies of every tile on the board, for the minimum and maximum counts
+    placed: Vec<usize>,
+    // Tiles short of their minimum count that no open cell can take anymore
+    out_of_reach: HashSet<usize>,
+    // Goes up whenever the board or the candidates of its cells change
+    revision: u64,
+}
+
+impl Solver {
+    pub fn new(tiles: Vec<TileData>, settings: GenerationSettings) -> Self {
+        let mut solver = Self {
+            settings,
+            rng: StdRng::seed_from_u64(settings.seed),
+            connections: resolve_connections(&tiles),
+            current_tile_set: tiles,
+            canvas_connections: Vec::new(),
+            cell_candidates: Vec::new(),
+            canvas_representation: Vec::new(),
+            mask: vec![vec![None; settings.width]; settings.height],
+            restrictions: vec![vec![None; settings.width]; settings.height],
+            forbidden: vec![vec![HashSet::new(); settings.width]; settings.height],
+            edge_constraints: HashMap::new(),
+            contradictions: 0,
+            attempt: 1,
+            seed: settings.seed,
+            recording: false,
+            decisions: Vec::new(),
+            timings: PhaseTimings::default(),
+            placed: Vec::new(),
+            out_of_reach: HashSet::new(),
+            revision: 0,
+        };
+        solver.reset();
+        solver
+    }
+
+    // Clears the board and restarts the generation from the seed
+    pub fn reset(&mut self) {
+        self.attempt = 1;
+        self.restart();
+    }
+
+    // Clears the board and restarts from a seed of an earlier run, for comparing it to an edited tileset
+    // Retries are skipped, so the board only depends on the seed and the tiles
+    pub fn rerun_seed(&mut self, seed: u64) {
+        self.attempt = self.settings.max_attempts.max(1);
+        self.restart_from(seed);
+    }
+
+    // Clears the board for the current attempt
+    fn restart(&mut self) {
+        self.restart_from(self.settings.seed.wrapping_add(self.attempt as u64 - 1));
+    }
+
+    fn restart_from(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+        self.contradictions = 0;
+        self.decisions.clear();
+        self.clear_board();
+    }
+
+    // Empties every cell and applies the mask and edge constraints again
+    fn clear_board(&mut self) {
+        self.revision += 1;
+        self.canvas_representation = vec![vec![None; self.settings.width]; self.settings.height];
+        self.placed = vec![0; self.current_tile_set.len()];
+        self.out_of_reach.clear();
+        self.fill_representation_array();
+
+        // Restricted cells narrow down their surroundings before anything is collapsed
+        for row_index in 0..self.settings.height {
+            for col_index in 0..self.settings.width {
+                let pos = (row_index, col_index);
+                if self.mask[row_index][col_index].is_some()
+                    || self.restrictions[row_index][col_index].is_some()
+                    || !self.forbidden[row_index][col_index].is_empty()
+                    || !self.edges_of(pos).is_empty()
+                {
+                    self.propagate(pos);
+                }
+            }
+        }
+
+        // Every cell starts from the tiles that fit it, so corners and constrained cells get a lower entropy
+        self.cell_candidates = (0..self.settings.height)
+            .map(|row_index| {
+                (0..self.settings.width)
+                    .map(|col_index| self.candidates((row_index, col_index)))
+                    .collect()
+            })
+            .collect();
+    }
+
+    // Clears a rectangle of cells between two corners (x is the column, y the row, both inclusive)
+    // The cells around it stay collapsed and constrain it, so the next steps only fill the rectangle
+    // The random state isn't reset, so every call gives a different result
+    pub fn regenerate_region(&mut self, x0: usize, y0: usize, x1: usize, y1: usize) {
+        let columns = x0.min(x1)..=x0.max(x1);
+        let rows = y0.min(y1)..=y0.max(y1);
+        let board = std::mem::take(&mut self.canvas_representation);
+        self.clear_board();
+
+        // Kept cells were already logged when they were first placed
+        let recording = std::mem::replace(&mut self.recording, false);
+        for (row_index, row) in board.into_iter().enumerate() {
+            for (col_index, tile) in row.into_iter().enumerate() {
+                if rows.contains(&row_index) && columns.contains(&col_index) {
+                    continue;
+                }
+                if let Some(tile) = tile {
+                    self.place((row_index, col_index), tile);
+                }
+            }
+        }
+        self.recording = recording;
+
+        // Retrying would restart the whole board and lose the kept cells
+        self.attempt = self.settings.max_attempts.max(1);
+    }
+
+    pub fn edge_constraints(&self) -> &HashMap<Direction, Vec<usize>> {
+        &self.edge_constraints
+    }
+
+    // Restricts the cells along the edges of the canvas and restarts the generation
+    pub fn set_edge_constraints(&mut self, edge_constraints: HashMap<Direction, Vec<usize>>) {
+        self.edge_constraints = edge_constraints;
+        self.reset();
+    }
+
+    // Constrained edges of the canvas a position lies on
+    // This is independent of wrapping, so a wrapped canvas still keeps its edges
+    fn edges_of(&self, pos: (usize, usize)) -> Vec<&Vec<usize>> {
+        let on_edge = [
+            (Direction::North, pos.0 == 0),
+            (Direction::South, pos.0 + 1 == self.settings.height),
+            (Direction::East, pos.1 + 1 == self.settings.width),
+            (Direction::West, pos.1 == 0),
+        ];
+        on_edge
+            .iter()
+            .filter(|(_, on_edge)| *on_edge)
+            .filter_map(|(direction, _)| self.edge_constraints.get(direction))
+            .collect()
+    }
+
+    pub fn mask(&self) -> &[Vec<Option<String>>] {
+        &self.mask
+    }
+
+    // Restricts cells to the tiles of a category and restarts the generation
+    // The mask has to be the same size as the canvas
+    pub fn set_mask(&mut self, mask: Vec<Vec<Option<String>>>) {
+        self.mask = mask;
+        self.reset();
+    }
+
+    pub fn restrictions(&self) -> &[Vec<Option<Vec<usize>>>] {
+        &self.restrictions
+    }
+
+    // Limits cells to a set of tiles and restarts the generation
+    // The restrictions have to be the same size as the canvas
+    pub fn set_restrictions(&mut self, restrictions: Vec<Vec<Option<Vec<usize>>>>) {
+        self.restrictions = restrictions;
+        self.reset();
+    }
+
+    pub fn forbidden(&self) -> &[Vec<HashSet<usize>>] {
+        &self.forbidden
+    }
+
+    // Keeps tiles out of cells and restarts the generation
+    // The forbidden tiles have to be the same size as the canvas
+    pub fn set_forbidden(&mut self, forbidden: Vec<Vec<HashSet<usize>>>) {
+        self.forbidden = forbidden;
+        self.reset();
+    }
+
+    // Whether a tile belongs to the category of a cell, is among the tiles the cell is limited to
+    // and isn't forbidden there, and it hasn't been placed as often as it may be
+    fn allowed_in_cell(&self, pos: (usize, usize), index: usize) -> bool {
+        let category = &self.mask[pos.0][pos.1];
+        let restriction = &self.restrictions[pos.0][pos.1];
+        category
+            .as_ref()
+            .is_none_or(|category| self.current_tile_set[index].categories.contains(category))
+            && restriction
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(&index))
+            && !self.forbidden[pos.0][pos.1].contains(&index)
+            && !self.exhausted(index)
+    }
+
+    // Whether a tile was placed as often as its maximum count allows
+    fn exhausted(&self, index: usize) -> bool {
+        self.current_tile_set[index]
+            .max_count
+            .is_some_and(|max_count| self.placed_count(index) >= max_count as usize)
+    }
+
+    fn placed_count(&self, index: usize) -> usize {
+        self.placed.get(index).copied().unwrap_or(0)
+    }
+
+    // Whether a tile was placed fewer times than its minimum count asks for
+    fn short_of_minimum(&self, index: usize) -> bool {
+        self.placed_count(index) < self.current_tile_set[index].min_count as usize
+    }
+
+    pub fn tiles(&self) -> &[TileData] {
+        &self.current_tile_set
+    }
+
+    pub fn representation(&self) -> &[Vec<Option<TileData>>] {
+        &self.canvas_representation
+    }
+
+    // Tiles that can still be placed at a position
+    // Read from the candidates kept up to date by the propagation, so it is cheap to call every frame
+    pub fn possible_tiles(&self, pos: (usize, usize)) -> Vec<usize> {
+        self.cell_candidates[pos.0][pos.1].clone()
+    }
+
+    // Every tile that could be picked if the cell at column x, row y collapsed next,
+    // with its normalized weight, which is the chance it would be drawn
+    pub fn cell_distribution(&self, x: usize, y: usize) -> Vec<(usize, f32)> {
+        if let Some(tile) = &self.canvas_representation[y][x] {
+            return vec![(tile.image_index, 1.0)];
+        }
+
+        // The cached candidates already agree with the collapsed neighbors
+        let possible_tiles = &self.cell_candidates[y][x];
+        let weights = possible_tiles
+            .iter()
+            .map(|&index| self.weight_at(index, (y, x)).max(0.0))
+            .collect::<Vec<_>>();
+        let total = weights.iter().sum::<f32>();
+        let count = possible_tiles.len() as f32;
+        possible_tiles
+            .iter()
+            .zip(weights)
+            .map(|(&index, weight)| {
+                if total > 0.0 {
+                    (index, weight / total)
+                } else {
+                    // Every weight is zero, so the pick is uniform
+                    (index, 1.0 / count)
+                }
+            })
+            .collect()
+    }
+
+    // Chance of a tile being picked if the position collapsed next
+    pub fn probability(&self, pos: (usize, usize), index: usize) -> f32 {
+        self.cell_distribution(pos.1, pos.0)
+            .into_iter()
+            .find(|(tile, _)| *tile == index)
+            .map_or(0.0, |(_, chance)| chance)
+    }
+
+    // Amount of tiles left at a position
+    pub fn entropy(&self, pos: (usize, usize)) -> usize {
+        self.cell_candidates[pos.0][pos.1].len()
+    }
+
+    // Image index of every cell, None for the open cells
+    pub fn cells(&self) -> Vec<Vec<Option<usize>>> {
+        self.canvas_representation
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|tile| tile.as_ref().map(|tile| tile.image_index))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Writes the image index of every collapsed cell, leaving the open cells empty
+    pub fn save_partial_canvas(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, serde_json::to_string(&self.cells())?)
+    }
+
+    // Restarts the generation from a saved canvas, keeping its collapsed cells
+    // Exported maps are read too, their layers are left to be generated again
+    // The canvas takes on the size of the saved one
+    pub fn load_partial_canvas(&mut self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let text = std::fs::read_to_string(path)?;
+        let cells: Vec<Vec<Option<usize>>> = if text.trim_start().starts_with('{') {
+            Map::parse(&text)?.cells
+        } else {
+            serde_json::from_str(&text)?
+        };
+        let height = cells.len();
+        let width = cells.first().map_or(0, Vec::len);
+        if width == 0 || cells.iter().any(|row| row.len() != width) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Canvas rows have different lengths",
+            ));
+        }
+        if let Some(missing) = cells
+            .iter()
+            .flatten()
+            .flatten()
+            .find(|index| **index >= self.current_tile_set.len())
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Canvas uses tile {missing} which isn't in the tileset"),
+            ));
+        }
+
+        if (width, height) != (self.settings.width, self.settings.height) {
+            self.settings.width = width;
+            self.settings.height = height;
+            self.mask = vec![vec![None; width]; height];
+            self.restrictions = vec![vec![None; width]; height];
+            self.forbidden = vec![vec![HashSet::new(); width]; height];
+        }
+        self.reset();
+        for (row_index, row) in cells.iter().enumerate() {
+            for (col_index, index) in row.iter().enumerate() {
+                if let Some(index) = index {
+                    let tile = self.current_tile_set[*index].clone();
+                    self.place((row_index, col_index), tile);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn settings(&self) -> GenerationSettings {
+        self.settings
+    }
+
+    // How many cells have collapsed into each tile
+    pub fn tile_usage(&self) -> Vec<usize> {
+        let mut usage = vec![0; self.current_tile_set.len()];
+        for tile in self.canvas_representation.iter().flatten().flatten() {
+            if let Some(count) = usage.get_mut(tile.image_index) {
+                *count += 1;
+            }
+        }
+        usage
+    }
+
+    // Image index of every cell, or '.' if it is not collapsed yet
+    // One row per line with the cells separated by spaces
+    pub fn export_text(&self) -> String {
+        self.canvas_representation
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|tile| match tile {
+                        Some(tile) => tile.image_index.to_string(),
+                        None => ".".to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // Observes the lowest entropy cell, collapses it and propagates the result
+    // Returns the collapsed position, or None once the board is complete
+    pub fn step(&mut self) -> Result<Option<(usize, usize)>, SolverError> {
+        if self.current_tile_set.is_empty() {
+            return Err(SolverError::EmptyTileset);
+        }
+        let started = Instant::now();
+        let lowest = self.get_lowest_entropy();
+        self.timings.entropy_scan += started.elapsed();
+        let Some(pos) = lowest else {
+            return Ok(None);
+        };
+        self.collapse_at(pos)?;
+
+        // A contradicted board is thrown away right away while there are attempts left
+        // The last attempt runs to the end so its partial result stays visible
+        if self.contradictions > 0 && self.attempt < self.settings.max_attempts {
+            self.attempt += 1;
+            self.restart();
+        }
+        Ok(Some(pos))
+    }
+
+    // Runs the generation to the end in one go
+    pub fn solve_all(&mut self) -> Result<(), SolverError> {
+        while self.step()?.is_some() {}
+        Ok(())
+    }
+
+    // Fills every open cell at once, for a quick preview of an unfinished board
+    // Each cell takes the heaviest tile that still fits, starting with the most constrained cells
+    // Ties between equally heavy tiles are broken randomly
+    // Cells where nothing fits get the heaviest tile of their region instead
+    pub fn finalize(&mut self) -> Result<(), SolverError> {
+        if self.current_tile_set.is_empty() {
+            return Err(SolverError::EmptyTileset);
+        }
+
+        while let Some(pos) = self.get_lowest_entropy() {
+            let candidates = self.candidates(pos);
+            let mut possible_tiles = self.get_possible_tiles(pos);
+            possible_tiles.retain(|index| candidates.contains(index));
+
+            if possible_tiles.is_empty() {
+                self.contradictions += 1;
+                possible_tiles = (0..self.current_tile_set.len())
+                    .filter(|&index| self.allowed_in_cell(pos, index))
+                    .collect();
+                if possible_tiles.is_empty() {
+                    possible_tiles = (0..self.current_tile_set.len()).collect();
+                }
+            }
+
+            // Tiles tied for the heaviest are drawn from the seeded generator
+            // rather than always favoring the same index
+            let max_weight = possible_tiles
+                .iter()
+                .map(|&index| self.current_tile_set[index].weight)
+                .max_by(f32::total_cmp)
+                .unwrap();
+            possible_tiles.retain(|&index| {
+                self.current_tile_set[index]
+                    .weight
+                    .total_cmp(&max_weight)
+                    .is_eq()
+            });
+            let heaviest = self.weighted_choice(&possible_tiles, pos);
+            self.place(pos, self.current_tile_set[heaviest].clone());
+        }
+        Ok(())
+    }
+
+    // Cells that haven't been collapsed yet
+    pub fn remaining_cells(&self) -> usize {
+        self.canvas_representation
+            .iter()
+            .flatten()
+            .filter(|tile| tile.is_none())
+            .count()
+    }
+
+    pub fn contradictions(&self) -> usize {
+        self.contradictions
+    }
+
+    // Time spent in each phase since the timings were last taken, starting over from zero
+    pub fn take_timings(&mut self) -> PhaseTimings {
+        std::mem::take(&mut self.timings)
+    }
+
+    // Seed the current board was started from
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    // Changes made to the board so far, so views of it only have to be worked out again when it changes
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    // Starts or stops logging the placed tiles, the log is kept either way
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn decisions(&self) -> &[(usize, usize, usize)] {
+        &self.decisions
+    }
+
+    // Restarts the board and places exactly the recorded tiles, in order, without any randomness
+    // Tiles that no longer fit still count as contradictions
+    pub fn replay(&mut self, decisions: &[(usize, usize, usize)]) -> Result<(), SolverError> {
+        self.reset();
+        for &(row, col, tile) in decisions {
+            if row >= self.settings.height
+                || col >= self.settings.width
+                || tile >= self.current_tile_set.len()
+            {
+                return Err(SolverError::InvalidDecision(row, col, tile));
+            }
+            if !self.possible_tiles((row, col)).contains(&tile) {
+                self.contradictions += 1;
+            }
+            self.place((row, col), self.current_tile_set[tile].clone());
+        }
+        Ok(())
+    }
+
+    // Places a chosen tile and propagates from it like a regular collapse
+    // A tile that doesn't fit counts as a contradiction, the same as in a replay
+    pub fn place_tile(&mut self, pos: (usize, usize), tile: usize) -> Result<(), SolverError> {
+        if pos.0 >= self.settings.height
+            || pos.1 >= self.settings.width
+            || tile >= self.current_tile_set.len()
+        {
+            return Err(SolverError::InvalidDecision(pos.0, pos.1, tile));
+        }
+        if !self.possible_tiles(pos).contains(&tile) {
+            self.contradictions += 1;
+        }
+        self.place(pos, self.current_tile_set[tile].clone());
+        Ok(())
+    }
+
+    // Runs a full generation for every seed, keeping the tiles, settings and regions
+    // Only the statistics are kept, the boards themselves are thrown away
+    pub fn run_batch(&self, seeds: &[u64]) -> Result<BatchStats, SolverError> {
+        let mut stats = BatchStats {
+            runs: seeds.len(),
+            ..Default::default()
+        };
+        let mut total_steps = 0;
+
+        for &seed in seeds {
+            let settings = GenerationSettings {
+                seed,
+                ..self.settings
+            };
+            let mut solver = Solver::new(self.current_tile_set.clone(), settings);
+            solver.mask = self.mask.clone();
+            solver.restrictions = self.restrictions.clone();
+            solver.forbidden = self.forbidden.clone();
+            solver.edge_constraints = self.edge_constraints.clone();
+            solver.reset();
+            if solver.current_tile_set.is_empty() {
+                return Err(SolverError::EmptyTileset);
+            }
+
+            while let Some(pos) = solver.get_lowest_entropy() {
+                let entropy = solver.entropy(pos);
+                if stats.entropy_histogram.len() <= entropy {
+                    stats.entropy_histogram.resize(entropy + 1, 0);
+                }
+                stats.entropy_histogram[entropy] += 1;
+                solver.collapse_at(pos)?;
+                total_steps += 1;
+            }
+
+            if solver.contradictions > 0 {
+                stats.contradicted_runs += 1;
+            }
+            stats.total_contradictions += solver.contradictions;
+        }
+
+        if !seeds.is_empty() {
+            stats.average_steps = total_steps as f32 / seeds.len() as f32;
+        }
+        Ok(stats)
+    }
+
+    // Collapses a position and propagates the result
+    fn collapse_at(&mut self, pos: (usize, usize)) -> Result<(), SolverError> {
+        let started = Instant::now();
+        let tile_connections = self.canvas_connections[pos.0][pos.1].clone();
+        let result = self.collapse_tile(&tile_connections, pos);
+        self.timings.collapse += started.elapsed();
+        self.place(pos, result?);
+
+        if cfg!(debug_assertions) {
+            self.assert_consistent();
+        }
+        Ok(())
+    }
+
+    // Position of the adjacent cell in a direction
+    // Wraps around the edges of the canvas if enabled
+    fn neighbor(&self, pos: (usize, usize), direction: Direction) -> Option<(usize, usize)> {
+        SquareGrid::new(&self.settings).neighbor(pos, direction)
+    }
+
+    // Removes all of the duplicate elements from a slice
+    // Keeps the first occurrence of each element, in order
+    fn remove_dupes(arr: &[Connection]) -> Vec<Connection> {
+        let mut seen = HashSet::with_capacity(arr.len());
+        arr.iter()
+            .filter(|elem| seen.insert(**elem))
+            .copied()
+            .collect()
+    }
+
+    // Fills every side of every cell with all the connections of the tileset
+    // The candidates of each cell are worked out from these once the constraints are propagated
+    fn fill_representation_array(&mut self) {
+        let all_connections = DIRECTIONS.map(|direction| {
+            Self::remove_dupes(
+                &self
+                    .connections
+                    .iter()
+                    .flat_map(|sides| sides[direction as usize].clone())
+                    .collect::<Vec<_>>(),
+            )
+        });
+
+        // Sides on the border of the canvas have nothing to connect to
+        self.canvas_connections =
+            vec![vec![PossibleConnections::default(); self.settings.width]; self.settings.height];
+        for row_index in 0..self.settings.height {
+            for col_index in 0..self.settings.width {
+                let pos = (row_index, col_index);
+                for (direction, connections) in DIRECTIONS.iter().zip(&all_connections) {
+                    if self.neighbor(pos, *direction).is_some() {
+                        *self.connections_in_mut(pos, *direction) = connections.clone();
+                    }
+                }
+            }
+        }
+    }
+
+    // Calculates the tile with the lowest entropy (lowest amount of possible states)
+    // Ties are broken by the noise setting, drawn from the seeded generator
+    // The collapse order setting can put the distance to the center or edges before the entropy
+    // Collapsed cells are never considered, so None means the board is complete
+    fn get_lowest_entropy(&mut self) -> Option<(usize, usize)> {
+        if let Some(pos) = self.cell_for_required_tile() {
+            return Some(pos);
+        }
+
+        let open_cells = (0..self.settings.height)
+            .flat_map(|row_index| (0..self.settings.width).map(move |col| (row_index, col)))
+            .filter(|&(row_index, col_index)| {
+                self.canvas_representation[row_index][col_index].is_none()
+            })
+            .map(|pos| {
+                (
+                    pos,
+                    self.cell_candidates[pos.0][pos.1].len(),
+                    self.collapse_bias(pos),
+                )
+            })
+            .collect::<Vec<_>>();
+        lowest_entropy(&mut self.rng, &self.settings, &open_cells)
+    }
+
+    // Tiles short of their minimum count are placed before anything else, each at a random cell that can take it,
+    // so they are spread over the board rather than bunched up where the entropy is lowest
+    // A tile no open cell can take anymore counts as a contradiction
+    fn cell_for_required_tile(&mut self) -> Option<(usize, usize)> {
+        let required = (0..self.current_tile_set.len())
+            .filter(|&index| self.short_of_minimum(index) && !self.out_of_reach.contains(&index))
+            .collect::<Vec<_>>();
+        if required.is_empty() {
+            return None;
+        }
+
+        let mut cells = Vec::new();
+        let mut reachable = HashSet::new();
+        for row_index in 0..self.settings.height {
+            for col_index in 0..self.settings.width {
+                let pos = (row_index, col_index);
+                if self.canvas_representation[row_index][col_index].is_some() {
+                    continue;
+                }
+                let candidates = self.candidates(pos);
+                let fitting = required
+                    .iter()
+                    .copied()
+                    .filter(|index| candidates.contains(index))
+                    .collect::<Vec<usize>>();
+                if !fitting.is_empty() {
+                    reachable.extend(fitting);
+                    cells.push(pos);
+                }
+            }
+        }
+
+        // The board only narrows down from here, so these tiles won't fit anywhere later either
+        if self.remaining_cells() > 0 {
+            for index in required {
+                if !reachable.contains(&index) {
+                    self.out_of_reach.insert(index);
+                    self.contradictions += 1;
+                }
+            }
+        }
+
+        if cells.is_empty() {
+            return None;
+        }
+        Some(cells[self.rng.gen_range(0..cells.len())])
+    }
+
+    // Distance that decides the collapse order before the entropy does, lower goes first
+    fn collapse_bias(&self, pos: (usize, usize)) -> f32 {
+        let (row, col) = (pos.0 as f32, pos.1 as f32);
+        let (height, width) = (self.settings.height as f32, self.settings.width as f32);
+        match self.settings.collapse_order {
+            CollapseBias::CenterOut => {
+                let (center_row, center_col) = ((height - 1.0) / 2.0, (width - 1.0) / 2.0);
+                (row - center_row).hypot(col - center_col)
+            }
+            CollapseBias::EdgesIn => row.min(col).min(height - 1.0 - row).min(width - 1.0 - col),
+            CollapseBias::CornersIn => self
+                .corners()
+                .iter()
+                .map(|&(corner_row, corner_col)| {
+                    (row - corner_row as f32).hypot(col - corner_col as f32)
+                })
+                .fold(f32::INFINITY, f32::min),
+            CollapseBias::LowestEntropy | CollapseBias::Scanline => 0.0,
+        }
+    }
+
+    // Top left, top right, bottom left and bottom right cells of the canvas
+    fn corners(&self) -> [(usize, usize); 4] {
+        let (last_row, last_col) = (
+            self.settings.height.saturating_sub(1),
+            self.settings.width.saturating_sub(1),
+        );
+        [(0, 0), (0, last_col), (last_row, 0), (last_row, last_col)]
+    }
+
+    // Tile of the first collapsed corner, for the other corners to match
+    fn corner_tile(&self, pos: (usize, usize)) -> Option<usize> {
+        let corners = self.corners();
+        if !corners.contains(&pos) {
+            return None;
+        }
+        corners
+            .iter()
+            .find_map(|corner| self.canvas_representation[corner.0][corner.1].as_ref())
+            .map(|tile| tile.image_index)
+    }
+
+    // Reads surrounding tiles and converts the entropy into a set of possible states
+    // Only tiles permitted by every collapsed neighbor are kept
+    fn get_possible_tiles(&self, pos: (usize, usize)) -> Vec<usize> {
+        let mut tiles = (0..self.current_tile_set.len()).collect::<Vec<_>>();
+        for direction in DIRECTIONS {
+            let Some(neighbor) = self.neighbor(pos, direction) else {
+                continue;
+            };
+            if let Some(tile) = &self.canvas_representation[neighbor.0][neighbor.1] {
+                tiles.retain(|&index| {
+                    tile.allows(direction.opposite(), &self.current_tile_set[index])
+                });
+            }
+        }
+
+        tiles
+    }
+
+    // Weight of a tile at a position, taking the collapsed neighbors into account
+    fn weight_at(&self, index: usize, pos: (usize, usize)) -> f32 {
+        let tile = &self.current_tile_set[index];
+        if tile.contextual_weight.is_empty() {
+            return tile.weight;
+        }
+
+        let contextual = DIRECTIONS
+            .iter()
+            .filter_map(|&direction| {
+                let neighbor = self.neighbor(pos, direction)?;
+                let other = self.canvas_representation[neighbor.0][neighbor.1].as_ref()?;
+                tile.contextual_weight.get(&(other.id, direction))
+            })
+            .collect::<Vec<_>>();
+        if contextual.is_empty() {
+            tile.weight
+        } else {
+            contextual.iter().copied().sum::<f32>() / contextual.len() as f32
+        }
+    }
+
+    // Picks one of the tiles, favoring the ones with a higher weight
+    fn weighted_choice(&mut self, tiles: &[usize], pos: (usize, usize)) -> usize {
+        let weights = tiles
+            .iter()
+            .map(|&index| self.weight_at(index, pos))
+            .collect::<Vec<_>>();
+        tiles[weighted_pick(&mut self.rng, &weights)]
+    }
+
+    // Picks the tile a position collapses into
+    fn collapse_tile(
+        &mut self,
+        tile_connections: &PossibleConnections,
+        pos: (usize, usize),
+    ) -> Result<TileData, SolverError> {
+        // Only tiles that survived propagation are considered
+        let candidates = self.candidates(pos);
+        let mut possible_tiles = self.get_possible_tiles(pos);
+        possible_tiles.retain(|index| candidates.contains(index));
+
+        if possible_tiles.is_empty() {
+            // Contradiction, nothing fits here anymore
+            self.contradictions += 1;
+            tile_connections
+                .random_tile(&self.current_tile_set, &mut self.rng)
+                .cloned()
+                .ok_or(SolverError::EmptyTileset)
+        } else {
+            // Tiles short of their minimum count take precedence wherever they fit
+            let required = possible_tiles
+                .iter()
+                .copied()
+                .filter(|&index| self.short_of_minimum(index))
+                .collect::<Vec<_>>();
+            if !required.is_empty() {
+                possible_tiles = required;
+            }
+            if self.settings.collapse_order == CollapseBias::CornersIn
+                && self.settings.matching_corners
+            {
+                if let Some(corner_tile) = self
+                    .corner_tile(pos)
+                    .filter(|index| possible_tiles.contains(index))
+                {
+                    possible_tiles = vec![corner_tile];
+                }
+            }
+            let index = self.weighted_choice(&possible_tiles, pos);
+            Ok(self.current_tile_set[index].clone())
+        }
+    }
+
+    // Commits a tile to a position
+    // Reduces the possible states (entropy) of surrounding tiles
+    // Neighbors are told which side of the image faces them, so rotated tiles are matched correctly
+    fn place(&mut self, pos: (usize, usize), most_likely_tile: TileData) {
+        if let Some(north) = self.neighbor(pos, Direction::North) {
+            let vec = vec![(
+                most_likely_tile.image_index,
+                most_likely_tile.side_facing(Direction::North),
+            )];
+            self.canvas_connections[north.0][north.1].south_connections = vec;
+        }
+        if let Some(south) = self.neighbor(pos, Direction::South) {
+            let vec = vec![(
+                most_likely_tile.image_index,
+                most_likely_tile.side_facing(Direction::South),
+            )];
+            self.canvas_connections[south.0][south.1].north_connections = vec;
+        }
+        if let Some(west) = self.neighbor(pos, Direction::West) {
+            let vec = vec![(
+                most_likely_tile.image_index,
+                most_likely_tile.side_facing(Direction::West),
+            )];
+            self.canvas_connections[west.0][west.1].east_connections = vec;
+        }
+        if let Some(east) = self.neighbor(pos, Direction::East) {
+            let vec = vec![(
+                most_likely_tile.image_index,
+                most_likely_tile.side_facing(Direction::East),
+            )];
+            self.canvas_connections[east.0][east.1].west_connections = vec;
+        }
+
+        if self.recording {
+            self.decisions
+                .push((pos.0, pos.1, most_likely_tile.image_index));
+        }
+        let index = most_likely_tile.image_index;
+        self.revision += 1;
+        self.canvas_representation[pos.0][pos.1] = Some(most_likely_tile);
+        self.cell_candidates[pos.0][pos.1] = vec![index];
+        self.propagate(pos);
+        // The sides facing the tile were narrowed above, which the propagation may not have revisited
+        for direction in DIRECTIONS {
+            if let Some(neighbor) = self.neighbor(pos, direction) {
+                self.refresh_candidates(neighbor);
+            }
+        }
+
+        // Once a tile runs out, it is taken away from every open cell that could still hold it
+        if let Some(count) = self.placed.get_mut(index) {
+            *count += 1;
+            if self.exhausted(index) {
+                for row_index in 0..self.settings.height {
+                    for col_index in 0..self.settings.width {
+                        if self.canvas_representation[row_index][col_index].is_none() {
+                            self.propagate((row_index, col_index));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn connections_in(&self, pos: (usize, usize), direction: Direction) -> &Vec<Connection> {
+        let connections = &self.canvas_connections[pos.0][pos.1];
+        match direction {
+            Direction::North => &connections.north_connections,
+            Direction::South => &connections.south_connections,
+            Direction::East => &connections.east_connections,
+            Direction::West => &connections.west_connections,
+        }
+    }
+
+    fn connections_in_mut(
+        &mut self,
+        pos: (usize, usize),
+        direction: Direction,
+    ) -> &mut Vec<Connection> {
+        let connections = &mut self.canvas_connections[pos.0][pos.1];
+        match direction {
+            Direction::North => &mut connections.north_connections,
+            Direction::South => &mut connections.south_connections,
+            Direction::East => &mut connections.east_connections,
+            Direction::West => &mut connections.west_connections,
+        }
+    }
+
+    // Tiles that can still be placed at a position
+    // A tile is allowed if it belongs to the category of the cell and the tiles it is limited to,
+    // fits the edges the cell is on, every neighbor permits it,
+    // and it permits at least one of the states left for each neighbor
+    fn candidates(&self, pos: (usize, usize)) -> Vec<usize> {
+        if let Some(tile) = &self.canvas_representation[pos.0][pos.1] {
+            return vec![tile.image_index];
+        }
+
+        let edges = self.edges_of(pos);
+        (0..self.current_tile_set.len())
+            .filter(|&index| {
+                self.allowed_in_cell(pos, index)
+                    && edges.iter().all(|allowed| allowed.contains(&index))
+            })
+            .filter(|&index| {
+                DIRECTIONS.iter().all(|&direction| {
+                    let Some(neighbor) = self.neighbor(pos, direction) else {
+                        return true;
+                    };
+                    // The listed side has to be the one this tile turns towards the neighbor
+                    let permitted_by_neighbor = self
+                        .connections_in(neighbor, direction.opposite())
+                        .iter()
+                        .any(|(other, side)| {
+                            *other == index
+                                && self.current_tile_set[index].facing(*side) == direction
+                        });
+                    let permits_neighbor =
+                        self.connections_in(pos, direction)
+                            .iter()
+                            .any(|(state, _)| {
+                                self.current_tile_set[index]
+                                    .allows(direction, &self.current_tile_set[*state])
+                            });
+                    permitted_by_neighbor && permits_neighbor
+                })
+            })
+            .collect()
+    }
+
+    // Spreads the consequences of a collapse through the board
+    // Removes every state that is no longer supported by the neighboring cells
+    // Stops at the propagation radius if there is one
+    fn propagate(&mut self, start: (usize, usize)) {
+        let started = Instant::now();
+        let mut queue = VecDeque::from([(start, 0)]);
+        let mut queued = HashSet::from([start]);
+
+        while let Some((pos, distance)) = queue.pop_front() {
+            queued.remove(&pos);
+            let candidates = self.candidates(pos);
+            self.cell_candidates[pos.0][pos.1].clone_from(&candidates);
+
+            for direction in DIRECTIONS {
+                let Some(neighbor) = self.neighbor(pos, direction) else {
+                    continue;
+                };
+
+                // What this cell still permits on that side, turned the way the connection expects
+                let tiles = &self.current_tile_set;
+                let connections = &self.connections;
+                let permitted = candidates
+                    .iter()
+                    .flat_map(|&index| {
+                        let tile = &tiles[index];
+                        connections[index][direction as usize].iter().filter(
+                            move |(other, side)| {
+                                !(tile.forbid_self_adjacency && *other == index)
+                                    && tiles[*other].facing(*side) == direction.opposite()
+                            },
+                        )
+                    })
+                    .copied()
+                    .collect::<HashSet<_>>();
+                let own_side = self.connections_in_mut(pos, direction);
+                let own_len = own_side.len();
+                own_side.retain(|connection| permitted.contains(connection));
+                let own_changed = own_side.len() != own_len;
+
+                // What the neighbor can still see on this cell
+                let neighbor_side = self.connections_in_mut(neighbor, direction.opposite());
+                let neighbor_len = neighbor_side.len();
+                neighbor_side.retain(|(index, _)| candidates.contains(index));
+                let neighbor_changed = neighbor_side.len() != neighbor_len;
+
+                if own_changed && queued.insert(pos) {
+                    queue.push_back((pos, distance));
+                }
+                let in_radius = self
+                    .settings
+                    .propagation_radius
+                    .is_none_or(|radius| distance < radius);
+                if (own_changed || neighbor_changed)
+                    && in_radius
+                    && self.canvas_representation[neighbor.0][neighbor.1].is_none()
+                    && queued.insert(neighbor)
+                {
+                    queue.push_back((neighbor, distance + 1));
+                } else if (own_changed || neighbor_changed) && !in_radius {
+                    // Past the radius the neighbor isn't propagated further, but its entropy still drops
+                    self.refresh_candidates(neighbor);
+                }
+            }
+        }
+        self.timings.propagation += started.elapsed();
+    }
+
+    // Works out the tiles an open cell can still take again
+    fn refresh_candidates(&mut self, pos: (usize, usize)) {
+        if self.canvas_representation[pos.0][pos.1].is_none() {
+            self.cell_candidates[pos.0][pos.1] = self.candidates(pos);
+        }
+    }
+
+    // Checks that the entropy of every uncollapsed cell agrees with its collapsed neighbors
+    // Panics with the offending position otherwise
+    pub fn assert_consistent(&self) {
+        for row in 0..self.settings.height {
+            for col in 0..self.settings.width {
+                let pos = (row, col);
+                if self.canvas_representation[row][col].is_some() {
+                    continue;
+                }
+
+                for direction in DIRECTIONS {
+                    let Some(neighbor) = self.neighbor(pos, direction) else {
+                        continue;
+                    };
+                    let Some(tile) = &self.canvas_representation[neighbor.0][neighbor.1] else {
+                        continue;
+                    };
+                    assert!(
+                        self.connections_in(pos, direction)
+                            .iter()
+                            .all(|(index, _)| *index == tile.image_index),
+                        "Cell {pos:?} expects something other than tile {} to its {direction}",
+                        tile.image_index
+                    );
+                }
+
+                let possible_tiles = self.get_possible_tiles(pos);
+                let candidates = self.candidates(pos);
+                for &index in &candidates {
+                    assert!(
+                        possible_tiles.contains(&index),
+                        "Cell {pos:?} allows tile {index} which its collapsed neighbors forbid"
+                    );
+                }
+                assert_eq!(
+                    self.cell_candidates[row][col], candidates,
+                    "Cell {pos:?} has stale candidates"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Connects every pair of sides whose sockets match, taking the rotation of the tiles into account
+    // Sockets are listed for the north, south, east and west sides of the unrotated images
+    fn connect_by_sockets(tiles: &mut [TileData], sockets: &[[u8; 4]]) {
+        let snapshot = tiles.to_vec();
+        for (index, tile) in tiles.iter_mut().enumerate() {
+            for direction in DIRECTIONS {
+                let own = sockets[index][tile.side_facing(direction) as usize];
+                for (other, other_tile) in snapshot.iter().enumerate() {
+                    let side = other_tile.side_facing(direction.opposite());
+                    if sockets[other][side as usize] == own {
+                        tile.valid_tiles_mut(direction).push((other_tile.id, side));
+                    }
+                }
+            }
+        }
+    }
+
+    // Tiles that fit next to each other in every way
+    fn fully_connected(count: usize) -> Vec<TileData> {
+        let mut tiles = (0..count).map(TileData::new).collect::<Vec<_>>();
+        connect_by_sockets(&mut tiles, &vec![[0; 4]; count]);
+        tiles
+    }
+
+    fn solve(tiles: Vec<TileData>, settings: GenerationSettings) -> Solver {
+        let mut solver = Solver::new(tiles, settings);
+        solver.solve_all().unwrap();
+        solver
+    }
+
+    // Runs a generation step by step, checking the cached entropy against the board after every step
+    fn solve_checked(tiles: Vec<TileData>, settings: GenerationSettings) -> Solver {
+        let mut solver = Solver::new(tiles, settings);
+        solver.assert_consistent();
+        while solver.step().unwrap().is_some() {
+            solver.assert_consistent();
+        }
+        solver
+    }
+
+    fn small_board(seed: u64, wrap: bool) -> GenerationSettings {
+        GenerationSettings {
+            width: 6,
+            height: 5,
+            seed,
+            wrap,
+            ..Default::default()
+        }
+    }
+
+    // Every pair of neighbors on the board is allowed by both tiles
+    fn assert_fits(solver: &Solver) {
+        let board = solver.representation();
+        for (row, cells) in board.iter().enumerate() {
+            for (col, tile) in cells.iter().enumerate() {
+                let tile = tile.as_ref().expect("the board is complete");
+                if let Some(Some(east)) = cells.get(col + 1) {
+                    assert!(tile.allows(Direction::East, east), "({row}, {col}) east");
+                    assert!(east.allows(Direction::West, tile), "({row}, {col}) west");
+                }
+                if let Some(Some(south)) = board.get(row + 1).map(|cells| &cells[col]) {
+                    assert!(tile.allows(Direction::South, south), "({row}, {col}) south");
+                    assert!(south.allows(Direction::North, tile), "({row}, {col}) north");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotated_tiles_solve_consistently() {
+        // The second tile is a vertical stripe once rotated, so it has to fill whole columns
+        let mut tiles = vec![TileData::new(0), TileData::new(1)];
+        tiles[1].rotation = 1;
+        connect_by_sockets(&mut tiles, &[[0, 0, 0, 0], [0, 0, 1, 1]]);
+
+        for seed in 0..20 {
+            let solver = solve(
+                tiles.clone(),
+                GenerationSettings {
+                    width: 3,
+                    height: 3,
+                    seed,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(solver.contradictions(), 0, "seed {seed}");
+            assert_fits(&solver);
+        }
+    }
+
+    #[test]
+    fn connections_to_the_wrong_side_are_ignored() {
+        // Both tiles list each other with the side an unrotated tile would face back with,
+        // which the rotated tile never turns towards its neighbors
+        let mut tiles = vec![TileData::new(0), TileData::new(1)];
+        let ids = [tiles[0].id, tiles[1].id];
+        for tile in &mut tiles {
+            for direction in DIRECTIONS {
+                *tile.valid_tiles_mut(direction) =
+                    ids.iter().map(|&id| (id, direction.opposite())).collect();
+            }
+        }
+        tiles[1].rotation = 1;
+
+        let solver = solve(
+            tiles,
+            GenerationSettings {
+                width: 3,
+                height: 3,
+                seed: 0,
+                ..Default::default()
+            },
+        );
+        assert_eq!(solver.contradictions(), 0);
+        assert_eq!(solver.tile_usage(), vec![9, 0]);
+        assert_fits(&solver);
+    }
+
+    #[test]
+    fn stays_consistent_with_rotated_tiles() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..10 {
+            let mut tiles = (0..4).map(TileData::new).collect::<Vec<_>>();
+            for tile in &mut tiles {
+                tile.rotation = rng.gen_range(0..4);
+            }
+            let sockets = (0..4)
+                .map(|_| [0; 4].map(|_: u8| rng.gen_range(0..2)))
+                .collect::<Vec<_>>();
+            connect_by_sockets(&mut tiles, &sockets);
+            for seed in 0..5 {
+                for wrap in [false, true] {
+                    solve_checked(tiles.clone(), small_board(seed, wrap));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stays_consistent_with_corner_adjacency() {
+        // Two colors in every combination, so any board can be completed
+        let mut tiles = (0..16)
+            .map(|colors: u32| {
+                let mut tile = TileData::new(colors as usize);
+                tile.corners = [0, 1, 2, 3].map(|corner| (colors >> corner) & 1);
+                tile
+            })
+            .collect::<Vec<_>>();
+        crate::tileset::connect_corners(&mut tiles);
+        for seed in 0..10 {
+            for wrap in [false, true] {
+                let solver = solve_checked(tiles.clone(), small_board(seed, wrap));
+                assert_eq!(solver.contradictions(), 0);
+                assert_fits(&solver);
+            }
+        }
+    }
+
+    #[test]
+    fn stays_consistent_with_socket_adjacency() {
+        use crate::socket::{tiles_from_sockets, Socket, SocketTile};
+
+        // A straight road and a bend, in all of their rotations, and an empty tile
+        let road = || Socket::new("road");
+        let grass = || Socket::new("grass");
+        let straight = SocketTile::new([road(), road(), grass(), grass()]);
+        let bend = SocketTile::new([road(), grass(), road(), grass()]);
+        let mut socket_tiles = vec![SocketTile::new([grass(), grass(), grass(), grass()])];
+        for quarter_turns in 0..4 {
+            socket_tiles.push(straight.rotated(quarter_turns));
+            socket_tiles.push(bend.rotated(quarter_turns));
+        }
+        let tiles = tiles_from_sockets(&socket_tiles);
+        for seed in 0..10 {
+            for wrap in [false, true] {
+                solve_checked(tiles.clone(), small_board(seed, wrap));
+            }
+        }
+    }
+
+    #[test]
+    fn removing_dupes_keeps_the_first_of_each() {
+        assert!(Solver::remove_dupes(&[]).is_empty());
+        let connections = [
+            (1, Direction::North),
+            (0, Direction::South),
+            (1, Direction::North),
+            (1, Direction::East),
+            (0, Direction::South),
+        ];
+        assert_eq!(
+            Solver::remove_dupes(&connections),
+            vec![
+                (1, Direction::North),
+                (0, Direction::South),
+                (1, Direction::East)
+            ]
+        );
+        assert_eq!(
+            Solver::remove_dupes(&[(2, Direction::West); 100]),
+            vec![(2, Direction::West)]
+        );
+    }
+
+    #[test]
+    fn random_tile_picks_from_the_listed_connections() {
+        let tiles = fully_connected(3);
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            PossibleConnections::default().random_tile(&tiles, &mut rng),
+            tiles.first()
+        );
+        assert_eq!(
+            PossibleConnections::default().random_tile(&[], &mut rng),
+            None
+        );
+
+        let connections = PossibleConnections {
+            north_connections: vec![(2, Direction::South); 4],
+            ..Default::default()
+        };
+        for _ in 0..20 {
+            let tile = connections.random_tile(&tiles, &mut rng).unwrap();
+            assert_eq!(tile.image_index, 2);
+        }
+    }
+
+    #[test]
+    fn step_collapses_every_cell_once() {
+        let mut solver = Solver::new(fully_connected(2), small_board(0, false));
+        let mut collapsed = HashSet::new();
+        while let Some(pos) = solver.step().unwrap() {
+            assert!(collapsed.insert(pos), "{pos:?} collapsed twice");
+            assert!(solver.representation()[pos.0][pos.1].is_some());
+        }
+        assert_eq!(collapsed.len(), 6 * 5);
+        assert_eq!(solver.remaining_cells(), 0);
+        assert_eq!(solver.step().unwrap(), None);
+    }
+
+    #[test]
+    fn finalize_fills_open_cells_with_the_heaviest_tile() {
+        let mut tiles = fully_connected(3);
+        tiles[1].weight = 5.0;
+        let mut solver = Solver::new(tiles, small_board(0, false));
+        for _ in 0..4 {
+            solver.step().unwrap();
+        }
+        let before = solver.cells();
+        solver.finalize().unwrap();
+
+        assert_eq!(solver.remaining_cells(), 0);
+        assert_eq!(solver.contradictions(), 0);
+        for (row, cells) in solver.cells().iter().enumerate() {
+            for (col, cell) in cells.iter().enumerate() {
+                match before[row][col] {
+                    Some(tile) => assert_eq!(*cell, Some(tile)),
+                    None => assert_eq!(*cell, Some(1)),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn replay_places_exactly_the_decisions() {
+        // The two tiles only fit next to copies of themselves
+        let mut tiles = (0..2).map(TileData::new).collect::<Vec<_>>();
+        connect_by_sockets(&mut tiles, &[[0; 4], [1; 4]]);
+        let mut solver = Solver::new(tiles, small_board(0, false));
+
+        solver.replay(&[(0, 0, 1), (0, 1, 1)]).unwrap();
+        assert_eq!(solver.cells()[0][..2], [Some(1), Some(1)]);
+        assert_eq!(solver.remaining_cells(), 6 * 5 - 2);
+        assert_eq!(solver.contradictions(), 0);
+
+        // A tile that doesn't fit is still placed, but counts as a contradiction
+        solver.replay(&[(0, 0, 1), (0, 1, 0)]).unwrap();
+        assert_eq!(solver.cells()[0][..2], [Some(1), Some(0)]);
+        assert_eq!(solver.contradictions(), 1);
+
+        assert_eq!(
+            solver.replay(&[(5, 0, 0)]),
+            Err(SolverError::InvalidDecision(5, 0, 0))
+        );
+        assert_eq!(
+            solver.replay(&[(0, 0, 2)]),
+            Err(SolverError::InvalidDecision(0, 0, 2))
+        );
+    }
+
+    #[test]
+    fn builds_grids_of_any_size() {
+        for (width, height) in [(1, 1), (100, 100)] {
+            let solver = Solver::new(
+                fully_connected(2),
+                GenerationSettings {
+                    width,
+                    height,
+                    ..Default::default()
+                },
+            );
+            let cells = solver.cells();
+            assert_eq!(cells.len(), height);
+            assert!(cells.iter().all(|row| row.len() == width));
+            assert_eq!(solver.entropy((height - 1, width - 1)), 2);
+        }
+
+        // A single cell has no neighbors to connect to
+        let solver = solve(
+            fully_connected(2),
+            GenerationSettings {
+                width: 1,
+                height: 1,
+                ..Default::default()
+            },
+        );
+        assert_eq!(solver.remaining_cells(), 0);
+    }
+
+    #[test]
+    fn removing_dupes_scales_to_large_tilesets() {
+        // Every connection twice, as after generating the rotations of a large tileset
+        let connections = (0..5000)
+            .flat_map(|index| [(index / 4, Direction::from(index % 4)); 2])
+            .collect::<Vec<_>>();
+        assert_eq!(connections.len(), 10_000);
+
+        let deduped = Solver::remove_dupes(&connections);
+
+        // The list based version this replaced
+        let mut naive = Vec::new();
+        for connection in &connections {
+            if !naive.contains(connection) {
+                naive.push(*connection);
+            }
+        }
+
+        assert_eq!(deduped, naive);
+        assert_eq!(deduped.len(), 5000);
+    }
+
+    #[test]
+    fn empty_tilesets_are_an_error() {
+        let mut solver = Solver::new(Vec::new(), small_board(0, false));
+        assert_eq!(solver.step(), Err(SolverError::EmptyTileset));
+        assert_eq!(solver.solve_all(), Err(SolverError::EmptyTileset));
+        assert_eq!(solver.finalize(), Err(SolverError::EmptyTileset));
+        assert_eq!(
+            solver.run_batch(&[0, 1]).unwrap_err(),
+            SolverError::EmptyTileset
+        );
+        assert_eq!(solver.remaining_cells(), 6 * 5);
+    }
+
+    #[test]
+    fn possible_tiles_satisfy_every_collapsed_neighbor() {
+        // A chain of three tiles, each only fitting to the east of the one before it
+        let mut tiles = (0..3).map(TileData::new).collect::<Vec<_>>();
+        for index in 0..2 {
+            let (id, next_id) = (tiles[index].id, tiles[index + 1].id);
+            tiles[index]
+                .east_valid_tiles
+                .push((next_id, Direction::West));
+            tiles[index + 1]
+                .west_valid_tiles
+                .push((id, Direction::East));
+        }
+        let strip = GenerationSettings {
+            width: 3,
+            height: 1,
+            ..Default::default()
+        };
+
+        let mut solver = Solver::new(tiles.clone(), strip);
+        solver.place_tile((0, 0), 0).unwrap();
+        assert_eq!(solver.get_possible_tiles((0, 1)), vec![1]);
+        solver.place_tile((0, 2), 2).unwrap();
+        assert_eq!(solver.get_possible_tiles((0, 1)), vec![1]);
+
+        // The middle tile can't be next to the first tile on both sides
+        let mut solver = Solver::new(tiles, strip);
+        solver.place_tile((0, 0), 0).unwrap();
+        solver.place_tile((0, 2), 0).unwrap();
+        assert!(solver.get_possible_tiles((0, 1)).is_empty());
+    }
+
+    #[test]
+    fn replaying_a_recording_gives_the_same_board() {
+        let mut tiles = (0..4).map(TileData::new).collect::<Vec<_>>();
+        connect_by_sockets(
+            &mut tiles,
+            &[[0, 0, 1, 1], [1, 1, 0, 0], [0, 1, 0, 1], [1; 4]],
+        );
+
+        let mut recorded = Solver::new(tiles.clone(), small_board(3, false));
+        recorded.set_recording(true);
+        recorded.solve_all().unwrap();
+        assert_eq!(recorded.decisions().len(), 6 * 5);
+
+        // A different seed would give a different board without the recording
+        let mut replayed = Solver::new(tiles, small_board(4, false));
+        replayed.replay(recorded.decisions()).unwrap();
+        assert_eq!(replayed.representation(), recorded.representation());
+        assert_eq!(replayed.contradictions(), recorded.contradictions());
+    }
+
+    #[test]
+    fn lowest_entropy_only_picks_open_cells() {
+        for collapse_order in CollapseBias::ALL {
+            let settings = GenerationSettings {
+                collapse_order,
+                ..small_board(0, false)
+            };
+            let mut solver = Solver::new(fully_connected(3), settings);
+            // Every cell but one is filled, the placed ones all have a single state
+            for row in 0..5 {
+                for col in 0..6 {
+                    if (row, col) != (3, 4) {
+                        solver.place_tile((row, col), 0).unwrap();
+                    }
+                }
+            }
+            for _ in 0..10 {
+                assert_eq!(
+                    solver.get_lowest_entropy(),
+                    Some((3, 4)),
+                    "{collapse_order:?}"
+                );
+            }
+            solver.place_tile((3, 4), 1).unwrap();
+            assert_eq!(solver.get_lowest_entropy(), None, "{collapse_order:?}");
+        }
+    }
+
+    #[test]
+    fn equal_candidates_are_both_picked_across_seeds() {
+        let single_cell = |seed| GenerationSettings {
+            width: 1,
+            height: 1,
+            seed,
+            ..Default::default()
+        };
+        let mut picked = [0; 2];
+        for seed in 0..100 {
+            let solver = solve(fully_connected(2), single_cell(seed));
+            picked[solver.cells()[0][0].unwrap()] += 1;
+        }
+        assert!(picked.iter().all(|&count| count > 25), "{picked:?}");
+
+        // A heavier tile is picked more often
+        let mut tiles = fully_connected(2);
+        tiles[1].weight = 9.0;
+        let heavy = (0..100)
+            .filter(|&seed| solve(tiles.clone(), single_cell(seed)).cells()[0][0] == Some(1))
+            .count();
+        assert!(heavy > 75, "{heavy}");
+    }
+
+    #[test]
+    fn fills_strips_and_rectangles() {
+        let mut tiles = (0..3).map(TileData::new).collect::<Vec<_>>();
+        connect_by_sockets(&mut tiles, &[[0, 0, 1, 1], [1, 1, 0, 0], [0; 4]]);
+        for (width, height) in [(1, 12), (12, 1), (80, 20)] {
+            let settings = GenerationSettings {
+                width,
+                height,
+                ..Default::default()
+            };
+            let solver = solve(tiles.clone(), settings);
+            assert_eq!(solver.remaining_cells(), 0, "{width}x{height}");
+            assert_eq!(solver.contradictions(), 0, "{width}x{height}");
+            assert_fits(&solver);
+
+            // The last row and column are constrained by the cells before them only
+            let corner = (height - 1, width - 1);
+            assert_eq!(solver.neighbor(corner, Direction::South), None);
+            assert_eq!(solver.neighbor(corner, Direction::East), None);
+            assert!(solver
+                .get_possible_tiles(corner)
+                .contains(&solver.cells()[height - 1][width - 1].unwrap()));
+        }
+    }
+
+    #[test]
+    fn constrained_corners_start_with_a_lower_entropy() {
+        let mut solver = Solver::new(fully_connected(4), small_board(0, false));
+        let center = (2, 3);
+        assert_eq!(solver.entropy((0, 0)), solver.entropy(center));
+
+        solver.set_edge_constraints(HashMap::from([
+            (Direction::North, vec![0, 1, 2]),
+            (Direction::West, vec![1, 2, 3]),
+        ]));
+        assert_eq!(solver.entropy(center), 4);
+        assert_eq!(solver.entropy((0, 3)), 3);
+        assert_eq!(solver.entropy((0, 0)), 2);
+        assert_eq!(solver.possible_tiles((0, 0)), vec![1, 2]);
+        assert_eq!(solver.get_lowest_entropy(), Some((0, 0)));
+    }
+
+    #[test]
+    fn corners_collapse_before_the_rest() {
+        for seed in 0..10 {
+            let settings = GenerationSettings {
+                collapse_order: CollapseBias::CornersIn,
+                matching_corners: true,
+                ..small_board(seed, false)
+            };
+            let mut solver = Solver::new(fully_connected(4), settings);
+            let mut order = Vec::new();
+            while let Some(pos) = solver.step().unwrap() {
+                order.push(pos);
+            }
+            let mut first = order[..4].to_vec();
+            first.sort();
+            assert_eq!(first, vec![(0, 0), (0, 5), (4, 0), (4, 5)], "seed {seed}");
+
+            let cells = solver.cells();
+            let corner = cells[0][0];
+            assert_eq!([cells[0][5], cells[4][0], cells[4][5]], [corner; 3]);
+        }
+    }
+
+    #[test]
+    fn uniform_tie_break_spreads_the_first_collapse() {
+        let mut first = HashMap::new();
+        for seed in 0..1600 {
+            let settings = GenerationSettings {
+                width: 4,
+                height: 4,
+                seed,
+                uniform_tie_break: true,
+                ..Default::default()
+            };
+            let mut solver = Solver::new(fully_connected(2), settings);
+            let pos = solver.step().unwrap().unwrap();
+            *first.entry(pos).or_insert(0) += 1;
+        }
+        // 100 first collapses per cell are expected
+        assert_eq!(first.len(), 16);
+        assert!(
+            first.values().all(|&count| (60..140).contains(&count)),
+            "{first:?}"
+        );
+    }
+
+    #[test]
+    fn distributions_follow_the_propagated_candidates() {
+        let mut tiles = (0..3).map(TileData::new).collect::<Vec<_>>();
+        connect_by_sockets(&mut tiles, &[[0, 0, 1, 1], [1, 1, 0, 0], [0, 1, 0, 1]]);
+        tiles[2].weight = 3.0;
+        let mut solver = Solver::new(tiles, small_board(2, false));
+        while solver.step().unwrap().is_some() {
+            for row in 0..5 {
+                for col in 0..6 {
+                    let possible_tiles = solver.possible_tiles((row, col));
+                    assert_eq!(possible_tiles, solver.candidates((row, col)));
+                    let distribution = solver.cell_distribution(col, row);
+                    assert_eq!(
+                        distribution
+                            .iter()
+                            .map(|(tile, _)| *tile)
+                            .collect::<Vec<_>>(),
+                        possible_tiles
+                    );
+                    if !distribution.is_empty() {
+                        let total = distribution.iter().map(|(_, chance)| chance).sum::<f32>();
+                        assert!((total - 1.0).abs() < 1e-4);
+                    }
+                    for (tile, chance) in distribution {
+                        assert_eq!(solver.probability((row, col), tile), chance);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn revision_follows_changes_to_the_board() {
+        let mut solver = Solver::new(fully_connected(2), small_board(0, false));
+        let start = solver.revision();
+        solver.cell_distribution(0, 0);
+        solver.possible_tiles((1, 1));
+        assert_eq!(solver.revision(), start);
+
+        solver.step().unwrap();
+        let stepped = solver.revision();
+        assert!(stepped > start);
+        solver.reset();
+        assert!(solver.revision() > stepped);
+    }
+
+    #[test]
+    fn exact_counts_are_placed_exactly() {
+        let mut tiles = fully_connected(3);
+        tiles[1].min_count = 4;
+        tiles[1].max_count = Some(4);
+        for seed in 0..20 {
+            let solver = solve_checked(tiles.clone(), small_board(seed, false));
+            assert_eq!(solver.contradictions(), 0);
+            assert_eq!(solver.tile_usage()[1], 4, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn maximum_counts_are_never_exceeded() {
+        // The capped tile is the likeliest one by far
+        let mut tiles = fully_connected(2);
+        tiles[0].weight = 100.0;
+        tiles[0].max_count = Some(3);
+        for seed in 0..20 {
+            let solver = solve_checked(tiles.clone(), small_board(seed, false));
+            assert!(solver.tile_usage()[0] <= 3, "seed {seed}");
+            assert_eq!(solver.remaining_cells(), 0);
+        }
+    }
+
+    #[test]
+    fn unreachable_minimums_are_contradictions() {
+        // The required tile is forbidden everywhere
+        let mut tiles = fully_connected(2);
+        tiles[1].min_count = 1;
+        let settings = small_board(2, false);
+        let mut solver = Solver::new(tiles, settings);
+        solver.set_forbidden(vec![
+            vec![HashSet::from([1]); settings.width];
+            settings.height
+        ]);
+        solver.solve_all().unwrap();
+
+        assert!(solver.contradictions() > 0);
+        assert_eq!(solver.tile_usage()[1], 0);
+        assert_eq!(solver.remaining_cells(), 0);
+    }
+}